@@ -0,0 +1,47 @@
+//! Captures without opening a window or initializing the renderer, e.g. on a server. Clouds
+//! are logged, saved to `cloud.ply` every 10 seconds and, with the `websocket` feature,
+//! streamed to remote viewers.
+
+use bevy::{
+    app::ScheduleRunnerPlugin, log::LogPlugin, prelude::*, time::common_conditions::on_timer,
+};
+use bevy_orbbec::{
+    ExportCloud, ExportFormat, OrbbecCapturePlugin, OrbbecExportPlugin, PointCloudReceived,
+};
+use std::time::Duration;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins((
+        // Without a window nothing paces the loop, so run it at the camera's rate.
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1.0 / 30.0,
+        ))),
+        LogPlugin::default(),
+        OrbbecCapturePlugin::default(),
+        OrbbecExportPlugin,
+    ))
+    .add_systems(
+        Update,
+        (
+            log_clouds,
+            save_cloud.run_if(on_timer(Duration::from_secs(10))),
+        ),
+    );
+    #[cfg(feature = "websocket")]
+    app.add_plugins(bevy_orbbec::WebSocketSinkPlugin::default());
+    app.run();
+}
+
+fn log_clouds(mut frames: EventReader<PointCloudReceived>) {
+    for PointCloudReceived(frame) in frames.read() {
+        debug!("Camera {} sent {} points", frame.camera, frame.points.len());
+    }
+}
+
+fn save_cloud(mut export: EventWriter<ExportCloud>) {
+    export.send(ExportCloud {
+        path: "cloud.ply".into(),
+        format: ExportFormat::Ply,
+    });
+}
@@ -0,0 +1,143 @@
+//! Removing a static background from the cloud, leaving the points that have moved.
+
+use crate::frame::receive_frames;
+use crate::orbbec::Frame;
+use bevy::{
+    math::IVec3,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+/// Send to capture the next cloud from each camera as the background reference. Replaces any
+/// reference captured before.
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct CaptureBackground;
+
+/// Removes the points of each received cloud that coincide with a captured background
+/// reference, so [`PointCloudReceived`](crate::PointCloudReceived) events only carry the
+/// foreground. Nothing is removed until a reference has been captured with
+/// [`CaptureBackground`].
+#[derive(Resource, Debug, Clone)]
+pub struct BackgroundSubtraction {
+    pub enabled: bool,
+    /// Size, in millimeters, of the cubes the reference is recorded in. Takes effect on the
+    /// next capture.
+    pub voxel_size_mm: f32,
+    /// How far, in millimeters, a point can be from the reference and still count as
+    /// background. Up to [`Self::voxel_size_mm`].
+    pub tolerance_mm: f32,
+    /// Occupied voxels of each camera's reference, keyed by [`Frame::camera`].
+    reference: HashMap<usize, HashSet<IVec3>>,
+    /// Voxel size the reference was captured with.
+    reference_voxel_size_mm: f32,
+    /// Cameras still to be captured for a requested reference.
+    capturing: Option<HashSet<usize>>,
+}
+
+impl Default for BackgroundSubtraction {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            voxel_size_mm: 20.0,
+            tolerance_mm: 10.0,
+            reference: HashMap::default(),
+            reference_voxel_size_mm: 20.0,
+            capturing: None,
+        }
+    }
+}
+
+impl BackgroundSubtraction {
+    /// Whether a background reference has been captured.
+    pub fn has_reference(&self) -> bool {
+        !self.reference.is_empty()
+    }
+
+    /// Forgets the background reference, so nothing is removed until the next capture.
+    pub fn clear_reference(&mut self) {
+        self.reference.clear();
+    }
+
+    /// Captures `frame` as its camera's reference when one was requested, or removes its
+    /// background points otherwise.
+    pub(crate) fn apply(&mut self, frame: &mut Frame) {
+        if let Some(capturing) = &mut self.capturing {
+            // The first frame from each camera after the request, ignoring the camera's
+            // earlier reference.
+            if capturing.insert(frame.camera) {
+                if capturing.len() == 1 {
+                    self.reference.clear();
+                    self.reference_voxel_size_mm = self.voxel_size_mm;
+                }
+                let voxel_size = self.reference_voxel_size_mm;
+                let voxels = frame
+                    .points
+                    .iter()
+                    .map(|(position, _)| voxel(position, voxel_size))
+                    .collect();
+                self.reference.insert(frame.camera, voxels);
+                return;
+            }
+            // Every camera has sent a frame since, so the reference is complete.
+            self.capturing = None;
+        }
+
+        if !self.enabled {
+            return;
+        }
+        let Some(reference) = self.reference.get(&frame.camera) else {
+            return;
+        };
+        let voxel_size = self.reference_voxel_size_mm;
+        let tolerance = self.tolerance_mm.clamp(0.0, voxel_size);
+        frame.retain_positions(|position| {
+            let center = voxel(position, voxel_size);
+            if reference.contains(&center) {
+                return false;
+            }
+            if tolerance == 0.0 {
+                return true;
+            }
+            // Within the tolerance of a neighboring voxel. Only the adjacent voxels can be,
+            // as the tolerance is at most a voxel.
+            let local = position - center.as_vec3() * voxel_size;
+            !neighbors().any(|offset| {
+                let min = offset.as_vec3() * voxel_size;
+                let nearest = local.clamp(min, min + voxel_size);
+                local.distance_squared(nearest) <= tolerance * tolerance
+                    && reference.contains(&(center + offset))
+            })
+        });
+    }
+}
+
+fn voxel(position: Vec3, voxel_size_mm: f32) -> IVec3 {
+    (position / voxel_size_mm).floor().as_ivec3()
+}
+
+/// Offsets of the 26 voxels around a voxel.
+fn neighbors() -> impl Iterator<Item = IVec3> {
+    (-1..=1)
+        .flat_map(|z| (-1..=1).flat_map(move |y| (-1..=1).map(move |x| IVec3::new(x, y, z))))
+        .filter(|&offset| offset != IVec3::ZERO)
+}
+
+/// Adds [`BackgroundSubtraction`], capturing its reference on [`CaptureBackground`].
+pub struct OrbbecBackgroundPlugin;
+
+impl Plugin for OrbbecBackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CaptureBackground>()
+            .init_resource::<BackgroundSubtraction>()
+            .add_systems(PreUpdate, request_capture.before(receive_frames));
+    }
+}
+
+fn request_capture(
+    mut events: EventReader<CaptureBackground>,
+    mut background: ResMut<BackgroundSubtraction>,
+) {
+    if events.read().count() > 0 {
+        background.capturing = Some(HashSet::default());
+    }
+}
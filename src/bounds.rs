@@ -0,0 +1,15 @@
+//! The extent of the displayed point cloud.
+
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, primitives::Aabb},
+};
+
+/// Axis-aligned bounding box of the displayed point cloud, in the same world units as the
+/// rendered points. Computed while the points are converted, before the cloud entity's own
+/// [`Transform`] (e.g. from [`OrbbecConfig::level_to_gravity`]) is applied. Only inserted
+/// once a non-empty cloud has been received.
+///
+/// [`OrbbecConfig::level_to_gravity`]: crate::OrbbecConfig::level_to_gravity
+#[derive(Resource, ExtractResource, Debug, Clone, Copy, Deref)]
+pub struct OrbbecBounds(pub Aabb);
@@ -0,0 +1,37 @@
+//! Helpers for pointing the app's camera at the point cloud.
+
+use crate::bounds::OrbbecBounds;
+use bevy::prelude::*;
+
+/// Moves each 3D camera so the whole cloud described by [`OrbbecBounds`] fits in view,
+/// keeping the direction it looks in. Does nothing until the bounds are known, and skips
+/// cameras that don't use a perspective projection.
+///
+/// Meant to be run once the cloud appears or on demand, e.g.
+/// `fit_camera_to_cloud.run_if(resource_added::<OrbbecBounds>)`.
+pub fn fit_camera_to_cloud(
+    bounds: Option<Res<OrbbecBounds>>,
+    mut cameras: Query<(&mut Transform, &Projection), With<Camera3d>>,
+) {
+    let Some(bounds) = bounds else {
+        return;
+    };
+    let center = Vec3::from(bounds.center);
+    let radius = Vec3::from(bounds.half_extents).length();
+
+    for (mut transform, projection) in &mut cameras {
+        let Projection::Perspective(perspective) = projection else {
+            continue;
+        };
+
+        // The narrower of the two fields of view limits how close the camera can be.
+        let half_fov_y = perspective.fov / 2.0;
+        let half_fov_x = (half_fov_y.tan() * perspective.aspect_ratio).atan();
+        let half_fov = half_fov_y.min(half_fov_x);
+        let distance = radius / half_fov.sin();
+
+        let back = transform.back();
+        *transform =
+            Transform::from_translation(center + back * distance).looking_at(center, Vec3::Y);
+    }
+}
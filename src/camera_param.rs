@@ -0,0 +1,113 @@
+//! The calibration of the open streams, for reprojecting between depth, color and 3D.
+
+#[cfg(feature = "sdk")]
+use crate::orbbec::ob;
+use crate::orbbec::OrbbecRx;
+use bevy::{math::Affine3A, prelude::*};
+
+/// Intrinsics, distortion and depth-to-color extrinsics of the streams the capture thread
+/// opened, as reported by `ob_pipeline_get_camera_param`. Inserted once the pipeline has
+/// started. Distances are in millimeters, like the SDK's points.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct OrbbecCameraParam {
+    pub depth_intrinsic: CameraIntrinsic,
+    pub color_intrinsic: CameraIntrinsic,
+    pub depth_distortion: CameraDistortion,
+    pub color_distortion: CameraDistortion,
+    /// The transform taking a point from the depth camera's coordinates to the color
+    /// camera's.
+    pub depth_to_color: Affine3A,
+    /// Whether the images are mirrored horizontally.
+    pub mirrored: bool,
+}
+
+/// A camera's focal lengths and principal point, in pixels, and the size of its images.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CameraIntrinsic {
+    pub fx: f32,
+    pub fy: f32,
+    pub cx: f32,
+    pub cy: f32,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// A camera's lens distortion, as radial coefficients `k1` to `k6` and tangential ones `p1`
+/// and `p2`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CameraDistortion {
+    pub k1: f32,
+    pub k2: f32,
+    pub k3: f32,
+    pub k4: f32,
+    pub k5: f32,
+    pub k6: f32,
+    pub p1: f32,
+    pub p2: f32,
+}
+
+#[cfg(feature = "sdk")]
+impl From<ob::ob_camera_param> for OrbbecCameraParam {
+    fn from(param: ob::ob_camera_param) -> Self {
+        let transform = &param.transform;
+        // The SDK stores the rotation row-major.
+        let rotation = Mat3::from_cols_array(&transform.rot).transpose();
+        Self {
+            depth_intrinsic: param.depthIntrinsic.into(),
+            color_intrinsic: param.rgbIntrinsic.into(),
+            depth_distortion: param.depthDistortion.into(),
+            color_distortion: param.rgbDistortion.into(),
+            depth_to_color: Affine3A::from_mat3_translation(
+                rotation,
+                Vec3::from_array(transform.trans),
+            ),
+            mirrored: param.isMirrored,
+        }
+    }
+}
+
+#[cfg(feature = "sdk")]
+impl From<ob::OBCameraIntrinsic> for CameraIntrinsic {
+    fn from(intrinsic: ob::OBCameraIntrinsic) -> Self {
+        Self {
+            fx: intrinsic.fx,
+            fy: intrinsic.fy,
+            cx: intrinsic.cx,
+            cy: intrinsic.cy,
+            // Signed in the SDK, but never negative for a calibrated stream.
+            width: intrinsic.width.max(0) as u16,
+            height: intrinsic.height.max(0) as u16,
+        }
+    }
+}
+
+#[cfg(feature = "sdk")]
+impl From<ob::OBCameraDistortion> for CameraDistortion {
+    fn from(distortion: ob::OBCameraDistortion) -> Self {
+        Self {
+            k1: distortion.k1,
+            k2: distortion.k2,
+            k3: distortion.k3,
+            k4: distortion.k4,
+            k5: distortion.k5,
+            k6: distortion.k6,
+            p1: distortion.p1,
+            p2: distortion.p2,
+        }
+    }
+}
+
+/// Inserts [`OrbbecCameraParam`] once it is received from [`OrbbecRx`].
+pub struct OrbbecCameraParamPlugin;
+
+impl Plugin for OrbbecCameraParamPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, update_camera_param);
+    }
+}
+
+fn update_camera_param(mut commands: Commands, orbbec: Res<OrbbecRx>) {
+    if let Some(param) = orbbec.try_get_camera_param() {
+        commands.insert_resource(param);
+    }
+}
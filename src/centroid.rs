@@ -0,0 +1,135 @@
+//! The center of the cloud, for following what is in front of the camera.
+
+use crate::frame::PointCloudReceived;
+use crate::orbbec::{OrbbecConfig, PointPayload};
+use crate::render::CropBox;
+use bevy::{
+    prelude::*,
+    tasks::{ComputeTaskPool, ParallelSlice},
+    utils::HashMap,
+};
+use std::ops::Range;
+
+/// Points summed by each task when averaging a cloud.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// The average position of the cloud's points passing [`CentroidSettings`], in the same world
+/// units and space as [`OrbbecBounds`](crate::OrbbecBounds). Updated by
+/// [`OrbbecCentroidPlugin`], only present while some points pass.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct OrbbecCentroid {
+    /// The centroid smoothed over the last clouds, see [`CentroidSettings::smoothing`].
+    pub position: Vec3,
+    /// The centroid of the most recent clouds alone.
+    pub latest: Vec3,
+    /// How many points the latest centroid was averaged from.
+    pub points: usize,
+}
+
+/// Which points [`OrbbecCentroidPlugin`] averages into [`OrbbecCentroid`], e.g. to leave out
+/// the background and follow the person in front of the camera.
+#[derive(Resource, Debug, Clone)]
+pub struct CentroidSettings {
+    /// Only average the points inside the [`CropBox`], when it is enabled.
+    pub within_crop_box: bool,
+    /// Only average the points within this range of depths, in millimeters.
+    pub depth_range_mm: Option<Range<f32>>,
+    /// How much of the previous centroid is kept when a cloud arrives, in `0.0..1.0`. Higher
+    /// values jitter less but lag behind movement. Defaults to `0.8`.
+    pub smoothing: f32,
+}
+
+impl Default for CentroidSettings {
+    fn default() -> Self {
+        Self {
+            within_crop_box: true,
+            depth_range_mm: None,
+            smoothing: 0.8,
+        }
+    }
+}
+
+/// Keeps [`OrbbecCentroid`] up to date with the received clouds, combining the latest cloud
+/// of every camera. The points are summed in parallel on the compute task pool.
+pub struct OrbbecCentroidPlugin;
+
+impl Plugin for OrbbecCentroidPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CentroidSettings>()
+            .add_systems(Update, update_centroid);
+    }
+}
+
+fn update_centroid(
+    mut commands: Commands,
+    mut frames: EventReader<PointCloudReceived>,
+    config: Res<OrbbecConfig>,
+    settings: Res<CentroidSettings>,
+    crop_box: Option<Res<CropBox>>,
+    centroid: Option<Res<OrbbecCentroid>>,
+    mut sums: Local<HashMap<usize, (Vec3, usize)>>,
+) {
+    let mut received = false;
+    for PointCloudReceived(frame) in frames.read() {
+        let transform = config.camera_transform(frame.camera);
+        let crop_box = crop_box.as_deref().filter(|_| settings.within_crop_box);
+        let world = |position: Vec3| {
+            let depth_range = settings.depth_range_mm.as_ref();
+            if depth_range.is_some_and(|range| !range.contains(&position.z)) {
+                return None;
+            }
+            let world = transform.transform_point(config.point_to_world(position));
+            crop_box
+                .map_or(true, |crop_box| crop_box.contains(world))
+                .then_some(world)
+        };
+        let sum = match &frame.points {
+            PointPayload::Color(points) => sum_points(points, |point| world(point.pos)),
+            PointPayload::Depth(points) => sum_points(points, |&position| world(position)),
+        };
+        sums.insert(frame.camera, sum);
+        received = true;
+    }
+    if !received {
+        return;
+    }
+
+    let (sum, points) = sums
+        .values()
+        .fold((Vec3::ZERO, 0), |(a, n), &(b, m)| (a + b, n + m));
+    if points == 0 {
+        if centroid.is_some() {
+            commands.remove_resource::<OrbbecCentroid>();
+        }
+        return;
+    }
+    let latest = sum / points as f32;
+    let position = match centroid {
+        Some(centroid) => centroid.position.lerp(latest, 1.0 - settings.smoothing),
+        None => latest,
+    };
+    commands.insert_resource(OrbbecCentroid {
+        position,
+        latest,
+        points,
+    });
+}
+
+/// Sums the world positions `world` returns for `points`, skipping those it returns `None`
+/// for, and counts them.
+fn sum_points<T: Sync>(
+    points: &[T],
+    world: impl Fn(&T) -> Option<Vec3> + Send + Sync,
+) -> (Vec3, usize) {
+    points
+        .par_chunk_map(ComputeTaskPool::get(), CHUNK_SIZE, |_, chunk| {
+            chunk
+                .iter()
+                .filter_map(&world)
+                .fold((Vec3::ZERO, 0), |(sum, count), point| {
+                    (sum + point, count + 1)
+                })
+        })
+        .into_iter()
+        .fold((Vec3::ZERO, 0), |(a, n), (b, m)| (a + b, n + m))
+}
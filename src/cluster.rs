@@ -0,0 +1,341 @@
+//! Grouping the cloud into separate objects.
+
+use crate::filter::{voxel_downsample_xyz, voxel_key};
+use crate::frame::PointCloudReceived;
+use crate::ground::{GroundPlane, GroundPlaneSettings};
+use crate::orbbec::{OrbbecConfig, RenderStyle};
+use crate::trail::TrailFrame;
+use crate::PointCloudSource;
+use bevy::{
+    color::palettes::css::{
+        DEEP_SKY_BLUE, GOLD, GRAY, HOT_PINK, LIME, ORANGE, ORCHID, SLATE_BLUE, TOMATO, TURQUOISE,
+    },
+    math::IVec3,
+    prelude::*,
+    render::primitives::Aabb,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+    utils::HashMap,
+};
+
+/// An object found by [`OrbbecClusterPlugin`].
+#[derive(Debug, Clone, Copy)]
+pub struct ObjectCluster {
+    /// Bounds of the cluster's points, in the same world units and space as
+    /// [`OrbbecBounds`](crate::OrbbecBounds).
+    pub bounds: Aabb,
+    /// How many of the downsampled points belong to the cluster.
+    pub points: usize,
+}
+
+/// The objects in the most recently clustered cloud, largest first.
+#[derive(Resource, Debug, Clone, Default, Deref)]
+pub struct ObjectClusters(pub Vec<ObjectCluster>);
+
+/// Colors of [`ColorMode::Cluster`](crate::ColorMode::Cluster), cycled through by cluster
+/// index.
+const CLUSTER_PALETTE: [Srgba; 10] = [
+    TOMATO,
+    DEEP_SKY_BLUE,
+    LIME,
+    GOLD,
+    ORCHID,
+    TURQUOISE,
+    ORANGE,
+    HOT_PINK,
+    SLATE_BLUE,
+    Srgba::WHITE,
+];
+
+/// Which of the [`ObjectClusters`] each part of the most recently clustered cloud belongs to,
+/// for [`ColorMode::Cluster`](crate::ColorMode::Cluster). Clustering works on the
+/// downsampled cloud, so points are labeled by the voxel of
+/// [`ClusterSettings::voxel_size_mm`] they fall in.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ClusterLabels {
+    /// The camera whose cloud was clustered.
+    camera: usize,
+    voxel_size_mm: f32,
+    /// The index into [`ObjectClusters`] of the cluster in each labeled voxel.
+    voxels: HashMap<IVec3, usize>,
+}
+
+impl ClusterLabels {
+    /// The index into [`ObjectClusters`] of the cluster containing a point of `camera`'s
+    /// cloud, with `position` in the SDK's millimeters. `None` for points outside any
+    /// cluster, including those of other cameras, and when the cloud wasn't downsampled.
+    pub fn cluster(&self, camera: usize, position: Vec3) -> Option<usize> {
+        if camera != self.camera || self.voxel_size_mm <= 0.0 {
+            return None;
+        }
+        self.voxels
+            .get(&voxel_key(position, self.voxel_size_mm))
+            .copied()
+    }
+}
+
+/// The color of the cluster with the given index in [`ObjectClusters`], or gray for points
+/// outside any cluster.
+pub fn cluster_color(cluster: Option<usize>) -> Color {
+    match cluster {
+        Some(index) => CLUSTER_PALETTE[index % CLUSTER_PALETTE.len()].into(),
+        None => GRAY.into(),
+    }
+}
+
+/// How [`OrbbecClusterPlugin`] groups points into [`ObjectClusters`].
+#[derive(Resource, Debug, Clone)]
+pub struct ClusterSettings {
+    /// Points closer than this, in millimeters, belong to the same cluster.
+    pub distance_threshold_mm: f32,
+    /// Clusters with fewer downsampled points are dropped as noise.
+    pub min_cluster_size: usize,
+    /// The cloud is downsampled to cubes of this size, in millimeters, before clustering.
+    /// Should be below [`Self::distance_threshold_mm`] or neighboring points won't connect.
+    pub voxel_size_mm: f32,
+    /// Leave out points on the [`GroundPlane`], when there is one, so objects standing on
+    /// the floor aren't joined through it.
+    pub ignore_ground: bool,
+    /// Draw each cluster's bounds as a gizmo.
+    pub draw_gizmos: bool,
+}
+
+impl Default for ClusterSettings {
+    fn default() -> Self {
+        Self {
+            distance_threshold_mm: 50.0,
+            min_cluster_size: 20,
+            voxel_size_mm: 20.0,
+            ignore_ground: true,
+            draw_gizmos: true,
+        }
+    }
+}
+
+/// Keeps [`ObjectClusters`] and [`ClusterLabels`] up to date with the received clouds.
+/// Clustering runs on the async compute task pool, one cloud at a time: clouds received while
+/// it is running are skipped.
+pub struct OrbbecClusterPlugin;
+
+impl Plugin for OrbbecClusterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ClusterSettings>()
+            .init_resource::<ObjectClusters>()
+            .init_resource::<ClusterLabels>()
+            .add_systems(Update, (cluster_objects, draw_clusters).chain());
+    }
+}
+
+fn cluster_objects(
+    mut frames: EventReader<PointCloudReceived>,
+    config: Res<OrbbecConfig>,
+    settings: Res<ClusterSettings>,
+    ground: Option<Res<GroundPlane>>,
+    ground_settings: Option<Res<GroundPlaneSettings>>,
+    mut clusters: ResMut<ObjectClusters>,
+    mut labels: ResMut<ClusterLabels>,
+    mut task: Local<Option<Task<(Vec<ObjectCluster>, ClusterLabels)>>>,
+) {
+    if let Some(running) = task.as_mut() {
+        let Some((found, found_labels)) = block_on(future::poll_once(running)) else {
+            frames.clear();
+            return;
+        };
+        *task = None;
+        clusters.0 = found;
+        *labels = found_labels;
+    }
+
+    let Some(PointCloudReceived(frame)) = frames.read().last() else {
+        return;
+    };
+    let frame = frame.clone();
+    let config = config.clone();
+    let settings = settings.clone();
+    // Ground points are those the ground plane's own threshold counts as on it.
+    let ground = ground
+        .filter(|_| settings.ignore_ground)
+        .zip(ground_settings)
+        .map(|(plane, ground_settings)| {
+            (
+                *plane,
+                ground_settings.inlier_threshold_mm * config.world_scale,
+            )
+        });
+    *task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        let positions: Vec<_> = frame.points.iter().map(|(position, _)| position).collect();
+        let transform = config.camera_transform(frame.camera);
+        let voxel_size_mm = settings.voxel_size_mm;
+        // Each downsampled point's voxel is kept alongside it for labeling.
+        let (voxels, points): (Vec<_>, Vec<_>) = voxel_downsample_xyz(&positions, voxel_size_mm)
+            .into_iter()
+            .map(|position| {
+                let world = transform.transform_point(config.point_to_world(position));
+                (voxel_key(position, voxel_size_mm), world)
+            })
+            .filter(|&(_, point)| {
+                ground.map_or(true, |(plane, threshold)| {
+                    plane.distance(point).abs() > threshold
+                })
+            })
+            .unzip();
+        let (clusters, point_labels) = euclidean_cluster_labels(
+            &points,
+            settings.distance_threshold_mm * config.world_scale,
+            settings.min_cluster_size,
+        );
+        let labels = ClusterLabels {
+            camera: frame.camera,
+            voxel_size_mm,
+            voxels: voxels
+                .into_iter()
+                .zip(point_labels)
+                .filter_map(|(voxel, label)| Some((voxel, label?)))
+                .collect(),
+        };
+        (clusters, labels)
+    }));
+}
+
+fn draw_clusters(
+    settings: Res<ClusterSettings>,
+    clusters: Res<ObjectClusters>,
+    labels: Res<ClusterLabels>,
+    clouds: Query<
+        (&GlobalTransform, Option<&PointCloudSource>),
+        (With<RenderStyle>, Without<TrailFrame>),
+    >,
+    mut gizmos: Gizmos,
+) {
+    if !settings.draw_gizmos {
+        return;
+    }
+    // The clusters are in the space of the cloud they were found in, so follow the transform
+    // of that camera's cloud entity, or of the cloud showing every camera.
+    let cloud = clouds
+        .iter()
+        .find(|(_, source)| source.map_or(true, |source| source.0 == labels.camera))
+        .map_or_else(GlobalTransform::default, |(transform, _)| *transform);
+    for cluster in clusters.iter() {
+        let transform = Transform::from_translation(cluster.bounds.center.into())
+            .with_scale(Vec3::from(cluster.bounds.half_extents) * 2.0);
+        gizmos.cuboid(cloud * transform, ORANGE);
+    }
+}
+
+/// Groups points that are within `threshold` of each other, directly or through other
+/// points, and returns the bounds of each group with at least `min_size` points, largest
+/// first.
+pub fn euclidean_clusters(points: &[Vec3], threshold: f32, min_size: usize) -> Vec<ObjectCluster> {
+    euclidean_cluster_labels(points, threshold, min_size).0
+}
+
+/// [`euclidean_clusters`], also returning the index of the cluster each point belongs to, or
+/// `None` for points in groups smaller than `min_size`.
+pub fn euclidean_cluster_labels(
+    points: &[Vec3],
+    threshold: f32,
+    min_size: usize,
+) -> (Vec<ObjectCluster>, Vec<Option<usize>>) {
+    if threshold <= 0.0 {
+        return (Vec::new(), vec![None; points.len()]);
+    }
+
+    // Neighbors are found in the 27 cells of size `threshold` around each point.
+    let cell = |point: Vec3| (point / threshold).floor().as_ivec3();
+    let mut grid: HashMap<IVec3, Vec<usize>> = HashMap::default();
+    for (index, &point) in points.iter().enumerate() {
+        grid.entry(cell(point)).or_default().push(index);
+    }
+
+    let mut sets = DisjointSets::new(points.len());
+    let threshold_squared = threshold * threshold;
+    for (index, &point) in points.iter().enumerate() {
+        let center = cell(point);
+        for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let Some(neighbors) = grid.get(&(center + IVec3::new(x, y, z))) else {
+                        continue;
+                    };
+                    // Each pair only needs joining once.
+                    for &neighbor in neighbors.iter().filter(|&&neighbor| neighbor > index) {
+                        if point.distance_squared(points[neighbor]) <= threshold_squared {
+                            sets.union(index, neighbor);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let roots: Vec<_> = (0..points.len()).map(|index| sets.find(index)).collect();
+    let mut clusters: HashMap<usize, (Vec3, Vec3, usize)> = HashMap::default();
+    for (&root, &point) in roots.iter().zip(points) {
+        let (min, max, count) = clusters.entry(root).or_insert((Vec3::MAX, Vec3::MIN, 0));
+        *min = min.min(point);
+        *max = max.max(point);
+        *count += 1;
+    }
+
+    let mut clusters: Vec<_> = clusters
+        .into_iter()
+        .filter(|&(_, (_, _, count))| count >= min_size)
+        .map(|(root, (min, max, points))| {
+            let cluster = ObjectCluster {
+                bounds: Aabb::from_min_max(min, max),
+                points,
+            };
+            (root, cluster)
+        })
+        .collect();
+    clusters.sort_by(|(_, a), (_, b)| b.points.cmp(&a.points));
+
+    let indices: HashMap<usize, usize> = clusters
+        .iter()
+        .enumerate()
+        .map(|(index, &(root, _))| (root, index))
+        .collect();
+    let labels = roots
+        .iter()
+        .map(|root| indices.get(root).copied())
+        .collect();
+    (
+        clusters.into_iter().map(|(_, cluster)| cluster).collect(),
+        labels,
+    )
+}
+
+/// Union-find over point indices, with path halving and union by size.
+struct DisjointSets {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+            sizes: vec![1; len],
+        }
+    }
+
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.sizes[a] < self.sizes[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parents[b] = a;
+        self.sizes[a] += self.sizes[b];
+    }
+}
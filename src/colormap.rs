@@ -0,0 +1,27 @@
+//! Colormaps for visualizing scalar values such as depth.
+
+/// Google's Turbo colormap, using their polynomial approximation. `t` is clamped to
+/// `0.0..=1.0` and the result is sRGB in `0.0..=1.0`.
+pub fn turbo(t: f32) -> [f32; 3] {
+    const RED4: [f32; 4] = [0.1357214, 4.615393, -42.66032, 132.1311];
+    const GREEN4: [f32; 4] = [0.09140261, 2.194188, 4.842967, -14.18503];
+    const BLUE4: [f32; 4] = [0.1066733, 12.64195, -60.58205, 110.3628];
+    const RED2: [f32; 2] = [-152.9424, 59.28638];
+    const GREEN2: [f32; 2] = [4.277299, 2.829566];
+    const BLUE2: [f32; 2] = [-89.90311, 27.34825];
+
+    let x = t.clamp(0.0, 1.0);
+    let v4 = [1.0, x, x * x, x * x * x];
+    let v2 = [v4[2] * v4[2], v4[3] * v4[2]];
+    let channel = |c4: [f32; 4], c2: [f32; 2]| {
+        let value = v4.iter().zip(c4).map(|(v, c)| v * c).sum::<f32>()
+            + v2.iter().zip(c2).map(|(v, c)| v * c).sum::<f32>();
+        value.clamp(0.0, 1.0)
+    };
+
+    [
+        channel(RED4, RED2),
+        channel(GREEN4, GREEN2),
+        channel(BLUE4, BLUE2),
+    ]
+}
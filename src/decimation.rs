@@ -0,0 +1,113 @@
+//! Trading cloud density for frame rate, by adjusting the voxel size the capture thread
+//! downsamples with.
+
+use crate::orbbec::{OrbbecConfig, OrbbecRx};
+use crate::stats::OrbbecStats;
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// How [`OrbbecDecimationPlugin`] adjusts the voxel size to hold [`Self::target_fps`].
+///
+/// With vsync the frame rate can't rise above the display's refresh rate, so there is never
+/// headroom to lower the voxel size at a target equal to it. Aim a little below instead.
+#[derive(Resource, Debug, Clone)]
+pub struct AdaptiveDecimation {
+    /// Turn off to go back to [`OrbbecConfig::voxel_size_mm`].
+    pub enabled: bool,
+    /// The app frame rate to hold, as measured by [`OrbbecStats::app_fps`].
+    pub target_fps: f32,
+    /// How far, as a fraction of [`Self::target_fps`], the frame rate may stray either side
+    /// of it before the voxel size is changed. Keeps the voxel size from flipping back and
+    /// forth around the target.
+    pub tolerance: f32,
+    /// The finest voxel size, in millimeters. Zero keeps every point.
+    pub min_voxel_mm: f32,
+    /// The coarsest voxel size, in millimeters.
+    pub max_voxel_mm: f32,
+    /// How much the voxel size changes at a time, in millimeters.
+    pub step_mm: f32,
+    /// How long to wait after a change before the next one, giving the frame rate time to
+    /// settle.
+    pub interval: Duration,
+    /// The voxel size currently applied, `None` until the first adjustment.
+    voxel_size_mm: Option<f32>,
+}
+
+impl Default for AdaptiveDecimation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            target_fps: 55.0,
+            tolerance: 0.05,
+            min_voxel_mm: 0.0,
+            max_voxel_mm: 50.0,
+            step_mm: 2.0,
+            interval: Duration::from_millis(500),
+            voxel_size_mm: None,
+        }
+    }
+}
+
+impl AdaptiveDecimation {
+    /// The voxel size currently applied, in millimeters, or `None` while the config's is.
+    pub fn voxel_size_mm(&self) -> Option<f32> {
+        self.voxel_size_mm
+    }
+}
+
+/// Coarsens the cloud when the app falls below [`AdaptiveDecimation::target_fps`] and refines
+/// it again when there is headroom, see [`OrbbecRx::set_voxel_size_mm`]. Requires
+/// [`OrbbecStatsPlugin`](crate::OrbbecStatsPlugin), which [`OrbbecPlugin`](crate::OrbbecPlugin)
+/// adds.
+pub struct OrbbecDecimationPlugin;
+
+impl Plugin for OrbbecDecimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AdaptiveDecimation>()
+            .add_systems(Update, adapt_decimation);
+    }
+}
+
+fn adapt_decimation(
+    time: Res<Time>,
+    orbbec: Res<OrbbecRx>,
+    config: Res<OrbbecConfig>,
+    stats: Res<OrbbecStats>,
+    mut decimation: ResMut<AdaptiveDecimation>,
+    mut since_change: Local<Duration>,
+) {
+    if !decimation.enabled {
+        if decimation.voxel_size_mm.take().is_some() {
+            orbbec.set_voxel_size_mm(None);
+        }
+        return;
+    }
+
+    *since_change += time.delta();
+    if *since_change < decimation.interval || stats.app_fps == 0.0 {
+        return;
+    }
+
+    let min = decimation.min_voxel_mm;
+    let max = decimation.max_voxel_mm.max(min);
+    let current = decimation
+        .voxel_size_mm
+        .or(config.voxel_size_mm)
+        .unwrap_or(0.0)
+        .clamp(min, max);
+    let target = decimation.target_fps;
+    let next = if stats.app_fps < target * (1.0 - decimation.tolerance) {
+        current + decimation.step_mm
+    } else if stats.app_fps > target * (1.0 + decimation.tolerance) {
+        current - decimation.step_mm
+    } else {
+        current
+    }
+    .clamp(min, max);
+
+    if decimation.voxel_size_mm != Some(next) {
+        decimation.voxel_size_mm = Some(next);
+        orbbec.set_voxel_size_mm(Some(next));
+        *since_change = Duration::ZERO;
+    }
+}
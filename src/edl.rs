@@ -0,0 +1,271 @@
+//! Eye-dome lighting, shading the rendered cloud by its depth alone.
+
+use bevy::{
+    asset::embedded_asset,
+    core_pipeline::{
+        core_3d::graph::{Core3d, Node3d},
+        fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    },
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::{
+            ComponentUniforms, DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin,
+            UniformComponentPlugin,
+        },
+        render_graph::{
+            NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_resource::{
+            binding_types::{
+                texture_2d, texture_depth_2d, texture_depth_2d_multisampled, uniform_buffer,
+            },
+            *,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::BevyDefault,
+        view::{ExtractedView, ViewDepthTexture, ViewTarget},
+        Render, RenderApp, RenderSet,
+    },
+};
+
+/// Add to a 3D camera to darken each pixel by how much closer its neighbors are, outlining
+/// the silhouettes in the cloud and giving it a relief-like look without normals. Applied as
+/// a post-process to everything the camera renders, before tonemapping.
+///
+/// The camera's depth texture is made bindable when this is added, see
+/// [`Camera3d::depth_texture_usages`].
+#[derive(Component, ShaderType, Debug, Clone, Copy)]
+pub struct EyeDomeLighting {
+    /// How dark occluded edges get, `0.0` disables the effect.
+    pub strength: f32,
+    /// Distance, in physical pixels, to the neighbors each pixel is compared with. Larger
+    /// radii give thicker outlines.
+    pub radius: f32,
+}
+
+impl Default for EyeDomeLighting {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            radius: 1.5,
+        }
+    }
+}
+
+impl ExtractComponent for EyeDomeLighting {
+    type QueryData = &'static EyeDomeLighting;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        Some(*item)
+    }
+}
+
+/// Applies [`EyeDomeLighting`] to the cameras that have it.
+pub struct EyeDomeLightingPlugin;
+
+impl Plugin for EyeDomeLightingPlugin {
+    fn build(&self, app: &mut App) {
+        embedded_asset!(app, "shaders/edl.wgsl");
+
+        app.add_plugins((
+            ExtractComponentPlugin::<EyeDomeLighting>::default(),
+            UniformComponentPlugin::<EyeDomeLighting>::default(),
+        ))
+        .add_systems(PostUpdate, bind_depth_texture);
+
+        app.sub_app_mut(RenderApp)
+            .init_resource::<SpecializedRenderPipelines<EdlPipeline>>()
+            .add_systems(Render, prepare_edl_pipelines.in_set(RenderSet::Prepare))
+            .add_render_graph_node::<ViewNodeRunner<EdlNode>>(Core3d, EdlLabel)
+            .add_render_graph_edges(Core3d, (Node3d::EndMainPass, EdlLabel, Node3d::Tonemapping));
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp).init_resource::<EdlPipeline>();
+    }
+}
+
+/// The shader reads the main pass' depth, which by default is only a render attachment.
+fn bind_depth_texture(mut cameras: Query<&mut Camera3d, Added<EyeDomeLighting>>) {
+    for mut camera in &mut cameras {
+        camera.depth_texture_usages =
+            (TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING).into();
+    }
+}
+
+#[derive(Resource)]
+struct EdlPipeline {
+    shader: Handle<Shader>,
+    layout: BindGroupLayout,
+    /// With MSAA the depth texture is multisampled, and needs its own binding type.
+    multisampled_layout: BindGroupLayout,
+}
+
+impl FromWorld for EdlPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = |label, depth| {
+            render_device.create_bind_group_layout(
+                label,
+                &BindGroupLayoutEntries::sequential(
+                    ShaderStages::FRAGMENT,
+                    (
+                        texture_2d(TextureSampleType::Float { filterable: false }),
+                        depth,
+                        uniform_buffer::<EyeDomeLighting>(true),
+                    ),
+                ),
+            )
+        };
+
+        Self {
+            shader: world.load_asset("embedded://bevy_orbbec/shaders/edl.wgsl"),
+            layout: layout("edl layout", texture_depth_2d()),
+            multisampled_layout: layout("edl multisampled layout", texture_depth_2d_multisampled()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct EdlPipelineKey {
+    hdr: bool,
+    multisampled: bool,
+}
+
+impl EdlPipeline {
+    fn layout(&self, multisampled: bool) -> &BindGroupLayout {
+        if multisampled {
+            &self.multisampled_layout
+        } else {
+            &self.layout
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for EdlPipeline {
+    type Key = EdlPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut shader_defs = Vec::new();
+        if key.multisampled {
+            shader_defs.push("MULTISAMPLED".into());
+        }
+
+        RenderPipelineDescriptor {
+            label: Some("edl pipeline".into()),
+            layout: vec![self.layout(key.multisampled).clone()],
+            push_constant_ranges: Vec::new(),
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs,
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: if key.hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+        }
+    }
+}
+
+/// The pipeline specialized for a view.
+#[derive(Component)]
+struct ViewEdlPipeline {
+    id: CachedRenderPipelineId,
+    multisampled: bool,
+}
+
+fn prepare_edl_pipelines(
+    mut commands: Commands,
+    views: Query<(Entity, &ExtractedView), With<EyeDomeLighting>>,
+    edl_pipeline: Res<EdlPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<EdlPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    msaa: Res<Msaa>,
+) {
+    for (entity, view) in &views {
+        let key = EdlPipelineKey {
+            hdr: view.hdr,
+            multisampled: msaa.samples() > 1,
+        };
+        let id = pipelines.specialize(&pipeline_cache, &edl_pipeline, key);
+        commands.entity(entity).insert(ViewEdlPipeline {
+            id,
+            multisampled: key.multisampled,
+        });
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct EdlLabel;
+
+#[derive(Default)]
+struct EdlNode;
+
+impl ViewNode for EdlNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static ViewDepthTexture,
+        &'static ViewEdlPipeline,
+        &'static DynamicUniformIndex<EyeDomeLighting>,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view_target, depth, view_pipeline, settings_index): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let edl_pipeline = world.resource::<EdlPipeline>();
+        let Some(pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(view_pipeline.id)
+        else {
+            return Ok(());
+        };
+        let Some(settings) = world
+            .resource::<ComponentUniforms<EyeDomeLighting>>()
+            .uniforms()
+            .binding()
+        else {
+            return Ok(());
+        };
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "edl bind group",
+            edl_pipeline.layout(view_pipeline.multisampled),
+            &BindGroupEntries::sequential((post_process.source, depth.view(), settings)),
+        );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("edl pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[settings_index.index()]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
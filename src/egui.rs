@@ -0,0 +1,231 @@
+//! A debug panel for inspecting and tuning the camera at runtime, built with `bevy_egui`.
+
+use crate::orbbec::{ConnectionState, OrbbecConfig, OrbbecRx, StreamProfile};
+use crate::stats::OrbbecStats;
+use crate::temperature::OrbbecTemperature;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+
+/// Shown on controls for properties the device doesn't report.
+const UNSUPPORTED: &str = "Not supported by this device";
+
+/// Whether [`OrbbecEguiPlugin`] shows its panel.
+#[derive(Resource, Debug, Clone)]
+pub struct OrbbecPanel {
+    pub open: bool,
+}
+
+impl Default for OrbbecPanel {
+    fn default() -> Self {
+        Self { open: true }
+    }
+}
+
+/// Shows a window with the device, its stream profiles, [`OrbbecStats`] and
+/// [`OrbbecTemperature`], with controls for exposure, gain, the laser and the filters. Changes
+/// are applied to the [`OrbbecConfig`] resource and pushed to the capture threads with
+/// [`OrbbecRx::reconfigure`], which restarts capture once they settle.
+///
+/// Adds [`EguiPlugin`] unless it was already added.
+pub struct OrbbecEguiPlugin;
+
+impl Plugin for OrbbecEguiPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        app.init_resource::<OrbbecPanel>()
+            .add_systems(Update, show_panel);
+    }
+}
+
+fn show_panel(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<OrbbecPanel>,
+    orbbec: Res<OrbbecRx>,
+    mut config: ResMut<OrbbecConfig>,
+    state: Option<Res<ConnectionState>>,
+    stats: Option<Res<OrbbecStats>>,
+    temperature: Option<Res<OrbbecTemperature>>,
+) {
+    if !panel.open {
+        return;
+    }
+
+    let mut edited = config.clone();
+    let mut changed = false;
+    egui::Window::new("Orbbec")
+        .open(&mut panel.open)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.collapsing("Device", |ui| {
+                let state = state.as_deref().copied().unwrap_or_default();
+                ui.label(format!("State: {state:?}"));
+                let Some(device) = orbbec.device() else {
+                    ui.label("No device info yet");
+                    return;
+                };
+                egui::Grid::new("device").show(ui, |ui| {
+                    ui.label("Model");
+                    ui.label(&device.name);
+                    ui.end_row();
+                    ui.label("Serial");
+                    ui.label(&device.serial_number);
+                    ui.end_row();
+                    ui.label("Firmware");
+                    ui.label(&device.firmware_version);
+                    ui.end_row();
+                    ui.label("Color");
+                    ui.label(profile_label(device.color_profile));
+                    ui.end_row();
+                    ui.label("Depth");
+                    ui.label(profile_label(device.depth_profile));
+                    ui.end_row();
+                });
+            });
+
+            ui.collapsing("Stats", |ui| {
+                let Some(stats) = stats.as_deref() else {
+                    ui.label("Requires OrbbecStatsPlugin");
+                    return;
+                };
+                egui::Grid::new("stats").show(ui, |ui| {
+                    ui.label("Cloud fps");
+                    ui.label(format!("{:.1}", stats.smoothed_fps));
+                    ui.end_row();
+                    ui.label("App fps");
+                    ui.label(format!("{:.1}", stats.app_fps));
+                    ui.end_row();
+                    ui.label("Points");
+                    ui.label(format!("{:.0}", stats.smoothed_point_count));
+                    ui.end_row();
+                    ui.label("Dropped frames");
+                    ui.label(stats.dropped_frames.to_string());
+                    ui.end_row();
+                    ui.label("Latency");
+                    ui.label(format!("{:.0?}", stats.latest_frame_age));
+                    ui.end_row();
+                });
+            });
+
+            ui.collapsing("Temperature", |ui| match temperature.as_deref() {
+                Some(temperature) => {
+                    egui::Grid::new("temperature").show(ui, |ui| {
+                        for (name, value) in [
+                            ("CPU", temperature.cpu),
+                            ("IR", temperature.ir),
+                            ("IR left", temperature.ir_left),
+                            ("IR right", temperature.ir_right),
+                            ("Laser", temperature.laser),
+                            ("Main board", temperature.main_board),
+                            ("TEC", temperature.tec),
+                            ("IMU", temperature.imu),
+                            ("Color", temperature.color),
+                            ("Chip top", temperature.chip_top),
+                            ("Chip bottom", temperature.chip_bottom),
+                        ] {
+                            if let Some(value) = value {
+                                ui.label(name);
+                                ui.label(format!("{value:.1} °C"));
+                                ui.end_row();
+                            }
+                        }
+                    });
+                }
+                None => {
+                    ui.label(UNSUPPORTED);
+                }
+            });
+
+            ui.collapsing("Color", |ui| {
+                // The device reports its exposure settings when capture starts, so a control
+                // it reported nothing for is unlikely to take effect.
+                let controls = orbbec.color_controls();
+                changed |= optional_slider(
+                    ui,
+                    "Exposure",
+                    &mut edited.color_exposure,
+                    controls.exposure,
+                    1..=10000,
+                );
+                changed |=
+                    optional_slider(ui, "Gain", &mut edited.color_gain, controls.gain, 0..=128);
+            });
+
+            ui.collapsing("Laser", |ui| {
+                changed |= ui.checkbox(&mut edited.laser_enabled, "Enabled").changed();
+                // The device's power isn't read back, so start from the lowest level.
+                changed |= optional_slider(ui, "Power", &mut edited.laser_power, Some(1), 0..=6);
+            });
+
+            ui.collapsing("Filters", |ui| {
+                changed |= optional_slider(
+                    ui,
+                    "Min depth (mm)",
+                    &mut edited.depth_min_mm,
+                    Some(0.0),
+                    0.0..=10000.0,
+                );
+                changed |= optional_slider(
+                    ui,
+                    "Max depth (mm)",
+                    &mut edited.depth_max_mm,
+                    Some(10000.0),
+                    0.0..=10000.0,
+                );
+                changed |= optional_slider(
+                    ui,
+                    "Voxel size (mm)",
+                    &mut edited.voxel_size_mm,
+                    Some(5.0),
+                    1.0..=100.0,
+                );
+                changed |= ui
+                    .add(egui::Slider::new(&mut edited.subsample, 1..=16).text("Subsample"))
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::Slider::new(&mut edited.min_confidence, 0.0..=1.0)
+                            .text("Min confidence"),
+                    )
+                    .changed();
+            });
+        });
+
+    if changed {
+        orbbec.reconfigure(edited.clone());
+        *config = edited;
+    }
+}
+
+fn profile_label(profile: Option<StreamProfile>) -> String {
+    profile.map_or_else(|| "None".to_string(), |profile| profile.to_string())
+}
+
+/// A checkbox turning an optional setting on, starting from `initial`, and a slider for its
+/// value. Both are disabled when `initial` is `None`, i.e. the device doesn't report the
+/// property, unless it is already set. Returns whether the setting changed.
+fn optional_slider<T: egui::emath::Numeric>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut Option<T>,
+    initial: Option<T>,
+    range: std::ops::RangeInclusive<T>,
+) -> bool {
+    let supported = value.is_some() || initial.is_some();
+    let response = ui.add_enabled_ui(supported, |ui| {
+        ui.horizontal(|ui| {
+            let mut enabled = value.is_some();
+            let mut changed = ui.checkbox(&mut enabled, label).changed();
+            if changed {
+                *value = if enabled { initial } else { None };
+            }
+            if let Some(value) = value {
+                changed |= ui.add(egui::Slider::new(value, range)).changed();
+            }
+            changed
+        })
+        .inner
+    });
+    response.response.on_disabled_hover_text(UNSUPPORTED);
+    response.inner
+}
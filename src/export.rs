@@ -0,0 +1,649 @@
+//! Saving point clouds to files for use in tools like MeshLab or CloudCompare, loading them
+//! back, and turning them into meshes.
+
+use crate::frame::PointCloudReceived;
+use crate::orbbec::{ColorPoint, Frame, OrbbecConfig, PointPayload};
+use crate::render::{InstanceData, InstanceMaterialData};
+use crate::trail::TrailFrame;
+use bevy::{
+    prelude::*,
+    render::{mesh::PrimitiveTopology, primitives::Aabb, render_asset::RenderAssetUsages},
+};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Writes `points` to a binary PLY file with a float position and byte color per vertex.
+/// Positions are multiplied by `scale`.
+pub fn save_ply(path: impl AsRef<Path>, points: &[ColorPoint], scale: f32) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write!(
+        writer,
+        "ply\n\
+         format binary_little_endian 1.0\n\
+         element vertex {}\n\
+         property float x\n\
+         property float y\n\
+         property float z\n\
+         property uchar red\n\
+         property uchar green\n\
+         property uchar blue\n\
+         end_header\n",
+        points.len()
+    )?;
+    for point in points {
+        for v in (point.pos * scale).to_array() {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        writer.write_all(&point.color)?;
+    }
+    writer.flush()
+}
+
+/// Writes `points` to a binary PCD file with `x y z rgb` fields, as read by PCL. Positions are
+/// multiplied by `scale`.
+pub fn save_pcd(path: impl AsRef<Path>, points: &[ColorPoint], scale: f32) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write!(
+        writer,
+        "# .PCD v0.7 - Point Cloud Data file format\n\
+         VERSION 0.7\n\
+         FIELDS x y z rgb\n\
+         SIZE 4 4 4 4\n\
+         TYPE F F F F\n\
+         COUNT 1 1 1 1\n\
+         WIDTH {0}\n\
+         HEIGHT 1\n\
+         VIEWPOINT 0 0 0 1 0 0 0\n\
+         POINTS {0}\n\
+         DATA binary\n",
+        points.len()
+    )?;
+    for point in points {
+        for v in (point.pos * scale).to_array() {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        // PCL packs the color into the bits of a float as 0x00RRGGBB.
+        let [r, g, b] = point.color;
+        writer.write_all(&u32::from_be_bytes([0, r, g, b]).to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+/// Reads the points of a PLY or PCD file, picked by the file's extension. ASCII and binary
+/// little-endian files are supported, such as those written by [`save_ply`] and [`save_pcd`].
+/// Files without colors load as [`PointPayload::Depth`].
+pub fn load_points(path: impl AsRef<Path>) -> io::Result<PointPayload> {
+    let path = path.as_ref();
+    let data = std::fs::read(path)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("ply") => parse_ply(&data),
+        Some(ext) if ext.eq_ignore_ascii_case("pcd") => parse_pcd(&data),
+        _ => Err(invalid_data(format!(
+            "{} is not a .ply or .pcd file",
+            path.display()
+        ))),
+    }
+}
+
+fn parse_ply(data: &[u8]) -> io::Result<PointPayload> {
+    let mut body = data;
+    if next_line(&mut body)? != "ply" {
+        return Err(invalid_data("Not a PLY file"));
+    }
+
+    let mut binary = false;
+    let mut count = None;
+    let mut in_vertex = false;
+    let mut fields = Vec::new();
+    loop {
+        let line = next_line(&mut body)?;
+        let mut words = line.split_ascii_whitespace();
+        match words.next() {
+            Some("format") => {
+                binary = match words.next() {
+                    Some("ascii") => false,
+                    Some("binary_little_endian") => true,
+                    format => {
+                        return Err(invalid_data(format!("Unsupported PLY format {format:?}")))
+                    }
+                }
+            }
+            // Elements after the vertices, such as faces, are ignored.
+            Some("element") if count.is_some() => in_vertex = false,
+            Some("element") => match (words.next(), words.next()) {
+                (Some("vertex"), Some(n)) => {
+                    count = Some(parse_count(n)?);
+                    in_vertex = true;
+                }
+                _ => return Err(invalid_data("PLY vertices must be the first element")),
+            },
+            Some("property") if in_vertex => {
+                let (Some(ty), Some(name)) = (words.next(), words.next()) else {
+                    return Err(invalid_data(format!("Invalid PLY property {line:?}")));
+                };
+                let ty = Scalar::from_ply(ty)
+                    .ok_or_else(|| invalid_data(format!("Unsupported PLY property {line:?}")))?;
+                fields.push((name, ty));
+            }
+            Some("end_header") => break,
+            // Comments, obj_info and properties of other elements.
+            _ => {}
+        }
+    }
+
+    let count = count.ok_or_else(|| invalid_data("PLY file has no vertices"))?;
+    read_points(body, &fields, count, binary, ["red", "green", "blue"])
+}
+
+fn parse_pcd(data: &[u8]) -> io::Result<PointPayload> {
+    let mut body = data;
+    let (mut names, mut sizes, mut types, mut counts) = (vec![], vec![], vec![], vec![]);
+    let mut count = None;
+    let binary = loop {
+        let line = next_line(&mut body)?;
+        let mut words = line.split_ascii_whitespace();
+        let key = words.next();
+        let values: Vec<_> = words.collect();
+        match key {
+            Some("FIELDS") => names = values,
+            Some("SIZE") => sizes = values,
+            Some("TYPE") => types = values,
+            Some("COUNT") => counts = values,
+            Some("POINTS") => count = values.first().map(|n| parse_count(n)).transpose()?,
+            Some("DATA") => match values.first() {
+                Some(&"ascii") => break false,
+                Some(&"binary") => break true,
+                data => return Err(invalid_data(format!("Unsupported PCD data {data:?}"))),
+            },
+            // VERSION, WIDTH, HEIGHT, VIEWPOINT and comments.
+            _ => {}
+        }
+    };
+    if sizes.len() != names.len() || types.len() != names.len() {
+        return Err(invalid_data("PCD FIELDS, SIZE and TYPE don't match"));
+    }
+
+    let mut fields = Vec::new();
+    for (i, name) in names.into_iter().enumerate() {
+        let ty = Scalar::from_pcd(types[i], sizes[i]).ok_or_else(|| {
+            invalid_data(format!(
+                "Unsupported PCD field {name} {} {}",
+                types[i], sizes[i]
+            ))
+        })?;
+        // COUNT is optional and defaults to one element per field. Only the first element of
+        // a field is ever looked up by name.
+        let elements = counts.get(i).map_or(Ok(1), |n| parse_count(n))?;
+        fields.extend(std::iter::repeat((name, ty)).take(elements));
+    }
+
+    let count = count.ok_or_else(|| invalid_data("PCD file has no POINTS"))?;
+    read_points(body, &fields, count, binary, ["r", "g", "b"])
+}
+
+/// Where the color of a point is read from.
+#[derive(Clone, Copy)]
+enum ColorFields {
+    /// A field per channel.
+    Channels([usize; 3]),
+    /// Packed as `0x00RRGGBB`, in the bits of a float when `true` like PCL writes it.
+    Packed(usize, bool),
+}
+
+/// Decodes `count` points laid out as `fields`, either as whitespace separated text or packed
+/// binary. Colors are read from the `channels` fields, or a packed `rgb` or `rgba` field.
+fn read_points(
+    body: &[u8],
+    fields: &[(&str, Scalar)],
+    count: usize,
+    binary: bool,
+    channels: [&str; 3],
+) -> io::Result<PointPayload> {
+    let index = |name: &str| fields.iter().position(|(field, _)| *field == name);
+    let [Some(x), Some(y), Some(z)] = ["x", "y", "z"].map(index) else {
+        return Err(invalid_data("Points have no x, y and z fields"));
+    };
+    let color = match channels.map(index) {
+        [Some(r), Some(g), Some(b)] => Some(ColorFields::Channels([r, g, b])),
+        _ => index("rgb")
+            .or_else(|| index("rgba"))
+            .map(|i| ColorFields::Packed(i, matches!(fields[i].1, Scalar::F32))),
+    };
+
+    let to_point = |record: &[f64]| {
+        let color = match color {
+            // Float to int casts saturate, so out of range colors are clamped.
+            Some(ColorFields::Channels(channels)) => channels.map(|i| record[i].round() as u8),
+            Some(ColorFields::Packed(i, float)) => {
+                let bits = if float {
+                    (record[i] as f32).to_bits()
+                } else {
+                    record[i] as u32
+                };
+                let [_, r, g, b] = bits.to_be_bytes();
+                [r, g, b]
+            }
+            None => [u8::MAX; 3],
+        };
+        ColorPoint {
+            pos: Vec3::new(record[x] as f32, record[y] as f32, record[z] as f32),
+            color,
+        }
+    };
+
+    let mut points = Vec::with_capacity(count.min(body.len()));
+    let mut record = vec![0.0; fields.len()];
+    if binary {
+        let stride: usize = fields.iter().map(|(_, ty)| ty.size()).sum();
+        if body.len() < stride * count {
+            return Err(invalid_data("File is truncated"));
+        }
+        for bytes in body.chunks_exact(stride).take(count) {
+            let mut offset = 0;
+            for (value, (_, ty)) in record.iter_mut().zip(fields) {
+                *value = ty.read_le(&bytes[offset..]);
+                offset += ty.size();
+            }
+            points.push(to_point(&record));
+        }
+    } else {
+        let text = std::str::from_utf8(body).map_err(|_| invalid_data("Invalid ASCII data"))?;
+        let mut tokens = text.split_ascii_whitespace();
+        for _ in 0..count {
+            for (value, (_, ty)) in record.iter_mut().zip(fields) {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| invalid_data("File is truncated"))?;
+                *value = ty
+                    .parse(token)
+                    .ok_or_else(|| invalid_data(format!("Invalid value {token:?}")))?;
+            }
+            points.push(to_point(&record));
+        }
+    }
+
+    Ok(match color {
+        Some(_) => PointPayload::Color(points),
+        None => PointPayload::Depth(points.into_iter().map(|p| p.pos).collect()),
+    })
+}
+
+/// The type of a PLY property or PCD field.
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_ply(ty: &str) -> Option<Self> {
+        Some(match ty {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return None,
+        })
+    }
+
+    fn from_pcd(ty: &str, size: &str) -> Option<Self> {
+        Some(match (ty, size) {
+            ("I", "1") => Self::I8,
+            ("U", "1") => Self::U8,
+            ("I", "2") => Self::I16,
+            ("U", "2") => Self::U16,
+            ("I", "4") => Self::I32,
+            ("U", "4") => Self::U32,
+            ("F", "4") => Self::F32,
+            ("F", "8") => Self::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// Reads a little-endian value from the start of `bytes`.
+    fn read_le(self, bytes: &[u8]) -> f64 {
+        fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+            bytes[..N].try_into().unwrap()
+        }
+        match self {
+            Self::I8 => i8::from_le_bytes(array(bytes)).into(),
+            Self::U8 => bytes[0].into(),
+            Self::I16 => i16::from_le_bytes(array(bytes)).into(),
+            Self::U16 => u16::from_le_bytes(array(bytes)).into(),
+            Self::I32 => i32::from_le_bytes(array(bytes)).into(),
+            Self::U32 => u32::from_le_bytes(array(bytes)).into(),
+            Self::F32 => f32::from_le_bytes(array(bytes)).into(),
+            Self::F64 => f64::from_le_bytes(array(bytes)),
+        }
+    }
+
+    fn parse(self, token: &str) -> Option<f64> {
+        match self {
+            // Parsed at their own precision, so packed colors keep their exact bits.
+            Self::F32 => token.parse::<f32>().ok().map(f64::from),
+            Self::F64 => token.parse().ok(),
+            _ => token.parse::<i64>().ok().map(|v| v as f64),
+        }
+    }
+}
+
+/// Splits the next header line off the front of `data`.
+fn next_line<'a>(data: &mut &'a [u8]) -> io::Result<&'a str> {
+    let end = data
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or_else(|| invalid_data("Unexpected end of header"))?;
+    let line = std::str::from_utf8(&data[..end]).map_err(|_| invalid_data("Invalid header"))?;
+    *data = &data[end + 1..];
+    Ok(line.trim())
+}
+
+fn parse_count(n: &str) -> io::Result<usize> {
+    n.parse()
+        .map_err(|_| invalid_data(format!("Invalid count {n:?}")))
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// File formats the displayed cloud can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Binary PLY, see [`save_ply`].
+    Ply,
+    /// Binary PCD, see [`save_pcd`].
+    Pcd,
+}
+
+/// Saves the displayed cloud to a file at `path`. Points are written in millimeters in the same
+/// orientation as they are rendered, with the colors shown on screen. Without a displayed
+/// cloud, e.g. when running headless with
+/// [`OrbbecCapturePlugin`](crate::OrbbecCapturePlugin), the latest cloud received from each
+/// camera is saved instead.
+#[derive(Event, Debug, Clone)]
+pub struct ExportCloud {
+    pub path: PathBuf,
+    pub format: ExportFormat,
+}
+
+/// Saves every received point cloud to a numbered file in `dir`, created if needed, until
+/// `max_frames` clouds or `duration` have been recorded, or [`StopRecordingFrames`] is sent.
+/// Unlike [`OrbbecConfig::record_to`] this produces standard files any tool can read, written
+/// like [`ExportCloud`] writes the latest clouds.
+///
+/// Files are named after the order they were received in, the camera and the frame's
+/// [`Frame::device_timestamp_us`], e.g. `000042_cam0_1234567us.ply`. They are written on a
+/// background thread, so neither capture nor rendering waits for the disk; clouds queue up in
+/// memory when it can't keep up. Sending another `RecordFrames` while recording starts over in
+/// the new directory.
+#[derive(Event, Debug, Clone)]
+pub struct RecordFrames {
+    pub dir: PathBuf,
+    pub format: ExportFormat,
+    pub max_frames: Option<usize>,
+    pub duration: Option<Duration>,
+}
+
+/// Stops a recording started with [`RecordFrames`]. Clouds already received are still written.
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct StopRecordingFrames;
+
+/// Handles [`ExportCloud`] and [`RecordFrames`] events.
+pub struct OrbbecExportPlugin;
+
+impl Plugin for OrbbecExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExportCloud>()
+            .add_event::<RecordFrames>()
+            .add_event::<StopRecordingFrames>()
+            .add_systems(Update, (export_cloud, record_frames));
+    }
+}
+
+/// The points of `frame` in world space, colored as the displayed cloud would be.
+fn world_points<'a>(
+    config: &'a OrbbecConfig,
+    frame: &'a Frame,
+) -> impl Iterator<Item = ColorPoint> + 'a {
+    let transform = config.camera_transform(frame.camera);
+    frame.points.iter().map(move |(position, color)| {
+        let world = transform.transform_point(config.point_to_world(position));
+        let [r, g, b, _] = InstanceData::pack_color(config.point_color(position, world, color));
+        ColorPoint {
+            pos: world,
+            color: [r, g, b],
+        }
+    })
+}
+
+/// Adds the points of `frame` to `meshes` as a point list mesh, positioned and colored like
+/// the displayed cloud, e.g. to hand to a physics engine or Bevy's mesh tooling. Returns the
+/// mesh with its bounds, which are empty for a frame without points. The mesh stays readable
+/// in the main world.
+pub fn frame_to_mesh(
+    config: &OrbbecConfig,
+    frame: &Frame,
+    meshes: &mut Assets<Mesh>,
+) -> (Handle<Mesh>, Aabb) {
+    let (positions, colors): (Vec<[f32; 3]>, Vec<[f32; 4]>) = world_points(config, frame)
+        .map(|point| {
+            let [r, g, b] = point.color;
+            let color = Color::srgb_u8(r, g, b).to_linear().to_f32_array();
+            (point.pos.to_array(), color)
+        })
+        .unzip();
+    let aabb = Aabb::enclosing(positions.iter().copied().map(Vec3::from)).unwrap_or_default();
+    let mesh = Mesh::new(PrimitiveTopology::PointList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    (meshes.add(mesh), aabb)
+}
+
+fn export_cloud(
+    mut events: EventReader<ExportCloud>,
+    mut frames: EventReader<PointCloudReceived>,
+    config: Res<OrbbecConfig>,
+    clouds: Query<&InstanceMaterialData, Without<TrailFrame>>,
+    mut latest: Local<BTreeMap<usize, Arc<Frame>>>,
+) {
+    for PointCloudReceived(frame) in frames.read() {
+        latest.insert(frame.camera, frame.clone());
+    }
+
+    for ExportCloud { path, format } in events.read() {
+        let points: Vec<_> = if clouds.is_empty() {
+            latest
+                .values()
+                .flat_map(|frame| world_points(&config, frame))
+                .collect()
+        } else {
+            clouds
+                .iter()
+                .flat_map(|cloud| cloud.iter())
+                .map(|instance| {
+                    let [r, g, b, _] = instance.color;
+                    ColorPoint {
+                        pos: instance.position,
+                        color: [r, g, b],
+                    }
+                })
+                .collect()
+        };
+        if points.is_empty() {
+            warn!("No point cloud to export yet");
+            continue;
+        }
+
+        let scale = 1.0 / config.world_scale;
+        let result = match format {
+            ExportFormat::Ply => save_ply(path, &points, scale),
+            ExportFormat::Pcd => save_pcd(path, &points, scale),
+        };
+        match result {
+            Ok(()) => info!("Saved {} points to {}", points.len(), path.display()),
+            Err(err) => error!("Failed to save {}: {err}", path.display()),
+        }
+    }
+}
+
+/// A recording started by [`RecordFrames`].
+struct FrameRecording {
+    /// Feeds the writer thread, which stops once this is dropped.
+    tx: Sender<Arc<Frame>>,
+    recorded: usize,
+    max_frames: Option<usize>,
+    /// When to stop, in [`Time::elapsed`].
+    until: Option<Duration>,
+}
+
+fn record_frames(
+    time: Res<Time>,
+    config: Res<OrbbecConfig>,
+    mut start: EventReader<RecordFrames>,
+    mut stop: EventReader<StopRecordingFrames>,
+    mut frames: EventReader<PointCloudReceived>,
+    mut recording: Local<Option<FrameRecording>>,
+) {
+    if stop.read().count() > 0 {
+        *recording = None;
+    }
+    if let Some(RecordFrames {
+        dir,
+        format,
+        max_frames,
+        duration,
+    }) = start.read().last()
+    {
+        let (tx, rx) = channel();
+        let (config, dir, format) = (config.clone(), dir.clone(), *format);
+        std::thread::spawn(move || write_frames(rx, &config, &dir, format));
+        *recording = Some(FrameRecording {
+            tx,
+            recorded: 0,
+            max_frames: *max_frames,
+            until: duration.map(|duration| time.elapsed() + duration),
+        });
+    }
+
+    let Some(active) = recording.as_mut() else {
+        frames.clear();
+        return;
+    };
+    if active.until.is_some_and(|until| time.elapsed() >= until) {
+        *recording = None;
+        frames.clear();
+        return;
+    }
+    for PointCloudReceived(frame) in frames.read() {
+        // Fails if the writer gave up, e.g. when the directory can't be created.
+        if active.tx.send(frame.clone()).is_err() {
+            *recording = None;
+            break;
+        }
+        active.recorded += 1;
+        if active.max_frames.is_some_and(|max| active.recorded >= max) {
+            *recording = None;
+            break;
+        }
+    }
+}
+
+/// Writes each frame received on `rx` to its own file in `dir`, until the recording stops.
+fn write_frames(rx: Receiver<Arc<Frame>>, config: &OrbbecConfig, dir: &Path, format: ExportFormat) {
+    if let Err(err) = std::fs::create_dir_all(dir) {
+        error!("Failed to create {}: {err}", dir.display());
+        return;
+    }
+
+    let scale = 1.0 / config.world_scale;
+    let extension = match format {
+        ExportFormat::Ply => "ply",
+        ExportFormat::Pcd => "pcd",
+    };
+    let mut written = 0;
+    for (index, frame) in rx.iter().enumerate() {
+        let name = format!(
+            "{index:06}_cam{}_{}us.{extension}",
+            frame.camera, frame.device_timestamp_us
+        );
+        let path = dir.join(name);
+        let points: Vec<_> = world_points(config, &frame).collect();
+        let result = match format {
+            ExportFormat::Ply => save_ply(&path, &points, scale),
+            ExportFormat::Pcd => save_pcd(&path, &points, scale),
+        };
+        match result {
+            Ok(()) => written += 1,
+            Err(err) => error!("Failed to save {}: {err}", path.display()),
+        }
+    }
+    info!("Recorded {written} point clouds to {}", dir.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> Vec<ColorPoint> {
+        vec![
+            ColorPoint {
+                pos: Vec3::new(-12.5, 40.0, 1000.25),
+                color: [255, 128, 0],
+            },
+            ColorPoint {
+                pos: Vec3::new(3.0, -7.75, 512.0),
+                color: [1, 2, 254],
+            },
+        ]
+    }
+
+    /// Saves [`points`] with `save`, loads them back and checks nothing changed.
+    fn round_trip(extension: &str, save: fn(&Path, &[ColorPoint], f32) -> io::Result<()>) {
+        let path = std::env::temp_dir().join(format!(
+            "bevy_orbbec_round_trip_{}.{extension}",
+            std::process::id()
+        ));
+        save(&path, &points(), 1.0).unwrap();
+        let loaded = load_points(&path);
+        std::fs::remove_file(&path).unwrap();
+        match loaded.unwrap() {
+            PointPayload::Color(loaded) => assert_eq!(loaded, points()),
+            PointPayload::Depth(_) => panic!("{extension} lost its colors"),
+        }
+    }
+
+    #[test]
+    fn ply_round_trip() {
+        round_trip("ply", |path, points, scale| save_ply(path, points, scale));
+    }
+
+    #[test]
+    fn pcd_round_trip() {
+        round_trip("pcd", |path, points, scale| save_pcd(path, points, scale));
+    }
+}
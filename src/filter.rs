@@ -0,0 +1,66 @@
+//! CPU filters applied to point clouds on the capture thread, before they are sent to the app.
+
+use crate::orbbec::ColorPoint;
+use bevy::{
+    math::{IVec3, Vec3},
+    utils::HashMap,
+};
+
+/// Reduces the cloud to at most one point per cube of `voxel_size_mm` on each side, averaging
+/// the position and color of the points that fall in the same cube.
+pub fn voxel_downsample(points: &[ColorPoint], voxel_size_mm: f32) -> Vec<ColorPoint> {
+    voxel_average(
+        points.iter().map(|p| {
+            let [r, g, b] = p.color.map(f32::from);
+            [p.pos.x, p.pos.y, p.pos.z, r, g, b]
+        }),
+        voxel_size_mm,
+    )
+    .into_iter()
+    .map(|[x, y, z, r, g, b]| ColorPoint {
+        pos: Vec3::new(x, y, z),
+        color: [r, g, b].map(|c| c.round() as u8),
+    })
+    .collect()
+}
+
+/// [`voxel_downsample`] for uncolored points.
+pub fn voxel_downsample_xyz(points: &[Vec3], voxel_size_mm: f32) -> Vec<Vec3> {
+    voxel_average(points.iter().map(|p| p.to_array()), voxel_size_mm)
+        .into_iter()
+        .map(Vec3::from_array)
+        .collect()
+}
+
+/// The voxel of `voxel_size_mm` containing `position`. A downsampled point stays in the
+/// voxel of the points it was averaged from.
+pub(crate) fn voxel_key(position: Vec3, voxel_size_mm: f32) -> IVec3 {
+    (position / voxel_size_mm).floor().as_ivec3()
+}
+
+/// Averages the attributes of the points in each voxel. The first three attributes of each
+/// point are its position.
+fn voxel_average<const N: usize>(
+    points: impl ExactSizeIterator<Item = [f32; N]>,
+    voxel_size_mm: f32,
+) -> Vec<[f32; N]> {
+    if voxel_size_mm <= 0.0 {
+        return points.collect();
+    }
+
+    // Running sums of each voxel's points, and how many were added.
+    let mut voxels: HashMap<IVec3, ([f32; N], u32)> = HashMap::default();
+    for point in points {
+        let key = voxel_key(Vec3::new(point[0], point[1], point[2]), voxel_size_mm);
+        let (sum, count) = voxels.entry(key).or_insert(([0.0; N], 0));
+        for (sum, value) in sum.iter_mut().zip(point) {
+            *sum += value;
+        }
+        *count += 1;
+    }
+
+    voxels
+        .into_values()
+        .map(|(sum, count)| sum.map(|sum| sum / count as f32))
+        .collect()
+}
@@ -0,0 +1,41 @@
+use crate::background::BackgroundSubtraction;
+use crate::orbbec::{ConnectionState, Frame, OrbbecRx};
+use bevy::prelude::*;
+use std::sync::Arc;
+
+/// Sent once for every point cloud received from the camera.
+///
+/// Reading these events with an [`EventReader`] is the preferred way to consume point clouds,
+/// as any number of systems can do so independently. Polling [`OrbbecRx::try_get_data`]
+/// directly still works, but only when [`OrbbecFramePlugin`] isn't draining the same receiver.
+#[derive(Event, Clone)]
+pub struct PointCloudReceived(pub Arc<Frame>);
+
+/// Drains [`OrbbecRx`] each frame, sending a [`PointCloudReceived`] event per point cloud, and
+/// keeps the [`ConnectionState`] resource up to date.
+pub struct OrbbecFramePlugin;
+
+impl Plugin for OrbbecFramePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PointCloudReceived>()
+            .init_resource::<ConnectionState>()
+            .add_systems(PreUpdate, (receive_frames, update_connection_state));
+    }
+}
+
+pub(crate) fn receive_frames(
+    orbbec: Res<OrbbecRx>,
+    mut background: Option<ResMut<BackgroundSubtraction>>,
+    mut events: EventWriter<PointCloudReceived>,
+) {
+    while let Some(mut frame) = orbbec.try_get_data() {
+        if let Some(background) = background.as_mut() {
+            background.bypass_change_detection().apply(&mut frame);
+        }
+        events.send(PointCloudReceived(Arc::new(frame)));
+    }
+}
+
+fn update_connection_state(orbbec: Res<OrbbecRx>, mut state: ResMut<ConnectionState>) {
+    state.set_if_neq(orbbec.connection_state());
+}
@@ -0,0 +1,105 @@
+//! Drawing where each camera is and what its depth sensor sees, to help position cameras.
+
+use crate::camera_param::OrbbecCameraParam;
+use crate::orbbec::{OrbbecConfig, RenderStyle};
+use crate::trail::TrailFrame;
+use crate::PointCloudSource;
+use bevy::{color::palettes::css::FUCHSIA, prelude::*};
+
+/// Length of the axes drawn at each sensor's origin, in millimeters.
+const AXES_LENGTH_MM: f32 = 100.0;
+
+/// How [`OrbbecFrustumPlugin`] draws the depth sensors.
+#[derive(Resource, Debug, Clone)]
+pub struct FrustumGizmos {
+    pub enabled: bool,
+    pub color: Color,
+}
+
+impl Default for FrustumGizmos {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            color: FUCHSIA.into(),
+        }
+    }
+}
+
+/// Draws the depth sensor's view frustum between the configured near and far depths, and its
+/// axes at the sensor origin, for each camera. Each is placed like the camera's cloud, so
+/// frustums that disagree with where the clouds overlap reveal a misplaced camera.
+///
+/// The frustum's shape comes from the depth intrinsics in [`OrbbecCameraParam`], which are
+/// those of the first camera, so it is only exact for cameras of the same model and profile.
+/// Nothing is drawn until they are received.
+pub struct OrbbecFrustumPlugin;
+
+impl Plugin for OrbbecFrustumPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FrustumGizmos>()
+            .add_systems(Update, draw_frustums);
+    }
+}
+
+fn draw_frustums(
+    settings: Res<FrustumGizmos>,
+    config: Res<OrbbecConfig>,
+    param: Option<Res<OrbbecCameraParam>>,
+    clouds: Query<
+        (&GlobalTransform, Option<&PointCloudSource>),
+        (With<RenderStyle>, Without<TrailFrame>),
+    >,
+    mut gizmos: Gizmos,
+) {
+    let Some(param) = param.filter(|_| settings.enabled) else {
+        return;
+    };
+    let intrinsic = param.depth_intrinsic;
+    if intrinsic.fx <= 0.0 || intrinsic.fy <= 0.0 {
+        return;
+    }
+
+    let near_mm = config.depth_min_mm.unwrap_or(config.depth_image_near_mm);
+    let far_mm = config.depth_max_mm.unwrap_or(config.depth_image_far_mm);
+    // The corners of the image, back projected to a depth in millimeters.
+    let corners = |z: f32| {
+        let (width, height) = (f32::from(intrinsic.width), f32::from(intrinsic.height));
+        [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)].map(|(u, v)| {
+            Vec3::new(
+                (u - intrinsic.cx) * z / intrinsic.fx,
+                (v - intrinsic.cy) * z / intrinsic.fy,
+                z,
+            )
+        })
+    };
+
+    let cameras = config.serials.len().max(1);
+    for camera in 0..cameras {
+        // Placed like the points: into world space, by the camera's transform, then by the
+        // transform of the entity drawing them.
+        let entity = clouds
+            .iter()
+            .find(|(_, source)| source.map_or(true, |source| source.0 == camera))
+            .map(|(transform, _)| transform.affine())
+            .unwrap_or_default();
+        let camera_transform = config.camera_transform(camera);
+        let to_world = |position: Vec3| {
+            let world = camera_transform.transform_point(config.point_to_world(position));
+            entity.transform_point3(world)
+        };
+
+        let origin = to_world(Vec3::ZERO);
+        let near = corners(near_mm).map(to_world);
+        let far = corners(far_mm).map(to_world);
+        for i in 0..4 {
+            let next = (i + 1) % 4;
+            gizmos.line(near[i], near[next], settings.color);
+            gizmos.line(far[i], far[next], settings.color);
+            gizmos.line(origin, far[i], settings.color);
+        }
+
+        let axes = Transform::from_translation(origin)
+            .with_rotation(entity.to_scale_rotation_translation().1 * camera_transform.rotation);
+        gizmos.axes(axes, AXES_LENGTH_MM * config.world_scale);
+    }
+}
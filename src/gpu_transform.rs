@@ -0,0 +1,406 @@
+//! Converting points into instances on the GPU, enabled by the `gpu_transform` feature.
+//!
+//! Instead of building [`InstanceMaterialData`] on the CPU, the cloud entity carries the
+//! received frames as a [`RawPointCloud`]. Their points are uploaded as they are and a compute
+//! shader writes the instance buffer, applying the world scale, Y flip, camera transform,
+//! colormaps and depth range. Devices without compute shaders, e.g. WebGL2, keep using the
+//! CPU path.
+//!
+//! As the instances only exist on the GPU, picking, measuring and exporting, which read
+//! [`InstanceMaterialData`], find no points while this path is active. Positions are never
+//! quantized either, see [`QuantizePositions`].
+//!
+//! [`InstanceMaterialData`]: crate::InstanceMaterialData
+//! [`QuantizePositions`]: crate::QuantizePositions
+
+use crate::orbbec::{ColorMode, Frame, OrbbecConfig, PointPayload};
+use crate::render::{InstanceBuffer, InstanceData};
+use bevy::{
+    asset::embedded_asset,
+    ecs::{
+        entity::{EntityHashMap, EntityHashSet},
+        query::QueryItem,
+    },
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        graph::CameraDriverLabel,
+        primitives::Aabb,
+        render_graph::{self, RenderGraph, RenderLabel},
+        render_resource::{
+            binding_types::{storage_buffer_read_only_sized, storage_buffer_sized, uniform_buffer},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        Render, RenderApp, RenderSet,
+    },
+};
+use bytemuck::{Pod, Zeroable};
+use std::sync::Arc;
+
+/// Matches `@workgroup_size` in the transform shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Whether the device can run the transform shader.
+pub(crate) fn is_supported(render_device: &RenderDevice) -> bool {
+    let limits = render_device.limits();
+    limits.max_compute_workgroups_per_dimension > 0
+        && limits.max_storage_buffers_per_shader_stage >= 2
+}
+
+/// The latest frame of each camera, converted into the entity's instances on the GPU.
+#[derive(Component, Clone)]
+pub struct RawPointCloud {
+    frames: Vec<RawFrame>,
+}
+
+#[derive(Clone)]
+struct RawFrame {
+    frame: Arc<Frame>,
+    params: TransformParams,
+}
+
+impl RawPointCloud {
+    pub(crate) fn new(config: &OrbbecConfig, frames: impl IntoIterator<Item = Arc<Frame>>) -> Self {
+        let mut offset = 0;
+        let frames = frames
+            .into_iter()
+            .map(|frame| {
+                let params = TransformParams::new(config, &frame, offset);
+                offset += params.count;
+                RawFrame { frame, params }
+            })
+            .collect();
+        Self { frames }
+    }
+
+    /// Total number of points, and so of instances.
+    pub fn len(&self) -> usize {
+        self.frames.iter().map(|raw| raw.frame.points.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The bounds of the points within the depth range, in world space. Only takes a min/max
+    /// pass over the raw positions, whose box is then transformed, so it may be a little
+    /// larger than the cloud when a camera is rotated.
+    pub(crate) fn bounds(&self, config: &OrbbecConfig) -> Option<Aabb> {
+        let (mut min, mut max) = (Vec3::MAX, Vec3::MIN);
+        for raw in &self.frames {
+            let (mut raw_min, mut raw_max) = (Vec3::MAX, Vec3::MIN);
+            for (position, _) in raw.frame.points.iter() {
+                if config.in_depth_range(position.z) {
+                    raw_min = raw_min.min(position);
+                    raw_max = raw_max.max(position);
+                }
+            }
+            if raw_min.cmpgt(raw_max).any() {
+                continue;
+            }
+            let transform = config.camera_transform(raw.frame.camera);
+            for corner in 0..8 {
+                let corner = Vec3::select(
+                    BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                    raw_max,
+                    raw_min,
+                );
+                let world = transform.transform_point(config.point_to_world(corner));
+                min = min.min(world);
+                max = max.max(world);
+            }
+        }
+        min.cmple(max).all().then(|| Aabb::from_min_max(min, max))
+    }
+}
+
+impl ExtractComponent for RawPointCloud {
+    type QueryData = &'static RawPointCloud;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        Some(item.clone())
+    }
+}
+
+/// How the transform shader converts the points of one frame, mirroring
+/// [`OrbbecConfig::point_to_world`] and [`OrbbecConfig::point_color`].
+#[derive(ShaderType, Clone, Copy)]
+struct TransformParams {
+    /// The camera's transform, applied after scaling into world units.
+    transform: Mat4,
+    /// The world scale, negated on Y when flipping.
+    scale: Vec3,
+    instance_scale: f32,
+    /// Points outside this depth range, in millimeters, are hidden.
+    depth_range: Vec2,
+    depth_colormap_range: Vec2,
+    height_colormap_range: Vec2,
+    /// `0` for [`ColorMode::Rgb`], `1` for [`ColorMode::DepthColormap`] and `2` for
+    /// [`ColorMode::HeightColormap`].
+    color_mode: u32,
+    /// Whether each position is followed by a packed color.
+    colored: u32,
+    /// Index of the first instance written for the frame.
+    offset: u32,
+    count: u32,
+}
+
+impl TransformParams {
+    fn new(config: &OrbbecConfig, frame: &Frame, offset: u32) -> Self {
+        let flip = if config.flip_y { -1.0 } else { 1.0 };
+        Self {
+            transform: config.camera_transform(frame.camera).compute_matrix(),
+            scale: Vec3::new(1.0, flip, 1.0) * config.world_scale,
+            instance_scale: config.render_style.instance_scale(config.world_scale),
+            depth_range: Vec2::new(
+                config.depth_min_mm.unwrap_or(f32::MIN),
+                config.depth_max_mm.unwrap_or(f32::MAX),
+            ),
+            depth_colormap_range: Vec2::new(config.depth_image_near_mm, config.depth_image_far_mm),
+            height_colormap_range: Vec2::new(
+                config.height_colormap_range.start,
+                config.height_colormap_range.end,
+            ),
+            color_mode: match config.color_mode {
+                ColorMode::Rgb => 0,
+                ColorMode::DepthColormap => 1,
+                ColorMode::HeightColormap => 2,
+                // Colored on the CPU instead, so never uploaded.
+                ColorMode::Cluster => 0,
+            },
+            colored: matches!(frame.points, PointPayload::Color(_)).into(),
+            offset,
+            count: frame.points.len() as u32,
+        }
+    }
+}
+
+/// A [`ColorPoint`](crate::ColorPoint) as read by the transform shader, with the color padded
+/// to a whole word.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct RawColorPoint {
+    position: [f32; 3],
+    color: [u8; 4],
+}
+
+/// Runs the transform shader for every [`RawPointCloud`]. Added by
+/// [`CustomMaterialPlugin`](crate::CustomMaterialPlugin).
+pub(crate) struct GpuTransformPlugin;
+
+impl Plugin for GpuTransformPlugin {
+    fn build(&self, app: &mut App) {
+        embedded_asset!(app, "shaders/transform.wgsl");
+
+        app.add_plugins(ExtractComponentPlugin::<RawPointCloud>::default());
+    }
+
+    fn finish(&self, app: &mut App) {
+        let render_app = app.sub_app_mut(RenderApp);
+        if !is_supported(render_app.world().resource::<RenderDevice>()) {
+            return;
+        }
+
+        render_app
+            .init_resource::<TransformPipeline>()
+            .init_resource::<TransformBufferCache>()
+            .add_systems(
+                Render,
+                prepare_transforms.in_set(RenderSet::PrepareBindGroups),
+            );
+
+        // Run before any camera draws, so the instance buffers are written by then.
+        let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
+        render_graph.add_node(TransformLabel, TransformNode);
+        render_graph.add_node_edge(TransformLabel, CameraDriverLabel);
+    }
+}
+
+#[derive(Resource)]
+struct TransformPipeline {
+    layout: BindGroupLayout,
+    pipeline: CachedComputePipelineId,
+}
+
+impl FromWorld for TransformPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let layout = world.resource::<RenderDevice>().create_bind_group_layout(
+            "point transform layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    uniform_buffer::<TransformParams>(false),
+                    storage_buffer_read_only_sized(false, None),
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+        let shader = world.load_asset("embedded://bevy_orbbec/shaders/transform.wgsl");
+        let pipeline =
+            world
+                .resource::<PipelineCache>()
+                .queue_compute_pipeline(ComputePipelineDescriptor {
+                    label: Some("point transform pipeline".into()),
+                    layout: vec![layout.clone()],
+                    push_constant_ranges: Vec::new(),
+                    shader,
+                    shader_defs: Vec::new(),
+                    entry_point: "transform".into(),
+                });
+
+        Self { layout, pipeline }
+    }
+}
+
+/// Instance buffers written by the transform shader, kept alive across frames like the
+/// CPU path's.
+#[derive(Resource, Default)]
+struct TransformBufferCache(EntityHashMap<Buffer>);
+
+/// One dispatch of the transform shader, converting the points of one frame.
+struct TransformJob {
+    bind_group: BindGroup,
+    workgroups: u32,
+}
+
+/// The dispatches run by [`TransformNode`] this frame.
+#[derive(Resource, Default)]
+struct TransformJobs(Vec<TransformJob>);
+
+fn prepare_transforms(
+    mut commands: Commands,
+    clouds: Query<(Entity, &RawPointCloud)>,
+    pipeline: Res<TransformPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut cache: ResMut<TransformBufferCache>,
+) {
+    // Until the shader has compiled nothing would write the instance buffers, so nothing is
+    // drawn from them.
+    if pipeline_cache
+        .get_compute_pipeline(pipeline.pipeline)
+        .is_none()
+    {
+        commands.insert_resource(TransformJobs::default());
+        return;
+    }
+
+    let mut jobs = Vec::new();
+    let mut live = EntityHashSet::default();
+    for (entity, cloud) in &clouds {
+        live.insert(entity);
+        let size = (cloud.len() * std::mem::size_of::<InstanceData>()) as u64;
+        let buffer = match cache.0.get(&entity) {
+            Some(buffer) if buffer.size() >= size => buffer.clone(),
+            _ => {
+                let buffer = render_device.create_buffer(&BufferDescriptor {
+                    label: Some("transformed instance buffer"),
+                    size: size.next_power_of_two().max(COPY_BUFFER_ALIGNMENT),
+                    usage: BufferUsages::VERTEX | BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                });
+                cache.0.insert(entity, buffer.clone());
+                buffer
+            }
+        };
+
+        for raw in &cloud.frames {
+            let packed: Vec<RawColorPoint>;
+            let contents: &[u8] = match &raw.frame.points {
+                // Repacked, as `ColorPoint`'s color leaves it padded.
+                PointPayload::Color(points) => {
+                    packed = points
+                        .iter()
+                        .map(|point| {
+                            let [r, g, b] = point.color;
+                            RawColorPoint {
+                                position: point.pos.to_array(),
+                                color: [r, g, b, u8::MAX],
+                            }
+                        })
+                        .collect();
+                    bytemuck::cast_slice(&packed)
+                }
+                PointPayload::Depth(points) => bytemuck::cast_slice(points),
+            };
+            if contents.is_empty() {
+                continue;
+            }
+
+            let points = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("raw point buffer"),
+                contents,
+                usage: BufferUsages::STORAGE,
+            });
+            let mut params = UniformBuffer::from(raw.params);
+            params.write_buffer(&render_device, &render_queue);
+            let Some(params) = params.binding() else {
+                continue;
+            };
+            let bind_group = render_device.create_bind_group(
+                "point transform bind group",
+                &pipeline.layout,
+                &BindGroupEntries::sequential((
+                    params,
+                    points.as_entire_binding(),
+                    buffer.as_entire_binding(),
+                )),
+            );
+            jobs.push(TransformJob {
+                bind_group,
+                workgroups: raw.params.count.div_ceil(WORKGROUP_SIZE),
+            });
+        }
+
+        commands.entity(entity).insert(InstanceBuffer {
+            buffer,
+            length: cloud.len(),
+        });
+    }
+
+    cache.0.retain(|entity, _| live.contains(entity));
+    commands.insert_resource(TransformJobs(jobs));
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct TransformLabel;
+
+struct TransformNode;
+
+impl render_graph::Node for TransformNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let Some(jobs) = world.get_resource::<TransformJobs>() else {
+            return Ok(());
+        };
+        let pipeline = world.resource::<TransformPipeline>();
+        let Some(compute_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline.pipeline)
+        else {
+            return Ok(());
+        };
+
+        let mut pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("point transform"),
+                    timestamp_writes: None,
+                });
+        pass.set_pipeline(compute_pipeline);
+        for job in &jobs.0 {
+            pass.set_bind_group(0, &job.bind_group, &[]);
+            pass.dispatch_workgroups(job.workgroups, 1, 1);
+        }
+        Ok(())
+    }
+}
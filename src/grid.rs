@@ -0,0 +1,77 @@
+//! A reference grid and axes, to judge the scale and orientation of the cloud.
+
+use crate::orbbec::OrbbecConfig;
+use bevy::{color::palettes::css::GRAY, prelude::*};
+
+/// Millimeters per meter, the grid being sized in meters and the points in millimeters.
+const MM_PER_M: f32 = 1000.0;
+
+/// How [`OrbbecGridPlugin`] draws the grid. Distances are in meters and follow
+/// [`OrbbecConfig::world_scale`], so the lines stay a meter apart whatever the scale.
+#[derive(Resource, Debug, Clone)]
+pub struct GridGizmos {
+    pub enabled: bool,
+    /// Distance between lines, in meters.
+    pub spacing_m: f32,
+    /// How far the grid reaches from the origin along each axis, in meters.
+    pub extent_m: f32,
+    /// Height of the grid above the origin, in meters, e.g. to line it up with the floor.
+    pub height_m: f32,
+    pub color: Color,
+    /// Length of the axes drawn at the origin, in meters. Zero draws none.
+    pub axes_length_m: f32,
+}
+
+impl Default for GridGizmos {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            spacing_m: 1.0,
+            extent_m: 10.0,
+            height_m: 0.0,
+            color: GRAY.into(),
+            axes_length_m: 1.0,
+        }
+    }
+}
+
+/// Draws a grid on the XZ plane and the world axes at the origin, see [`GridGizmos`].
+pub struct OrbbecGridPlugin;
+
+impl Plugin for OrbbecGridPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GridGizmos>()
+            .add_systems(Update, draw_grid);
+    }
+}
+
+fn draw_grid(settings: Res<GridGizmos>, config: Res<OrbbecConfig>, mut gizmos: Gizmos) {
+    if !settings.enabled {
+        return;
+    }
+
+    let scale = MM_PER_M * config.world_scale;
+    let y = settings.height_m * scale;
+    if settings.spacing_m > 0.0 {
+        let spacing = settings.spacing_m * scale;
+        let lines = (settings.extent_m / settings.spacing_m).floor() as i32;
+        let extent = lines as f32 * spacing;
+        for i in -lines..=lines {
+            let offset = i as f32 * spacing;
+            gizmos.line(
+                Vec3::new(offset, y, -extent),
+                Vec3::new(offset, y, extent),
+                settings.color,
+            );
+            gizmos.line(
+                Vec3::new(-extent, y, offset),
+                Vec3::new(extent, y, offset),
+                settings.color,
+            );
+        }
+    }
+
+    if settings.axes_length_m > 0.0 {
+        gizmos.axes(Transform::IDENTITY, settings.axes_length_m * scale);
+    }
+}
@@ -0,0 +1,172 @@
+//! Finding the ground, or whichever plane dominates the cloud, with RANSAC.
+
+use crate::filter::voxel_downsample_xyz;
+use crate::frame::PointCloudReceived;
+use crate::orbbec::OrbbecConfig;
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+
+/// The plane with the most points near it, in the same world units and space as the rendered
+/// points, before the cloud entity's own [`Transform`]. Points on it satisfy
+/// `normal.dot(point) == offset`. Only inserted once a plane has been found.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct GroundPlane {
+    /// Unit normal, turned to point up along +Y.
+    pub normal: Vec3,
+    pub offset: f32,
+    /// How many of the downsampled points the plane was fitted to lie within
+    /// [`GroundPlaneSettings::inlier_threshold_mm`] of it.
+    pub inliers: usize,
+}
+
+impl GroundPlane {
+    /// Signed distance of a point above the plane.
+    pub fn distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) - self.offset
+    }
+}
+
+/// What to do with the points within [`GroundPlaneSettings::inlier_threshold_mm`] of the
+/// [`GroundPlane`]. Applied in the shader, so it follows the plane without rebuilding the
+/// cloud.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GroundInliers {
+    /// Draw them like any other point.
+    #[default]
+    Show,
+    /// Draw them in this color instead of their own.
+    Color(Color),
+    /// Don't draw them, e.g. to hide the floor.
+    Hide,
+}
+
+/// How [`OrbbecGroundPlanePlugin`] fits the [`GroundPlane`].
+#[derive(Resource, Debug, Clone)]
+pub struct GroundPlaneSettings {
+    /// How many candidate planes are tried per cloud. More iterations find the plane more
+    /// reliably when it covers a small part of the cloud.
+    pub iterations: usize,
+    /// How far, in millimeters, a point can be from a plane and still count as on it.
+    pub inlier_threshold_mm: f32,
+    /// The cloud is downsampled to cubes of this size, in millimeters, before fitting.
+    pub voxel_size_mm: f32,
+    pub inliers: GroundInliers,
+}
+
+impl Default for GroundPlaneSettings {
+    fn default() -> Self {
+        Self {
+            iterations: 200,
+            inlier_threshold_mm: 20.0,
+            voxel_size_mm: 50.0,
+            inliers: GroundInliers::Show,
+        }
+    }
+}
+
+/// Keeps the [`GroundPlane`] fitted to the received clouds. Fitting runs on the async compute
+/// task pool, one cloud at a time: clouds received while a fit is running are skipped.
+pub struct OrbbecGroundPlanePlugin;
+
+impl Plugin for OrbbecGroundPlanePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GroundPlaneSettings>()
+            .add_systems(Update, fit_ground_plane);
+    }
+}
+
+fn fit_ground_plane(
+    mut commands: Commands,
+    mut frames: EventReader<PointCloudReceived>,
+    config: Res<OrbbecConfig>,
+    settings: Res<GroundPlaneSettings>,
+    mut task: Local<Option<Task<Option<GroundPlane>>>>,
+) {
+    if let Some(running) = task.as_mut() {
+        let Some(plane) = block_on(future::poll_once(running)) else {
+            frames.clear();
+            return;
+        };
+        *task = None;
+        if let Some(plane) = plane {
+            commands.insert_resource(plane);
+        }
+    }
+
+    let Some(PointCloudReceived(frame)) = frames.read().last() else {
+        return;
+    };
+    let frame = frame.clone();
+    let config = config.clone();
+    let settings = settings.clone();
+    *task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        let positions: Vec<_> = frame.points.iter().map(|(position, _)| position).collect();
+        let transform = config.camera_transform(frame.camera);
+        let points: Vec<_> = voxel_downsample_xyz(&positions, settings.voxel_size_mm)
+            .into_iter()
+            .map(|position| transform.transform_point(config.point_to_world(position)))
+            .collect();
+        fit_plane(
+            &points,
+            settings.iterations,
+            settings.inlier_threshold_mm * config.world_scale,
+        )
+    }));
+}
+
+/// Fits the plane with the most points within `threshold` of it, trying `iterations` planes
+/// through three random points. Returns `None` when every sample was degenerate, e.g. for
+/// fewer than three points.
+pub fn fit_plane(points: &[Vec3], iterations: usize, threshold: f32) -> Option<GroundPlane> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    // A fixed seed keeps the result stable for a static scene.
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    let mut best: Option<GroundPlane> = None;
+    for _ in 0..iterations {
+        let [a, b, c] = [(); 3].map(|_| points[rng.next() as usize % points.len()]);
+        let Some(normal) = (b - a).cross(c - a).try_normalize() else {
+            continue;
+        };
+        let mut plane = GroundPlane {
+            normal,
+            offset: normal.dot(a),
+            inliers: 0,
+        };
+        plane.inliers = points
+            .iter()
+            .filter(|&&point| plane.distance(point).abs() <= threshold)
+            .count();
+        if best.map_or(true, |best| plane.inliers > best.inliers) {
+            best = Some(plane);
+        }
+    }
+
+    best.map(|plane| {
+        if plane.normal.y < 0.0 {
+            GroundPlane {
+                normal: -plane.normal,
+                offset: -plane.offset,
+                ..plane
+            }
+        } else {
+            plane
+        }
+    })
+}
+
+/// A small, fast generator for picking RANSAC samples, see Marsaglia's "Xorshift RNGs".
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
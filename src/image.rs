@@ -0,0 +1,238 @@
+//! Camera frames published as Bevy [`Image`]s.
+
+use crate::colormap::turbo;
+use crate::orbbec::OrbbecRx;
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use jpeg_decoder::PixelFormat;
+
+/// An 8-bit sRGB RGBA image copied out of a camera frame on the capture thread.
+#[derive(Debug, Clone)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub(crate) fn from_rgb(width: u32, height: u32, rgb: &[u8]) -> Self {
+        let data = rgb
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], u8::MAX])
+            .collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub(crate) fn from_bgr(width: u32, height: u32, bgr: &[u8]) -> Self {
+        let data = bgr
+            .chunks_exact(3)
+            .flat_map(|p| [p[2], p[1], p[0], u8::MAX])
+            .collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub(crate) fn from_gray(width: u32, height: u32, gray: &[u8]) -> Self {
+        let data = gray.iter().flat_map(|&l| [l, l, l, u8::MAX]).collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Converts a 16-bit grayscale frame, such as IR, stretching the brightest pixel of the
+    /// frame to white as the sensor rarely uses the full range.
+    pub(crate) fn from_gray16(width: u32, height: u32, gray: &[u16]) -> Self {
+        let max = gray.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let data = gray
+            .iter()
+            .flat_map(|&l| {
+                let l = (l as f32 / max * 255.0) as u8;
+                [l, l, l, u8::MAX]
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Colorizes a depth frame with the turbo colormap. `depth` is in the device's units, which
+    /// `value_scale` converts to millimeters, and depths outside `near_mm..far_mm` are clamped
+    /// to the ends of the colormap. Pixels without a depth reading are black.
+    pub(crate) fn from_depth(
+        width: u32,
+        height: u32,
+        depth: &[u16],
+        value_scale: f32,
+        near_mm: f32,
+        far_mm: f32,
+    ) -> Self {
+        let data = depth
+            .iter()
+            .flat_map(|&d| {
+                if d == 0 {
+                    return [0, 0, 0, u8::MAX];
+                }
+                let t = (d as f32 * value_scale - near_mm) / (far_mm - near_mm);
+                let [r, g, b] = turbo(t).map(|c| (c * 255.0) as u8);
+                [r, g, b, u8::MAX]
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Decodes a single MJPG frame, logging and returning `None` if it can't be decoded.
+    pub(crate) fn from_mjpeg(jpeg: &[u8]) -> Option<Self> {
+        let mut decoder = jpeg_decoder::Decoder::new(jpeg);
+        let pixels = match decoder.decode() {
+            Ok(pixels) => pixels,
+            Err(err) => {
+                warn!("Failed to decode MJPG frame: {err}");
+                return None;
+            }
+        };
+        let info = decoder.info()?;
+        let (width, height) = (info.width as u32, info.height as u32);
+
+        match info.pixel_format {
+            PixelFormat::RGB24 => Some(Self::from_rgb(width, height, &pixels)),
+            PixelFormat::L8 => Some(Self::from_gray(width, height, &pixels)),
+            format => {
+                warn!("Unsupported MJPG pixel format {format:?}");
+                None
+            }
+        }
+    }
+
+    /// Replaces the contents of the image behind `handle`, reusing the existing asset when the
+    /// size hasn't changed.
+    fn write_to(self, images: &mut Assets<Image>, handle: &Handle<Image>) {
+        let size = Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
+        };
+
+        match images.get_mut(handle) {
+            Some(image) if image.texture_descriptor.size == size => image.data = self.data,
+            _ => {
+                images.insert(
+                    handle,
+                    Image::new(
+                        size,
+                        TextureDimension::D2,
+                        self.data,
+                        TextureFormat::Rgba8UnormSrgb,
+                        RenderAssetUsages::default(),
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// The latest color frame from the camera, updated in place as frames arrive. Only updated
+/// when [`OrbbecConfig::capture_color_image`] is set.
+///
+/// [`OrbbecConfig::capture_color_image`]: crate::OrbbecConfig::capture_color_image
+#[derive(Resource, Deref)]
+pub struct OrbbecColorImage(pub Handle<Image>);
+
+impl FromWorld for OrbbecColorImage {
+    fn from_world(world: &mut World) -> Self {
+        Self(world.resource_mut::<Assets<Image>>().add(Image::default()))
+    }
+}
+
+/// The latest depth frame from the camera, colorized and updated in place as frames arrive.
+/// Only updated when [`OrbbecConfig::capture_depth_image`] is set.
+///
+/// [`OrbbecConfig::capture_depth_image`]: crate::OrbbecConfig::capture_depth_image
+#[derive(Resource, Deref)]
+pub struct OrbbecDepthImage(pub Handle<Image>);
+
+impl FromWorld for OrbbecDepthImage {
+    fn from_world(world: &mut World) -> Self {
+        Self(world.resource_mut::<Assets<Image>>().add(Image::default()))
+    }
+}
+
+/// The latest infrared frame from the camera, in grayscale and updated in place as frames
+/// arrive. Only updated when [`OrbbecConfig::capture_ir_image`] is set.
+///
+/// [`OrbbecConfig::capture_ir_image`]: crate::OrbbecConfig::capture_ir_image
+#[derive(Resource, Deref)]
+pub struct OrbbecIrImage(pub Handle<Image>);
+
+impl FromWorld for OrbbecIrImage {
+    fn from_world(world: &mut World) -> Self {
+        Self(world.resource_mut::<Assets<Image>>().add(Image::default()))
+    }
+}
+
+/// Publishes images received from [`OrbbecRx`] into image resources.
+pub struct OrbbecImagePlugin;
+
+impl Plugin for OrbbecImagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            (update_color_image, update_depth_image, update_ir_image),
+        );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.init_resource::<OrbbecColorImage>()
+            .init_resource::<OrbbecDepthImage>()
+            .init_resource::<OrbbecIrImage>();
+    }
+}
+
+fn update_color_image(
+    orbbec: Res<OrbbecRx>,
+    color_image: Res<OrbbecColorImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if let Some(image) = orbbec.try_get_color_image() {
+        image.write_to(&mut images, &color_image);
+    }
+}
+
+fn update_depth_image(
+    orbbec: Res<OrbbecRx>,
+    depth_image: Res<OrbbecDepthImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if let Some(image) = orbbec.try_get_depth_image() {
+        image.write_to(&mut images, &depth_image);
+    }
+}
+
+fn update_ir_image(
+    orbbec: Res<OrbbecRx>,
+    ir_image: Res<OrbbecIrImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if let Some(image) = orbbec.try_get_ir_image() {
+        image.write_to(&mut images, &ir_image);
+    }
+}
@@ -0,0 +1,82 @@
+//! Inertial measurements from devices that have an IMU.
+
+use crate::orbbec::{OrbbecConfig, OrbbecRx};
+use crate::render::InstanceMaterialData;
+use bevy::prelude::*;
+
+/// The latest IMU sample from the camera, combining the most recent accelerometer and
+/// gyroscope readings. Only inserted once a sample has arrived, which requires
+/// [`OrbbecConfig::enable_imu`] and a device with an IMU.
+///
+/// Both vectors are in the camera's coordinate frame as reported by the SDK, where Y points
+/// down, and are not affected by [`OrbbecConfig::flip_y`].
+///
+/// [`OrbbecConfig::enable_imu`]: crate::OrbbecConfig::enable_imu
+/// [`OrbbecConfig::flip_y`]: crate::OrbbecConfig::flip_y
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+pub struct OrbbecImu {
+    /// Accelerometer reading, in m/s². At rest this is dominated by gravity.
+    pub linear_acceleration: Vec3,
+    /// Gyroscope reading, in rad/s.
+    pub angular_velocity: Vec3,
+    /// Device timestamp of the most recent of the two readings, in microseconds.
+    pub timestamp_us: u64,
+}
+
+/// Keeps the [`OrbbecImu`] resource up to date with the samples received from [`OrbbecRx`].
+pub struct OrbbecImuPlugin;
+
+impl Plugin for OrbbecImuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, update_imu)
+            .add_systems(Update, level_to_gravity);
+    }
+}
+
+fn update_imu(mut commands: Commands, orbbec: Res<OrbbecRx>, imu: Option<Res<OrbbecImu>>) {
+    let Some(sample) = orbbec.imu() else {
+        return;
+    };
+    if imu.map_or(true, |imu| *imu != sample) {
+        commands.insert_resource(sample);
+    }
+}
+
+/// Rotates the cloud so the smoothed gravity direction points down the world's -Y axis, see
+/// [`OrbbecConfig::level_to_gravity`].
+fn level_to_gravity(
+    config: Res<OrbbecConfig>,
+    imu: Option<Res<OrbbecImu>>,
+    mut up: Local<Option<Vec3>>,
+    mut clouds: Query<&mut Transform, With<InstanceMaterialData>>,
+) {
+    if !config.level_to_gravity {
+        return;
+    }
+
+    if let Some(imu) = imu.filter(|imu| imu.is_changed()) {
+        // At rest the accelerometer measures the reaction to gravity, pointing up. Bring it
+        // into world space the same way as the points.
+        let mut sample = imu.linear_acceleration;
+        if config.flip_y {
+            sample.y = -sample.y;
+        }
+        if let Some(sample) = sample.try_normalize() {
+            *up = Some(match *up {
+                Some(up) => up
+                    .lerp(sample, 1.0 - config.gravity_smoothing)
+                    .try_normalize()
+                    .unwrap_or(sample),
+                None => sample,
+            });
+        }
+    }
+
+    let rotation = match *up {
+        Some(up) => Quat::from_rotation_arc(up, Vec3::Y),
+        None => Quat::IDENTITY,
+    };
+    for mut transform in &mut clouds {
+        transform.rotation = rotation;
+    }
+}
@@ -0,0 +1,425 @@
+//! Streams point clouds from an Orbbec depth camera into Bevy.
+//!
+//! Add [`OrbbecPlugin`] to capture from the camera and [`CustomMaterialPlugin`] to render the
+//! received cloud.
+//!
+//! To capture without a window or renderer, e.g. to stream or record on a server, add
+//! [`OrbbecCapturePlugin`] to an app with `MinimalPlugins` instead. Point clouds then arrive as
+//! [`PointCloudReceived`] events, see `examples/headless.rs`.
+//!
+//! The shaders are embedded in the crate. To edit them without restarting, enable the
+//! `shader_hot_reload` feature and run from a checkout of this repository: changes to the files
+//! in `src/shaders` are then picked up while the app runs. The feature turns on watching in
+//! [`AssetPlugin`](bevy::asset::AssetPlugin) unless the app overrides it with
+//! `watch_for_changes_override: Some(false)`; set it to `Some(true)` to be explicit.
+
+pub mod background;
+pub mod bounds;
+pub mod camera;
+pub mod camera_param;
+pub mod centroid;
+pub mod cluster;
+pub mod colormap;
+pub mod decimation;
+pub mod edl;
+#[cfg(feature = "orbbec_egui")]
+pub mod egui;
+pub mod export;
+pub mod filter;
+pub mod frame;
+pub mod frustum;
+#[cfg(feature = "gpu_transform")]
+pub mod gpu_transform;
+pub mod grid;
+pub mod ground;
+pub mod image;
+pub mod imu;
+pub mod measure;
+#[cfg(feature = "websocket")]
+pub mod net;
+pub mod normals;
+pub mod orbbec;
+pub mod pick;
+pub mod render;
+pub mod stats;
+pub mod temperature;
+pub mod trail;
+
+pub use crate::background::{BackgroundSubtraction, CaptureBackground, OrbbecBackgroundPlugin};
+pub use crate::bounds::OrbbecBounds;
+pub use crate::camera_param::{
+    CameraDistortion, CameraIntrinsic, OrbbecCameraParam, OrbbecCameraParamPlugin,
+};
+pub use crate::centroid::{CentroidSettings, OrbbecCentroid, OrbbecCentroidPlugin};
+pub use crate::cluster::{
+    cluster_color, euclidean_cluster_labels, euclidean_clusters, ClusterLabels, ClusterSettings,
+    ObjectCluster, ObjectClusters, OrbbecClusterPlugin,
+};
+pub use crate::decimation::{AdaptiveDecimation, OrbbecDecimationPlugin};
+pub use crate::edl::{EyeDomeLighting, EyeDomeLightingPlugin};
+#[cfg(feature = "orbbec_egui")]
+pub use crate::egui::{OrbbecEguiPlugin, OrbbecPanel};
+pub use crate::export::{
+    frame_to_mesh, load_points, save_pcd, save_ply, ExportCloud, ExportFormat, OrbbecExportPlugin,
+    RecordFrames, StopRecordingFrames,
+};
+pub use crate::frame::{OrbbecFramePlugin, PointCloudReceived};
+pub use crate::frustum::{FrustumGizmos, OrbbecFrustumPlugin};
+#[cfg(feature = "gpu_transform")]
+pub use crate::gpu_transform::RawPointCloud;
+pub use crate::grid::{GridGizmos, OrbbecGridPlugin};
+pub use crate::ground::{
+    fit_plane, GroundInliers, GroundPlane, GroundPlaneSettings, OrbbecGroundPlanePlugin,
+};
+pub use crate::image::{OrbbecColorImage, OrbbecDepthImage, OrbbecImagePlugin, OrbbecIrImage};
+pub use crate::imu::{OrbbecImu, OrbbecImuPlugin};
+pub use crate::measure::{Measurement, OrbbecMeasurePlugin};
+#[cfg(feature = "websocket")]
+pub use crate::net::{decode_frame, encode_frame, WebSocketSink, WebSocketSinkPlugin};
+pub use crate::orbbec::{
+    AlignMode, BlendMode, CameraSerial, ColorControls, ColorMode, ColorPoint, ConnectionState,
+    DeviceSource, DropPolicy, ExceptionType, Frame, HoleFilling, LogSeverity, OpenedDevice,
+    OrbbecConfig, OrbbecConfigBuilder, OrbbecError, OrbbecRx, PointPayload, RenderStyle,
+    SensorType, SizeAttenuation, SpatialFilter, StreamFormat, StreamProfile, StreamProfileSpec,
+    SyncMode, TemporalFilter, TranslucentDepth,
+};
+pub use crate::pick::{OrbbecPickPlugin, PointPicked};
+pub use crate::render::{
+    CropBox, CustomMaterialPlugin, InstanceData, InstanceMaterialData, PointAlpha, PointFog,
+    PointLod, QuantizePositions, TranslucentPoints,
+};
+pub use crate::stats::{OrbbecStats, OrbbecStatsPlugin};
+pub use crate::temperature::{OrbbecTemperature, OrbbecTemperaturePlugin};
+pub use crate::trail::{OrbbecTrailPlugin, PointTrail, TrailFrame};
+
+use crate::normals::estimate_normals;
+use crate::render::point_mesh;
+#[cfg(feature = "gpu_transform")]
+use bevy::render::renderer::RenderDevice;
+use bevy::{
+    prelude::*,
+    render::{primitives::Aabb, view::NoFrustumCulling},
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Starts capturing from the camera described by `config` and keeps an entity with
+/// [`InstanceMaterialData`] updated with the latest point cloud.
+#[derive(Default)]
+pub struct OrbbecPlugin {
+    pub config: OrbbecConfig,
+}
+
+impl Plugin for OrbbecPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            OrbbecCapturePlugin {
+                config: self.config.clone(),
+            },
+            OrbbecImagePlugin,
+        ))
+        .add_event::<ClearCloud>()
+        .add_systems(Update, update);
+    }
+}
+
+/// Send to despawn the displayed cloud, along with any [`PointTrail`], leaving the view
+/// empty until the next point cloud is received.
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct ClearCloud;
+
+/// Starts capturing from the camera described by `config`, without anything that needs a
+/// window, assets or the renderer, so it also runs with `MinimalPlugins`. Point clouds are
+/// delivered as [`PointCloudReceived`] events but not turned into an entity; use
+/// [`OrbbecPlugin`] for that.
+#[derive(Default)]
+pub struct OrbbecCapturePlugin {
+    pub config: OrbbecConfig,
+}
+
+impl Plugin for OrbbecCapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(OrbbecRx::with_config(self.config.clone()))
+            .insert_resource(self.config.clone())
+            .add_plugins((
+                OrbbecCameraParamPlugin,
+                OrbbecFramePlugin,
+                OrbbecImuPlugin,
+                OrbbecStatsPlugin,
+                OrbbecTemperaturePlugin,
+            ));
+    }
+}
+
+/// Marks a cloud entity showing the points of a single camera, with
+/// [`OrbbecConfig::separate_clouds`]. Holds the camera's index, as in [`Frame::camera`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PointCloudSource(pub usize);
+
+#[allow(clippy::too_many_arguments)]
+fn update(
+    mut commands: Commands,
+    mut clear: EventReader<ClearCloud>,
+    mut frames: EventReader<PointCloudReceived>,
+    config: Res<OrbbecConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    clouds: Query<(Entity, Option<&PointCloudSource>), (With<RenderStyle>, Without<TrailFrame>)>,
+    trail: Query<Entity, With<TrailFrame>>,
+    labels: Option<Res<ClusterLabels>>,
+    mut latest: Local<BTreeMap<usize, Arc<Frame>>>,
+    #[cfg(feature = "gpu_transform")] render_device: Option<Res<RenderDevice>>,
+) {
+    if clear.read().count() > 0 {
+        for entity in clouds.iter().map(|(entity, _)| entity).chain(&trail) {
+            commands.entity(entity).despawn();
+        }
+        // Forgotten too, so a config change doesn't bring the old clouds back.
+        latest.clear();
+        // The despawns are deferred, so a cloud received this frame would be inserted into
+        // an entity on its way out.
+        frames.clear();
+        return;
+    }
+
+    // Only the most recent cloud of each camera is displayed.
+    let mut received = false;
+    for PointCloudReceived(frame) in frames.read() {
+        latest.insert(frame.camera, frame.clone());
+        received = true;
+    }
+    let labels = labels.filter(|_| config.color_mode == ColorMode::Cluster);
+    // Rebuilt when the config changes too, e.g. to switch the color mode, and when the
+    // clusters the points are colored by change.
+    let relabeled = labels.as_ref().is_some_and(|labels| labels.is_changed());
+    if latest.is_empty() || (!received && !config.is_changed() && !relabeled) {
+        return;
+    }
+
+    // Hand the points to the transform shader instead, when the device can run it. Points
+    // are colored by cluster on the CPU, as the clusters are found there.
+    #[cfg(feature = "gpu_transform")]
+    let on_gpu = config.color_mode != ColorMode::Cluster
+        && render_device
+            .as_deref()
+            .is_some_and(gpu_transform::is_supported);
+
+    // An entity per camera, or a single one with every camera's points.
+    let groups: Vec<(Option<usize>, Vec<&Arc<Frame>>)> = if config.separate_clouds {
+        latest
+            .iter()
+            .map(|(&camera, frame)| (Some(camera), vec![frame]))
+            .collect()
+    } else {
+        vec![(None, latest.values().collect())]
+    };
+
+    let mut bounds = None;
+    let mut updated = Vec::new();
+    for (source, frames) in groups {
+        let existing = clouds
+            .iter()
+            .find(|(_, cloud_source)| cloud_source.map(|cloud_source| cloud_source.0) == source);
+        let entity = match existing {
+            Some((entity, _)) => entity,
+            None => {
+                let mut cloud = commands.spawn(cloud_bundle(&mut meshes, &config));
+                if let Some(camera) = source {
+                    cloud.insert(PointCloudSource(camera));
+                }
+                cloud.id()
+            }
+        };
+        updated.push(entity);
+
+        // The transform shader doesn't read confidence, so it would neither filter nor fade.
+        #[cfg(feature = "gpu_transform")]
+        if on_gpu && frames.iter().all(|frame| frame.confidence.is_none()) {
+            let cloud = RawPointCloud::new(&config, frames.into_iter().cloned());
+            bounds = merge_bounds(bounds, cloud.bounds(&config));
+            // Left over from the CPU path, e.g. before switching from `ColorMode::Cluster`.
+            commands
+                .entity(entity)
+                .insert(cloud)
+                .remove::<InstanceMaterialData>();
+            continue;
+        }
+
+        let (data, cloud_bounds) = cloud_instances(&config, &frames, labels.as_deref());
+        bounds = merge_bounds(bounds, cloud_bounds);
+        let mut cloud = commands.entity(entity);
+        cloud.insert(InstanceMaterialData(Arc::new(data)));
+        #[cfg(feature = "gpu_transform")]
+        cloud.remove::<RawPointCloud>();
+        // Faded by confidence, see `cloud_instances`.
+        if config.confidence_alpha && frames.iter().any(|frame| frame.confidence.is_some()) {
+            cloud.insert(TranslucentPoints);
+        } else {
+            cloud.remove::<TranslucentPoints>();
+        }
+    }
+
+    // Left over from before `separate_clouds` was switched.
+    for (entity, _) in &clouds {
+        if !updated.contains(&entity) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    if let Some(bounds) = bounds {
+        commands.insert_resource(OrbbecBounds(bounds));
+    }
+}
+
+/// Converts the points of `frames` into instances, returning them along with their bounds,
+/// or `None` when there are no points. Points are colored by cluster from `labels` when
+/// given.
+fn cloud_instances(
+    config: &OrbbecConfig,
+    frames: &[&Arc<Frame>],
+    labels: Option<&ClusterLabels>,
+) -> (Vec<InstanceData>, Option<Aabb>) {
+    let mut data = Vec::new();
+    // Bounds are accumulated as the points are converted, saving a pass over the cloud.
+    let (mut min, mut max) = (Vec3::MAX, Vec3::MIN);
+    for frame in frames {
+        let transform = config.camera_transform(frame.camera);
+        let start = data.len();
+        data.extend(
+            frame
+                .points
+                .iter()
+                .enumerate()
+                .filter_map(|(index, (position, color))| {
+                    let confidence = frame
+                        .confidence
+                        .as_ref()
+                        .map(|confidence| f32::from(confidence[index]) / 255.0);
+                    if confidence.is_some_and(|confidence| confidence < config.min_confidence) {
+                        return None;
+                    }
+                    let world = transform.transform_point(config.point_to_world(position));
+                    let mut color = match labels {
+                        Some(labels) => cluster_color(labels.cluster(frame.camera, position)),
+                        None => config.point_color(position, world, color),
+                    };
+                    if let Some(confidence) = confidence.filter(|_| config.confidence_alpha) {
+                        color = color.with_alpha(confidence);
+                    }
+                    Some(InstanceData {
+                        position: world,
+                        scale: config.render_style.instance_scale(config.world_scale),
+                        color: InstanceData::pack_color(color),
+                        normal: [0; 4],
+                    })
+                })
+                .inspect(|instance| {
+                    min = min.min(instance.position);
+                    max = max.max(instance.position);
+                }),
+        );
+
+        if let Some(radius_mm) = config.normal_radius_mm {
+            // Per camera, as each camera's normals face it.
+            let instances = &mut data[start..];
+            let positions: Vec<_> = instances.iter().map(|i| i.position).collect();
+            let camera = transform.translation;
+            let normals = estimate_normals(&positions, radius_mm * config.world_scale, camera);
+            for (instance, normal) in instances.iter_mut().zip(normals) {
+                instance.normal = InstanceData::pack_normal(normal);
+            }
+        }
+    }
+
+    let bounds = (!data.is_empty()).then(|| Aabb::from_min_max(min, max));
+    (data, bounds)
+}
+
+/// The smallest box containing both boxes, either of which may be missing.
+fn merge_bounds(a: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(Aabb::from_min_max(
+            a.min().min(b.min()).into(),
+            a.max().max(b.max()).into(),
+        )),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Everything the cloud entity needs besides its points.
+fn cloud_bundle(meshes: &mut Assets<Mesh>, config: &OrbbecConfig) -> impl Bundle {
+    (
+        meshes.add(point_mesh(config.render_style)),
+        SpatialBundle::INHERITED_IDENTITY,
+        config.render_style,
+        // NOTE: Frustum culling is done based on the Aabb of the Mesh and the GlobalTransform.
+        // As the cube is at the origin, if its Aabb moves outside the view frustum, all the
+        // instanced cubes will be culled.
+        // The InstanceMaterialData contains the 'GlobalTransform' information for this custom
+        // instancing, and that is not taken into account with the built-in frustum culling.
+        // We must disable the built-in frustum culling by adding the `NoFrustumCulling` marker
+        // component to avoid incorrect culling.
+        NoFrustumCulling,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    /// Checks every point of `frame` is colored from the depth colormap by its distance.
+    fn assert_depth_colored(config: &OrbbecConfig, frame: &Arc<Frame>, positions: &[Vec3]) {
+        let (instances, bounds) = cloud_instances(config, &[frame], None);
+        assert_eq!(instances.len(), positions.len());
+        assert!(bounds.is_some());
+        for (instance, position) in instances.iter().zip(positions) {
+            let expected = config.depth_color(position.length());
+            assert_eq!(instance.color, InstanceData::pack_color(expected));
+        }
+        assert_ne!(instances[0].color, instances[1].color);
+    }
+
+    #[test]
+    fn depth_only_points_use_the_depth_colormap() {
+        let positions = [Vec3::new(0.0, 0.0, 800.0), Vec3::new(0.0, 0.0, 3000.0)];
+        let frame = Arc::new(Frame {
+            camera: 0,
+            points: PointPayload::Depth(positions.to_vec()),
+            confidence: None,
+            device_timestamp_us: 0,
+            index: None,
+            system_time: SystemTime::now(),
+        });
+        assert!(frame.points.iter().all(|(_, color)| color.is_none()));
+
+        assert_depth_colored(&OrbbecConfig::default(), &frame, &positions);
+    }
+
+    #[test]
+    fn colorless_file_is_received_as_depth_only() {
+        let path =
+            std::env::temp_dir().join(format!("bevy_orbbec_depth_only_{}.ply", std::process::id()));
+        std::fs::write(
+            &path,
+            "ply\nformat ascii 1.0\nelement vertex 2\n\
+             property float x\nproperty float y\nproperty float z\nend_header\n\
+             10 -20 800\n-5 15 3000\n",
+        )
+        .unwrap();
+        let config = OrbbecConfig {
+            load_from: Some(path.clone()),
+            ..default()
+        };
+        let received = OrbbecRx::capture_once(config.clone(), Duration::from_secs(10));
+        std::fs::remove_file(&path).unwrap();
+        let frame = received.unwrap();
+
+        // The file's y is flipped back into the SDK's axes on load.
+        let positions = [Vec3::new(10.0, 20.0, 800.0), Vec3::new(-5.0, -15.0, 3000.0)];
+        match &frame.points {
+            PointPayload::Depth(points) => assert_eq!(points, &positions),
+            PointPayload::Color(_) => panic!("a file without colors loaded as colored"),
+        }
+        assert_depth_colored(&config, &Arc::new(frame), &positions);
+    }
+}
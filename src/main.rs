@@ -1,280 +1,230 @@
-//! A shader that renders a mesh multiple times in one draw call.
-
-mod orbbec;
-
-use bevy::{
-    core_pipeline::core_3d::Transparent3d,
-    ecs::{
-        query::QueryItem,
-        system::{lifetimeless::*, SystemParamItem},
-    },
-    pbr::{
-        MeshPipeline, MeshPipelineKey, RenderMeshInstances, SetMeshBindGroup, SetMeshViewBindGroup,
-    },
-    prelude::*,
-    render::{
-        extract_component::{ExtractComponent, ExtractComponentPlugin},
-        mesh::{GpuBufferInfo, GpuMesh, MeshVertexBufferLayoutRef},
-        render_asset::RenderAssets,
-        render_phase::{
-            AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
-            RenderCommandResult, SetItemPipeline, SortedRenderPhase, TrackedRenderPass,
-        },
-        render_resource::*,
-        renderer::RenderDevice,
-        view::{ExtractedView, NoFrustumCulling},
-        Render, RenderApp, RenderSet,
-    },
+use bevy::{color::palettes::css::AQUA, input::common_conditions::input_just_pressed, prelude::*};
+use bevy_orbbec::{
+    camera::fit_camera_to_cloud, CaptureBackground, ClearCloud, ClusterSettings, ConnectionState,
+    CropBox, CustomMaterialPlugin, ExportCloud, ExportFormat, EyeDomeLighting,
+    EyeDomeLightingPlugin, FrustumGizmos, GridGizmos, GroundInliers, GroundPlaneSettings,
+    OrbbecBackgroundPlugin, OrbbecBounds, OrbbecClusterPlugin, OrbbecConfig, OrbbecExportPlugin,
+    OrbbecFrustumPlugin, OrbbecGridPlugin, OrbbecGroundPlanePlugin, OrbbecMeasurePlugin,
+    OrbbecPickPlugin, OrbbecPlugin, OrbbecRx, OrbbecTrailPlugin, PointLod, PointTrail,
+    QuantizePositions,
 };
-use bytemuck::{Pod, Zeroable};
 
 fn main() {
-    unsafe { orbbec::do_the_thing(); }
     App::new()
-        .add_plugins((DefaultPlugins, CustomMaterialPlugin))
+        .add_plugins((
+            DefaultPlugins.set(AssetPlugin {
+                // Reload edited shaders, see the `shader_hot_reload` feature.
+                watch_for_changes_override: cfg!(feature = "shader_hot_reload").then_some(true),
+                ..default()
+            }),
+            CustomMaterialPlugin,
+            OrbbecPlugin::default(),
+            OrbbecPickPlugin,
+            OrbbecMeasurePlugin,
+            OrbbecExportPlugin,
+            EyeDomeLightingPlugin,
+            OrbbecTrailPlugin,
+            OrbbecGroundPlanePlugin,
+            OrbbecClusterPlugin,
+            OrbbecBackgroundPlugin,
+            OrbbecFrustumPlugin,
+            OrbbecGridPlugin,
+        ))
+        // Off until toggled with T.
+        .insert_resource(PointTrail {
+            trail_frames: 0,
+            ..default()
+        })
+        // Hidden until toggled with O.
+        .insert_resource(ClusterSettings {
+            draw_gizmos: false,
+            ..default()
+        })
+        // Hidden until toggled with V.
+        .insert_resource(FrustumGizmos {
+            enabled: false,
+            ..default()
+        })
         .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                show_errors,
+                show_connection_state.run_if(resource_changed::<ConnectionState>),
+                // Frame the cloud when it first appears, and again on F.
+                fit_camera_to_cloud.run_if(resource_added::<OrbbecBounds>),
+                fit_camera_to_cloud.run_if(input_just_pressed(KeyCode::KeyF)),
+                toggle_crop_box.run_if(input_just_pressed(KeyCode::KeyC)),
+                draw_crop_box,
+                toggle_pause.run_if(input_just_pressed(KeyCode::Space)),
+                toggle_quantization.run_if(input_just_pressed(KeyCode::KeyQ)),
+                toggle_lod.run_if(input_just_pressed(KeyCode::KeyL)),
+                toggle_eye_dome_lighting.run_if(input_just_pressed(KeyCode::KeyE)),
+                toggle_trail.run_if(input_just_pressed(KeyCode::KeyT)),
+                cycle_ground_inliers.run_if(input_just_pressed(KeyCode::KeyG)),
+                toggle_clusters.run_if(input_just_pressed(KeyCode::KeyO)),
+                capture_background.run_if(input_just_pressed(KeyCode::KeyB)),
+                clear_cloud.run_if(input_just_pressed(KeyCode::KeyX)),
+                toggle_frustums.run_if(input_just_pressed(KeyCode::KeyV)),
+                toggle_grid.run_if(input_just_pressed(KeyCode::KeyH)),
+                export_cloud(ExportFormat::Ply).run_if(input_just_pressed(KeyCode::KeyS)),
+                export_cloud(ExportFormat::Pcd).run_if(input_just_pressed(KeyCode::KeyP)),
+            ),
+        )
         .run();
 }
 
-fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+/// Marker for the text used to report capture errors.
+#[derive(Component)]
+struct OrbbecStatus;
+
+fn setup(mut commands: Commands, config: Res<OrbbecConfig>) {
+    // camera, 1.5m from the origin until the cloud arrives and it is framed
     commands.spawn((
-        meshes.add(Cuboid::new(0.5, 0.5, 0.5)),
-        SpatialBundle::INHERITED_IDENTITY,
-        InstanceMaterialData(
-            (1..=10)
-                .flat_map(|x| (1..=10).map(move |y| (x as f32 / 10.0, y as f32 / 10.0)))
-                .map(|(x, y)| InstanceData {
-                    position: Vec3::new(x * 10.0 - 5.0, y * 10.0 - 5.0, 0.0),
-                    scale: 1.0,
-                    color: LinearRgba::from(Color::hsla(x * 360., y, 0.5, 1.0)).to_f32_array(),
-                })
-                .collect(),
-        ),
-        // NOTE: Frustum culling is done based on the Aabb of the Mesh and the GlobalTransform.
-        // As the cube is at the origin, if its Aabb moves outside the view frustum, all the
-        // instanced cubes will be culled.
-        // The InstanceMaterialData contains the 'GlobalTransform' information for this custom
-        // instancing, and that is not taken into account with the built-in frustum culling.
-        // We must disable the built-in frustum culling by adding the `NoFrustumCulling` marker
-        // component to avoid incorrect culling.
-        NoFrustumCulling,
+        Camera3dBundle {
+            transform: Transform::from_xyz(0.0, 0.0, 1500.0 * config.world_scale)
+                .looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        },
+        EyeDomeLighting::default(),
     ));
 
-    // camera
-    commands.spawn(Camera3dBundle {
-        transform: Transform::from_xyz(0.0, 0.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y),
-        ..default()
-    });
+    commands.spawn((
+        TextBundle::from_section("", TextStyle::default()).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        }),
+        OrbbecStatus,
+    ));
 }
 
-#[derive(Component, Deref)]
-struct InstanceMaterialData(Vec<InstanceData>);
-
-impl ExtractComponent for InstanceMaterialData {
-    type QueryData = &'static InstanceMaterialData;
-    type QueryFilter = ();
-    type Out = Self;
-
-    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
-        Some(InstanceMaterialData(item.0.clone()))
+fn show_errors(orbbec: Res<OrbbecRx>, mut status: Query<&mut Text, With<OrbbecStatus>>) {
+    if let Some(err) = orbbec.try_get_error() {
+        for mut text in &mut status {
+            text.sections[0].value = format!("Camera stopped: {err}");
+        }
     }
 }
 
-struct CustomMaterialPlugin;
-
-impl Plugin for CustomMaterialPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_plugins(ExtractComponentPlugin::<InstanceMaterialData>::default());
-        app.sub_app_mut(RenderApp)
-            .add_render_command::<Transparent3d, DrawCustom>()
-            .init_resource::<SpecializedMeshPipelines<CustomPipeline>>()
-            .add_systems(
-                Render,
-                (
-                    queue_custom.in_set(RenderSet::QueueMeshes),
-                    prepare_instance_buffers.in_set(RenderSet::PrepareResources),
-                ),
-            );
+fn show_connection_state(
+    state: Res<ConnectionState>,
+    mut status: Query<&mut Text, With<OrbbecStatus>>,
+) {
+    let message = match *state {
+        ConnectionState::Reconnecting => "Camera disconnected, waiting for it to come back",
+        ConnectionState::NoFrames => "Camera connected but not sending frames",
+        ConnectionState::Connected => "",
+        // Leave any error shown by `show_errors`.
+        ConnectionState::Disconnected => return,
+    };
+    for mut text in &mut status {
+        text.sections[0].value = message.to_string();
     }
+}
 
-    fn finish(&self, app: &mut App) {
-        app.sub_app_mut(RenderApp).init_resource::<CustomPipeline>();
+fn toggle_pause(orbbec: Res<OrbbecRx>) {
+    if orbbec.is_paused() {
+        orbbec.resume();
+    } else {
+        orbbec.pause();
     }
 }
 
-#[derive(Clone, Copy, Pod, Zeroable)]
-#[repr(C)]
-struct InstanceData {
-    position: Vec3,
-    scale: f32,
-    color: [f32; 4],
+fn toggle_quantization(mut quantize: ResMut<QuantizePositions>) {
+    quantize.0 = !quantize.0;
+    info!(
+        "Position quantization {}",
+        if quantize.0 { "on" } else { "off" }
+    );
 }
 
-#[allow(clippy::too_many_arguments)]
-fn queue_custom(
-    transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
-    custom_pipeline: Res<CustomPipeline>,
-    msaa: Res<Msaa>,
-    mut pipelines: ResMut<SpecializedMeshPipelines<CustomPipeline>>,
-    pipeline_cache: Res<PipelineCache>,
-    meshes: Res<RenderAssets<GpuMesh>>,
-    render_mesh_instances: Res<RenderMeshInstances>,
-    material_meshes: Query<Entity, With<InstanceMaterialData>>,
-    mut views: Query<(&ExtractedView, &mut SortedRenderPhase<Transparent3d>)>,
-) {
-    let draw_custom = transparent_3d_draw_functions.read().id::<DrawCustom>();
-
-    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+fn toggle_lod(mut lod: ResMut<PointLod>) {
+    lod.enabled = !lod.enabled;
+    info!("Distance LOD {}", if lod.enabled { "on" } else { "off" });
+}
 
-    for (view, mut transparent_phase) in &mut views {
-        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
-        let rangefinder = view.rangefinder3d();
-        for entity in &material_meshes {
-            let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(entity) else {
-                continue;
-            };
-            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
-                continue;
-            };
-            let key =
-                view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology());
-            let pipeline = pipelines
-                .specialize(&pipeline_cache, &custom_pipeline, key, &mesh.layout)
-                .unwrap();
-            transparent_phase.add(Transparent3d {
-                entity,
-                pipeline,
-                draw_function: draw_custom,
-                distance: rangefinder.distance_translation(&mesh_instance.translation),
-                batch_range: 0..1,
-                extra_index: PhaseItemExtraIndex::NONE,
-            });
-        }
+fn toggle_eye_dome_lighting(mut lighting: Query<&mut EyeDomeLighting>) {
+    for mut lighting in &mut lighting {
+        lighting.strength = if lighting.strength > 0.0 {
+            0.0
+        } else {
+            EyeDomeLighting::default().strength
+        };
     }
 }
 
-#[derive(Component)]
-struct InstanceBuffer {
-    buffer: Buffer,
-    length: usize,
+fn toggle_trail(mut trail: ResMut<PointTrail>) {
+    trail.trail_frames = if trail.trail_frames > 0 {
+        0
+    } else {
+        PointTrail::default().trail_frames
+    };
 }
 
-fn prepare_instance_buffers(
-    mut commands: Commands,
-    query: Query<(Entity, &InstanceMaterialData)>,
-    render_device: Res<RenderDevice>,
-) {
-    for (entity, instance_data) in &query {
-        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            label: Some("instance data buffer"),
-            contents: bytemuck::cast_slice(instance_data.as_slice()),
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-        });
-        commands.entity(entity).insert(InstanceBuffer {
-            buffer,
-            length: instance_data.len(),
-        });
-    }
+/// Shows the ground plane's points as captured, then highlighted, then hidden.
+fn cycle_ground_inliers(mut settings: ResMut<GroundPlaneSettings>) {
+    settings.inliers = match settings.inliers {
+        GroundInliers::Show => GroundInliers::Color(Color::srgb(0.2, 0.8, 0.2)),
+        GroundInliers::Color(_) => GroundInliers::Hide,
+        GroundInliers::Hide => GroundInliers::Show,
+    };
 }
 
-#[derive(Resource)]
-struct CustomPipeline {
-    shader: Handle<Shader>,
-    mesh_pipeline: MeshPipeline,
+fn toggle_clusters(mut settings: ResMut<ClusterSettings>) {
+    settings.draw_gizmos = !settings.draw_gizmos;
 }
 
-impl FromWorld for CustomPipeline {
-    fn from_world(world: &mut World) -> Self {
-        let mesh_pipeline = world.resource::<MeshPipeline>();
+/// Takes the current scene as the background, so only what moves afterwards is shown.
+fn capture_background(mut capture: EventWriter<CaptureBackground>) {
+    capture.send(CaptureBackground);
+}
 
-        CustomPipeline {
-            shader: world.load_asset("shaders/instancing.wgsl"),
-            mesh_pipeline: mesh_pipeline.clone(),
-        }
-    }
+fn toggle_frustums(mut frustums: ResMut<FrustumGizmos>) {
+    frustums.enabled = !frustums.enabled;
 }
 
-impl SpecializedMeshPipeline for CustomPipeline {
-    type Key = MeshPipelineKey;
+fn toggle_grid(mut grid: ResMut<GridGizmos>) {
+    grid.enabled = !grid.enabled;
+}
 
-    fn specialize(
-        &self,
-        key: Self::Key,
-        layout: &MeshVertexBufferLayoutRef,
-    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
-        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+fn clear_cloud(mut clear: EventWriter<ClearCloud>) {
+    clear.send(ClearCloud);
+}
 
-        descriptor.vertex.shader = self.shader.clone();
-        descriptor.vertex.buffers.push(VertexBufferLayout {
-            array_stride: std::mem::size_of::<InstanceData>() as u64,
-            step_mode: VertexStepMode::Instance,
-            attributes: vec![
-                VertexAttribute {
-                    format: VertexFormat::Float32x4,
-                    offset: 0,
-                    shader_location: 3, // shader locations 0-2 are taken up by Position, Normal and UV attributes
-                },
-                VertexAttribute {
-                    format: VertexFormat::Float32x4,
-                    offset: VertexFormat::Float32x4.size(),
-                    shader_location: 4,
-                },
-            ],
+fn export_cloud(format: ExportFormat) -> impl FnMut(EventWriter<ExportCloud>) {
+    move |mut export| {
+        let path = match format {
+            ExportFormat::Ply => "cloud.ply",
+            ExportFormat::Pcd => "cloud.pcd",
+        };
+        export.send(ExportCloud {
+            path: path.into(),
+            format,
         });
-        descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
-        Ok(descriptor)
     }
 }
 
-type DrawCustom = (
-    SetItemPipeline,
-    SetMeshViewBindGroup<0>,
-    SetMeshBindGroup<1>,
-    DrawMeshInstanced,
-);
-
-struct DrawMeshInstanced;
-
-impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
-    type Param = (SRes<RenderAssets<GpuMesh>>, SRes<RenderMeshInstances>);
-    type ViewQuery = ();
-    type ItemQuery = Read<InstanceBuffer>;
-
-    #[inline]
-    fn render<'w>(
-        item: &P,
-        _view: (),
-        instance_buffer: Option<&'w InstanceBuffer>,
-        (meshes, render_mesh_instances): SystemParamItem<'w, '_, Self::Param>,
-        pass: &mut TrackedRenderPass<'w>,
-    ) -> RenderCommandResult {
-        let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(item.entity())
-            else {
-                return RenderCommandResult::Failure;
-            };
-        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
-            return RenderCommandResult::Failure;
-        };
-        let Some(instance_buffer) = instance_buffer else {
-            return RenderCommandResult::Failure;
+/// Crops to the middle half of the cloud, or turns cropping off again.
+fn toggle_crop_box(bounds: Option<Res<OrbbecBounds>>, mut crop_box: ResMut<CropBox>) {
+    if crop_box.enabled {
+        crop_box.enabled = false;
+    } else if let Some(bounds) = bounds {
+        let center = Vec3::from(bounds.center);
+        let half_extents = Vec3::from(bounds.half_extents) * 0.5;
+        *crop_box = CropBox {
+            min: center - half_extents,
+            max: center + half_extents,
+            enabled: true,
         };
+    }
+}
 
-        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
-        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
-
-        match &gpu_mesh.buffer_info {
-            GpuBufferInfo::Indexed {
-                buffer,
-                index_format,
-                count,
-            } => {
-                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
-                pass.draw_indexed(0..*count, 0, 0..instance_buffer.length as u32);
-            }
-            GpuBufferInfo::NonIndexed => {
-                pass.draw(0..gpu_mesh.vertex_count, 0..instance_buffer.length as u32);
-            }
-        }
-        RenderCommandResult::Success
+fn draw_crop_box(crop_box: Res<CropBox>, mut gizmos: Gizmos) {
+    if crop_box.enabled {
+        let transform = Transform::from_translation((crop_box.min + crop_box.max) / 2.0)
+            .with_scale(crop_box.max - crop_box.min);
+        gizmos.cuboid(transform, AQUA);
     }
 }
@@ -0,0 +1,51 @@
+//! Measuring the distance between two picked points.
+
+use crate::orbbec::OrbbecConfig;
+use crate::pick::PointPicked;
+use bevy::{color::palettes::css::YELLOW, prelude::*};
+
+/// Points picked for measuring, see [`OrbbecMeasurePlugin`].
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Measurement {
+    /// The picked points in world space, at most two.
+    pub points: Vec<Vec3>,
+    /// Distance between the two points in millimeters, once both are picked.
+    pub distance_mm: Option<f32>,
+}
+
+/// Measures between the first two [`PointPicked`] events, drawing a line between them. A third
+/// pick clears the measurement. Requires [`OrbbecPickPlugin`](crate::OrbbecPickPlugin).
+pub struct OrbbecMeasurePlugin;
+
+impl Plugin for OrbbecMeasurePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Measurement>()
+            .add_systems(Update, (measure, draw_measurement).chain());
+    }
+}
+
+fn measure(
+    config: Res<OrbbecConfig>,
+    mut picked: EventReader<PointPicked>,
+    mut measurement: ResMut<Measurement>,
+) {
+    for point in picked.read() {
+        if measurement.points.len() == 2 {
+            *measurement = Measurement::default();
+            continue;
+        }
+
+        measurement.points.push(point.world_pos);
+        if let [start, end] = measurement.points[..] {
+            let distance_mm = start.distance(end) / config.world_scale;
+            info!("Measured {distance_mm:.1}mm");
+            measurement.distance_mm = Some(distance_mm);
+        }
+    }
+}
+
+fn draw_measurement(measurement: Res<Measurement>, mut gizmos: Gizmos) {
+    if let [start, end] = measurement.points[..] {
+        gizmos.line(start, end, YELLOW);
+    }
+}
@@ -0,0 +1,229 @@
+//! Streaming point clouds to remote viewers over WebSocket, e.g. to capture on a headless
+//! machine and view elsewhere. Requires the `websocket` feature.
+//!
+//! Each frame is sent as a single binary message, compact enough to stream a full cloud at
+//! the camera's frame rate over a local network:
+//!
+//! | Field                 | Type         |
+//! |-----------------------|--------------|
+//! | Magic `OBPC`          | `[u8; 4]`    |
+//! | Version, `1`          | `u8`         |
+//! | Camera index          | `u8`         |
+//! | Colored               | `u8`, 0 or 1 |
+//! | Reserved              | `u8`         |
+//! | Device timestamp, µs  | `u64`        |
+//! | Point count           | `u32`        |
+//! | Points                | see below    |
+//!
+//! Each point is its position in millimeters, in the SDK's axes, quantized to three `i16`s,
+//! followed by its sRGB color as three `u8`s when the frame is colored. Integers are
+//! little-endian.
+
+use crate::frame::PointCloudReceived;
+use crate::orbbec::{lock, ColorPoint, Frame, PointPayload};
+use bevy::prelude::*;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tungstenite::Message;
+
+const MAGIC: &[u8; 4] = b"OBPC";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 20;
+
+/// Serializes a frame into the wire format described in the [module docs](self).
+pub fn encode_frame(frame: &Frame) -> Vec<u8> {
+    let colored = matches!(frame.points, PointPayload::Color(_));
+    let point_size = if colored { 9 } else { 6 };
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + frame.points.len() * point_size);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&[VERSION, frame.camera as u8, colored.into(), 0]);
+    bytes.extend_from_slice(&frame.device_timestamp_us.to_le_bytes());
+    bytes.extend_from_slice(&(frame.points.len() as u32).to_le_bytes());
+
+    // Float to int casts saturate, so points beyond ±32.7m are clamped.
+    let push_position = |bytes: &mut Vec<u8>, pos: Vec3| {
+        for v in pos.to_array() {
+            bytes.extend_from_slice(&(v.round() as i16).to_le_bytes());
+        }
+    };
+    match &frame.points {
+        PointPayload::Color(points) => {
+            for point in points {
+                push_position(&mut bytes, point.pos);
+                bytes.extend_from_slice(&point.color);
+            }
+        }
+        PointPayload::Depth(points) => {
+            for &pos in points {
+                push_position(&mut bytes, pos);
+            }
+        }
+    }
+    bytes
+}
+
+/// Parses a message in the wire format described in the [module docs](self), returning `None`
+/// if it is malformed. The frame's [`Frame::system_time`] is when it was decoded.
+pub fn decode_frame(bytes: &[u8]) -> Option<Frame> {
+    if bytes.len() < HEADER_SIZE {
+        return None;
+    }
+    let (header, body) = bytes.split_at(HEADER_SIZE);
+    if &header[..4] != MAGIC || header[4] != VERSION {
+        return None;
+    }
+    let camera = header[5].into();
+    let colored = header[6] != 0;
+    let device_timestamp_us = u64::from_le_bytes(header[8..16].try_into().ok()?);
+    let count = u32::from_le_bytes(header[16..20].try_into().ok()?) as usize;
+
+    let point_size = if colored { 9 } else { 6 };
+    if body.len() != count * point_size {
+        return None;
+    }
+    let position = |p: &[u8]| {
+        let [x, y, z] = [0, 2, 4].map(|i| i16::from_le_bytes([p[i], p[i + 1]]));
+        Vec3::new(x.into(), y.into(), z.into())
+    };
+    let points = if colored {
+        PointPayload::Color(
+            body.chunks_exact(point_size)
+                .map(|p| ColorPoint {
+                    pos: position(p),
+                    color: [p[6], p[7], p[8]],
+                })
+                .collect(),
+        )
+    } else {
+        PointPayload::Depth(body.chunks_exact(point_size).map(position).collect())
+    };
+
+    Some(Frame {
+        camera,
+        points,
+        confidence: None,
+        index: None,
+        device_timestamp_us,
+        system_time: SystemTime::now(),
+    })
+}
+
+/// Broadcasts every received point cloud to the WebSocket clients connected to a
+/// [`WebSocketSink`] listening on `addr`. Local rendering is unaffected, as the sink reads the
+/// same [`PointCloudReceived`] events as the renderer.
+pub struct WebSocketSinkPlugin {
+    pub addr: SocketAddr,
+}
+
+impl Default for WebSocketSinkPlugin {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(([0, 0, 0, 0], 9090)),
+        }
+    }
+}
+
+impl Plugin for WebSocketSinkPlugin {
+    fn build(&self, app: &mut App) {
+        match WebSocketSink::bind(self.addr) {
+            Ok(sink) => {
+                info!("Streaming point clouds on ws://{}", sink.local_addr());
+                app.insert_resource(sink);
+            }
+            Err(err) => error!("Failed to listen on {}: {err}", self.addr),
+        }
+        app.add_systems(
+            Update,
+            broadcast_frames.run_if(resource_exists::<WebSocketSink>),
+        );
+    }
+}
+
+/// Accepts WebSocket clients and sends them encoded frames, see the [module docs](self).
+///
+/// Each client is served by its own thread, holding at most one frame waiting to be sent. A
+/// client that can't keep up misses frames rather than slowing down the app or the other
+/// clients.
+#[derive(Resource)]
+pub struct WebSocketSink {
+    addr: SocketAddr,
+    clients: Arc<Mutex<Vec<SyncSender<Arc<Vec<u8>>>>>>,
+}
+
+impl WebSocketSink {
+    /// Starts listening for clients on `addr`.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+
+        let accepted = clients.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        warn!("Failed to accept WebSocket client: {err}");
+                        continue;
+                    }
+                };
+                let (tx, rx) = sync_channel(1);
+                lock(&accepted).push(tx);
+                std::thread::spawn(move || serve_client(stream, rx));
+            }
+        });
+
+        Ok(Self { addr, clients })
+    }
+
+    /// The address the sink is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Encodes `frame` and queues it for every connected client.
+    pub fn send(&self, frame: &Frame) {
+        let mut clients = lock(&self.clients);
+        if clients.is_empty() {
+            return;
+        }
+
+        let message = Arc::new(encode_frame(frame));
+        // Dropped for clients still sending the previous frame, and disconnected clients are
+        // forgotten.
+        clients.retain(|tx| {
+            !matches!(
+                tx.try_send(message.clone()),
+                Err(TrySendError::Disconnected(_))
+            )
+        });
+    }
+}
+
+fn serve_client(stream: TcpStream, rx: Receiver<Arc<Vec<u8>>>) {
+    let peer = stream.peer_addr().ok();
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("WebSocket handshake with {peer:?} failed: {err}");
+            return;
+        }
+    };
+    info!("WebSocket client {peer:?} connected");
+
+    for message in rx {
+        if let Err(err) = socket.send(Message::Binary(message.to_vec())) {
+            info!("WebSocket client {peer:?} disconnected: {err}");
+            return;
+        }
+    }
+}
+
+fn broadcast_frames(sink: Res<WebSocketSink>, mut frames: EventReader<PointCloudReceived>) {
+    for PointCloudReceived(frame) in frames.read() {
+        sink.send(frame);
+    }
+}
@@ -0,0 +1,98 @@
+//! Estimating the surface normal of each point, so the cloud can be lit.
+
+use bevy::{
+    math::{IVec3, Mat3, Vec3},
+    utils::HashMap,
+};
+use std::f32::consts::TAU;
+
+/// Fewest neighbors, including the point itself, a plane is fitted to. Points with fewer get
+/// a zero normal.
+const MIN_NEIGHBORS: usize = 3;
+
+/// Estimates the normal of each point as the normal of the plane best fitting its neighbors
+/// within `radius`, found by principal component analysis. Normals are turned to face
+/// `viewpoint`, usually the camera that captured the points, and are zero for isolated
+/// points.
+///
+/// Every point looks at the points in the 27 cells of size `radius` around it, so the cost
+/// grows with density. Downsample dense clouds first, e.g. with
+/// [`OrbbecConfig::voxel_size_mm`](crate::OrbbecConfig::voxel_size_mm).
+pub fn estimate_normals(positions: &[Vec3], radius: f32, viewpoint: Vec3) -> Vec<Vec3> {
+    if radius <= 0.0 {
+        return vec![Vec3::ZERO; positions.len()];
+    }
+
+    let cell = |position: Vec3| (position / radius).floor().as_ivec3();
+    let mut grid: HashMap<IVec3, Vec<usize>> = HashMap::default();
+    for (index, &position) in positions.iter().enumerate() {
+        grid.entry(cell(position)).or_default().push(index);
+    }
+
+    let radius_squared = radius * radius;
+    positions
+        .iter()
+        .map(|&position| {
+            // Moments of the neighbors relative to the point, which keeps the sums small.
+            let (mut count, mut sum, mut outer) = (0, Vec3::ZERO, Mat3::ZERO);
+            let center = cell(position);
+            for z in -1..=1 {
+                for y in -1..=1 {
+                    for x in -1..=1 {
+                        let Some(neighbors) = grid.get(&(center + IVec3::new(x, y, z))) else {
+                            continue;
+                        };
+                        for &neighbor in neighbors {
+                            let d = positions[neighbor] - position;
+                            if d.length_squared() <= radius_squared {
+                                count += 1;
+                                sum += d;
+                                outer += Mat3::from_cols(d * d.x, d * d.y, d * d.z);
+                            }
+                        }
+                    }
+                }
+            }
+            if count < MIN_NEIGHBORS {
+                return Vec3::ZERO;
+            }
+
+            let mean = sum / count as f32;
+            let covariance = outer * (1.0 / count as f32)
+                - Mat3::from_cols(mean * mean.x, mean * mean.y, mean * mean.z);
+            let normal = smallest_eigenvector(covariance);
+            if normal.dot(viewpoint - position) < 0.0 {
+                -normal
+            } else {
+                normal
+            }
+        })
+        .collect()
+}
+
+/// The unit eigenvector of the smallest eigenvalue of a symmetric matrix, or zero when it
+/// isn't unique, e.g. for collinear points.
+fn smallest_eigenvector(m: Mat3) -> Vec3 {
+    let off_diagonal = m.y_axis.x.powi(2) + m.z_axis.x.powi(2) + m.z_axis.y.powi(2);
+    let diagonal = Vec3::new(m.x_axis.x, m.y_axis.y, m.z_axis.z);
+    let smallest = if off_diagonal == 0.0 {
+        diagonal.min_element()
+    } else {
+        // The closed form solution of the characteristic cubic for symmetric matrices.
+        let q = diagonal.element_sum() / 3.0;
+        let p = (((diagonal - q).length_squared() + 2.0 * off_diagonal) / 6.0).sqrt();
+        let b = (m - Mat3::from_diagonal(Vec3::splat(q))) * (1.0 / p);
+        let phi = (b.determinant() / 2.0).clamp(-1.0, 1.0).acos() / 3.0;
+        q + 2.0 * p * (phi + TAU / 3.0).cos()
+    };
+
+    // The eigenvector is orthogonal to the rows of m - λI, so take the longest cross product
+    // of two of them to stay clear of nearly parallel rows.
+    let shifted = m - Mat3::from_diagonal(Vec3::splat(smallest));
+    let (r0, r1, r2) = (shifted.x_axis, shifted.y_axis, shifted.z_axis);
+    let candidate = [r0.cross(r1), r0.cross(r2), r1.cross(r2)]
+        .into_iter()
+        .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .unwrap_or(Vec3::ZERO);
+    candidate.try_normalize().unwrap_or(Vec3::ZERO)
+}
@@ -1,268 +1,3943 @@
+use crate::camera_param::OrbbecCameraParam;
+use crate::cluster::cluster_color;
+use crate::colormap::turbo;
+use crate::filter::{voxel_downsample, voxel_downsample_xyz};
+use crate::image::RgbaImage;
+use crate::imu::OrbbecImu;
+use crate::render::CropBox;
+use crate::temperature::OrbbecTemperature;
+use bevy::prelude::*;
+pub use builder::OrbbecConfigBuilder;
+#[cfg(feature = "sdk")]
 pub use orbbec_sdk::ob;
-use orbbec_sdk::{OBSensorType_OB_SENSOR_COLOR};
-use std::ffi::c_int;
-use std::process::exit;
-use std::ptr::{null_mut};
-
-unsafe fn check_error(error: *mut ob::ob_error) {
-    if !error.is_null() {
-        println!(
-            "ob_error was raised:\n\tcall: {:?}({:?})",
-            ob::ob_error_function(error),
-            ob::ob_error_args(error),
-        );
-        let msg =ob::ob_error_message(error);
-        let msg = std::ffi::CStr::from_ptr(msg).to_str().unwrap();
-        println!("\tmessage: {:?}", msg);
-        let msg = ob::ob_error_exception_type(error);
-        println!("\terror type: {:?}", msg);
-        ob::ob_delete_error(error);
-        exit(1);
-    }
-}
-
-pub unsafe fn do_the_thing() {
-    let mut error: *mut ob::ob_error = null_mut();
-    let mut ob_pipeline: *mut ob::ob_pipeline = null_mut(); // pipeline, used to open the Color and Depth streams after connecting the device
-
-    ob::ob_set_logger_severity(ob::OBLogSeverity_OB_LOG_SEVERITY_ERROR, &mut error);
-    check_error(error);
-
-    ob_pipeline = ob::ob_create_pipeline(&mut error);
-    check_error(error);
-
-    // Create config to configure the resolution, frame rate, and format of Color and Depth streams
-    let ob_config: *mut ob::ob_config = ob::ob_create_config(&mut error);
-    check_error(error);
-
-    let mut color_profile: *mut ob::ob_stream_profile = null_mut();
-    let color_profiles: *mut ob::ob_stream_profile_list = ob::ob_pipeline_get_stream_profile_list(
-        ob_pipeline,
-        OBSensorType_OB_SENSOR_COLOR,
-        &mut error,
-    );
-    if !error.is_null() {
-        println!("Current device is not support color sensor!");
-        ob::ob_delete_error(error);
-        error = null_mut();
-        // Turn on D2C alignment, which needs to be turned on when generating RGBD point clouds
-        ob::ob_config_set_align_mode(ob_config, ob::OBAlignMode_ALIGN_DISABLE, &mut error);
-        check_error(error);
-    }
-
-    // Open the default profile of Color Sensor, which can be configured through the configuration file
-    if !color_profile.is_null() {
-        color_profile = ob::ob_stream_profile_list_get_profile(
-            color_profiles,
+#[cfg(feature = "sdk")]
+use orbbec_sdk::OBSensorType_OB_SENSOR_COLOR;
+#[cfg(feature = "sdk")]
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::fmt;
+use std::ops::Range;
+#[cfg(feature = "sdk")]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "sdk")]
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "sdk")]
+use std::sync::mpsc::TryRecvError;
+use std::sync::mpsc::{
+    channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError,
+};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+mod builder;
+mod file;
+#[cfg(feature = "mock")]
+mod mock;
+#[cfg(feature = "websocket")]
+mod remote;
+
+/// The target of everything this module and the capture threads log, so the camera's logs
+/// can be filtered on their own, e.g. with `RUST_LOG=orbbec=debug`. Logs from a capture thread
+/// are in a span carrying the `camera` index and its `device`.
+pub const LOG_TARGET: &str = "orbbec";
+
+/// How long the capture loop blocks waiting for a frameset before checking for shutdown again.
+/// Kept well under a frame interval so dropping [`OrbbecRx`] returns promptly even when the
+/// camera has stopped sending frames.
+#[cfg(feature = "sdk")]
+const FRAME_TIMEOUT_MS: u32 = 10;
+/// How often [`OrbbecRx::capture_once`] checks for a capture error while waiting for a frame.
+const CAPTURE_ONCE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a paused capture loop idles between checks for resume or shutdown.
+#[cfg(feature = "sdk")]
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a capture thread blocked by [`DropPolicy::Block`] waits for room in the queue
+/// between checks for shutdown.
+const BLOCKED_SEND_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How long to wait before first looking for a disconnected device, doubled after every
+/// attempt up to [`RECONNECT_MAX_INTERVAL`].
+const RECONNECT_MIN_INTERVAL: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_INTERVAL: Duration = Duration::from_secs(8);
+/// How often the device temperature is read, see [`OrbbecTemperature`].
+#[cfg(feature = "sdk")]
+const TEMPERATURE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a capture thread waits for the config to settle before restarting with it, so a
+/// burst of changes, e.g. from dragging a slider, restarts the pipeline once.
+const RECONFIGURE_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// An error raised by the Orbbec SDK, copied out of the `ob_error` before it is deleted.
+#[derive(Debug, Clone)]
+pub struct OrbbecError {
+    pub function: String,
+    pub args: String,
+    pub message: String,
+    pub exception_type: ExceptionType,
+}
+
+impl fmt::Display for OrbbecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ob_error was raised: call: {}({}), message: {}, error type: {:?}",
+            self.function, self.args, self.message, self.exception_type
+        )
+    }
+}
+
+impl std::error::Error for OrbbecError {}
+
+impl OrbbecError {
+    /// An error detected by this crate rather than raised by the SDK, e.g. a configuration
+    /// that doesn't match the connected hardware.
+    fn invalid_value(function: &str, args: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            function: function.to_string(),
+            args: args.into(),
+            message: message.into(),
+            exception_type: ExceptionType::InvalidValue,
+        }
+    }
+
+    /// Whether the error was raised because the device went away, e.g. its cable was
+    /// unplugged.
+    pub fn is_disconnected(&self) -> bool {
+        self.exception_type == ExceptionType::CameraDisconnected
+    }
+
+    /// A capture thread panicked, with the panic's payload.
+    fn panicked(payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Self {
+            function: "capture thread".to_string(),
+            args: String::new(),
+            message: format!("panicked: {message}"),
+            exception_type: ExceptionType::Unknown,
+        }
+    }
+}
+
+/// What kind of failure an [`OrbbecError`] is, following the SDK's `OBExceptionType`. Errors
+/// detected by this crate use the closest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionType {
+    Unknown,
+    /// A C++ standard library exception inside the SDK.
+    StdException,
+    /// The device went away, see [`OrbbecError::is_disconnected`].
+    CameraDisconnected,
+    /// The USB or network stack of the platform failed.
+    Platform,
+    /// An argument or configuration value isn't valid for the device.
+    InvalidValue,
+    /// An SDK function was called at the wrong point, e.g. before the pipeline started.
+    WrongApiCallSequence,
+    NotImplemented,
+    Io,
+    Memory,
+    UnsupportedOperation,
+}
+
+impl ExceptionType {
+    #[cfg(feature = "sdk")]
+    fn from_ob(exception_type: ob::ob_exception_type) -> Self {
+        match exception_type {
+            ob::OBExceptionType_OB_EXCEPTION_STD_EXCEPTION => ExceptionType::StdException,
+            ob::OBExceptionType_OB_EXCEPTION_TYPE_CAMERA_DISCONNECTED => {
+                ExceptionType::CameraDisconnected
+            }
+            ob::OBExceptionType_OB_EXCEPTION_TYPE_PLATFORM => ExceptionType::Platform,
+            ob::OBExceptionType_OB_EXCEPTION_TYPE_INVALID_VALUE => ExceptionType::InvalidValue,
+            ob::OBExceptionType_OB_EXCEPTION_TYPE_WRONG_API_CALL_SEQUENCE => {
+                ExceptionType::WrongApiCallSequence
+            }
+            ob::OBExceptionType_OB_EXCEPTION_TYPE_NOT_IMPLEMENTED => ExceptionType::NotImplemented,
+            ob::OBExceptionType_OB_EXCEPTION_TYPE_IO => ExceptionType::Io,
+            ob::OBExceptionType_OB_EXCEPTION_TYPE_MEMORY => ExceptionType::Memory,
+            ob::OBExceptionType_OB_EXCEPTION_TYPE_UNSUPPORTED_OPERATION => {
+                ExceptionType::UnsupportedOperation
+            }
+            _ => ExceptionType::Unknown,
+        }
+    }
+}
+
+/// Locks `mutex` even if a thread panicked while holding it. Everything shared with the
+/// capture threads stays valid whatever point a panic interrupts, e.g. a receiver or the
+/// latest reading, so one thread's panic shouldn't take down every other user of the lock.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Configuration for the capture thread, read once when [`OrbbecRx`] is created.
+#[derive(Resource, Debug, Clone)]
+pub struct OrbbecConfig {
+    /// The device to open, over USB or the network.
+    pub device: DeviceSource,
+    /// Capture from several USB cameras at once, one capture thread each, instead of the
+    /// single camera given by [`Self::device`]. Their clouds are merged into one, each placed with
+    /// its own transform. Images, IMU samples, camera parameters and recordings only come
+    /// from the first camera.
+    pub serials: Vec<CameraSerial>,
+    /// Give each camera's cloud an entity of its own, marked with
+    /// [`PointCloudSource`](crate::PointCloudSource), instead of merging them into one. Each
+    /// can then be moved with its `Transform` or hidden with its `Visibility`. Read every
+    /// frame. A [`PointTrail`](crate::PointTrail) only follows one of them.
+    pub separate_clouds: bool,
+    /// Only hand the most recent point cloud of each camera to the app, skipping any older
+    /// ones still queued. See [`OrbbecRx::try_get_data`].
+    pub latest_only: bool,
+    /// How many point clouds each camera can queue for the app before [`Self::drop_policy`]
+    /// applies, bounding memory when the app stalls. At least one, defaults to `2`.
+    pub frame_queue: usize,
+    /// What the capture thread does with a new point cloud when the queue is full.
+    pub drop_policy: DropPolicy,
+    /// How the received point cloud is drawn.
+    pub render_style: RenderStyle,
+    /// Clamp the on-screen size of [`RenderStyle::Billboard`] points. `None` keeps their
+    /// fixed world size, so near points cover much of the view and far ones vanish.
+    pub size_attenuation: Option<SizeAttenuation>,
+    /// How translucent clouds, see [`TranslucentPoints`](crate::TranslucentPoints), resolve
+    /// overlapping points.
+    pub translucent_depth: TranslucentDepth,
+    /// How the points of every cloud, translucent or not, are combined with what is behind
+    /// them. See [`BlendMode::Additive`] for a glowing look.
+    pub blend_mode: BlendMode,
+    /// World units per millimeter, the unit the SDK reports point positions in. Defaults to
+    /// `0.001` so the cloud is rendered in meters.
+    pub world_scale: f32,
+    /// Flip the Y axis of each point. The SDK's Y axis points down, while Bevy's points up.
+    pub flip_y: bool,
+    /// Places the sensor in the world, e.g. when the camera is mounted at an angle. Each point
+    /// is converted from the SDK's millimeters in this order: scaled by [`Self::world_scale`],
+    /// flipped by [`Self::flip_y`], placed by its camera's [`CameraSerial::transform`] when
+    /// capturing from several, then by this transform, which then moves the whole rig. See
+    /// [`Self::camera_transform`]. Read every frame.
+    pub extrinsic: Transform,
+    /// Copy the color frame out of each frameset, see [`OrbbecColorImage`]. Off by default as
+    /// decoding MJPG frames costs time on the capture thread.
+    ///
+    /// [`OrbbecColorImage`]: crate::image::OrbbecColorImage
+    pub capture_color_image: bool,
+    /// Colorize the depth frame of each frameset, see [`OrbbecDepthImage`].
+    ///
+    /// [`OrbbecDepthImage`]: crate::image::OrbbecDepthImage
+    pub capture_depth_image: bool,
+    /// Enable the infrared stream and copy each IR frame out as a grayscale image, see
+    /// [`OrbbecIrImage`]. Not every device can stream IR and depth at the same time, in
+    /// which case starting the capture fails with an [`OrbbecError`].
+    ///
+    /// [`OrbbecIrImage`]: crate::image::OrbbecIrImage
+    pub capture_ir_image: bool,
+    /// Start the accelerometer and gyroscope and publish their readings as [`OrbbecImu`].
+    /// Capture fails with an [`OrbbecError`] if the device has no IMU.
+    pub enable_imu: bool,
+    /// Rotate the rendered cloud so the floor stays horizontal however the camera is tilted,
+    /// using the gravity direction measured by the IMU. Requires [`Self::enable_imu`], the
+    /// cloud is left unrotated until a sample arrives.
+    pub level_to_gravity: bool,
+    /// How much of the previous gravity estimate is kept when a new IMU sample arrives, in
+    /// `0.0..1.0`. Higher values are steadier but slower to follow the camera.
+    pub gravity_smoothing: f32,
+    /// Mirror the depth stream horizontally, e.g. for a camera facing the user. Applied when
+    /// the pipeline starts, skipped with a warning on devices that don't support it.
+    pub mirror_depth: bool,
+    /// Mirror the color stream horizontally, see [`Self::mirror_depth`].
+    pub mirror_color: bool,
+    /// Manual color exposure, in the device's units. Setting this turns auto-exposure off,
+    /// which avoids colors flickering under artificial light. `None` leaves the device's
+    /// exposure settings untouched.
+    pub color_exposure: Option<i32>,
+    /// Manual color gain, in the device's units. Like [`Self::color_exposure`], setting this
+    /// turns auto-exposure off.
+    pub color_gain: Option<i32>,
+    /// Turn the structured light laser on. With it off the depth stream is blank, which is
+    /// useful to avoid interference between cameras or to save power.
+    pub laser_enabled: bool,
+    /// Laser power level, in the device's units. `None` leaves the device's setting untouched.
+    pub laser_power: Option<i32>,
+    /// Record the streams to this Orbbec `.bag` file while capturing. The file is finalized
+    /// when capture stops.
+    pub record_to: Option<PathBuf>,
+    /// Play back this Orbbec `.bag` file, e.g. one written with [`Self::record_to`], instead
+    /// of capturing from a device. The recorded streams go through the same point cloud
+    /// filter, so the rest of the app can't tell the difference. Device settings such as
+    /// [`Self::device`] and [`Self::enable_imu`] are ignored.
+    ///
+    /// Playback always runs in real time, at the rate the file was recorded: the SDK paces
+    /// the frames itself and has no playback rate to speed it up or slow it down.
+    pub playback_from: Option<PathBuf>,
+    /// Restart playback from the beginning when the end of the file is reached, otherwise
+    /// capture stops there. With [`Self::load_from`], keep resending the loaded cloud as if
+    /// it were a live stream rather than sending it once.
+    pub playback_loop: bool,
+    /// Replay the points of a PLY or PCD file, e.g. one saved with
+    /// [`ExportCloud`](crate::export::ExportCloud), instead of capturing from a device. Points
+    /// are read in millimeters the way up they are rendered, and go through the same depth
+    /// range and voxel filters as a live cloud. Device settings are ignored.
+    pub load_from: Option<PathBuf>,
+    /// Receive point clouds from a `WebSocketSink` at this `ws://` URL instead of capturing
+    /// from a device, reconnecting whenever the connection drops. See
+    /// [`OrbbecRx::connection_state`]. Requires the `websocket` feature, without it capture
+    /// fails with an [`OrbbecError`].
+    pub connect_to: Option<String>,
+    /// When the device is disconnected, wait for it to come back, by serial if one is
+    /// configured, and resume capturing instead of stopping with an error. See
+    /// [`OrbbecRx::connection_state`].
+    pub reconnect: bool,
+    /// How long the device can go without delivering a frame before the connection state
+    /// becomes [`ConnectionState::NoFrames`] and a warning is logged. `None` waits forever.
+    /// Not applied to playback.
+    pub no_frames_timeout: Option<Duration>,
+    /// Restart the pipeline after every [`Self::no_frames_timeout`] without frames, which
+    /// recovers some devices from transient faults.
+    pub restart_on_no_frames: bool,
+    /// The most verbose messages the SDK logs, to its own console and file outputs rather
+    /// than through Bevy's. Errors raised by the SDK are reported either way.
+    pub log_severity: LogSeverity,
+    /// How the device synchronizes its capture with other devices, see [`SyncMode`]. For
+    /// several cameras set it per camera with [`CameraSerial::sync_mode`] instead. `None`
+    /// leaves the device's setting untouched.
+    pub sync_mode: Option<SyncMode>,
+    /// The color stream's resolution, frame rate and format. Unset fields take any value,
+    /// and the device's default profile is used when all are unset or nothing matches. See
+    /// [`DeviceInfo::color_profiles`] for what a device offers.
+    pub color_profile: StreamProfileSpec,
+    /// The depth stream's resolution, frame rate and format, like [`Self::color_profile`].
+    /// When aligned to color, only the depth profiles supporting alignment to the selected
+    /// color profile can match. Without a frame rate, the color stream's is preferred.
+    pub depth_profile: StreamProfileSpec,
+    /// How depth is aligned to color, which the colored point cloud relies on.
+    pub align_mode: AlignMode,
+    /// Sensors not to stream even when other settings would, e.g. to stay within the
+    /// bandwidth of a USB 2.0 link. Without [`SensorType::Color`] the cloud is uncolored,
+    /// and without [`SensorType::Accel`] or [`SensorType::Gyro`] the IMU reports only the
+    /// other. Depth can't be disabled, as the cloud is made from it. See
+    /// [`DeviceInfo::sensors`] for what a device has.
+    pub disabled_sensors: Vec<SensorType>,
+    /// How the points of the cloud are colored. Read every frame, so it can be changed at
+    /// runtime through the [`OrbbecConfig`] resource.
+    pub color_mode: ColorMode,
+    /// World space heights mapped to the ends of the colormap in
+    /// [`ColorMode::HeightColormap`].
+    pub height_colormap_range: Range<f32>,
+    /// Depth mapped to the start of the colormap, in millimeters. Used for the depth image
+    /// and for coloring points by depth, see [`Self::depth_color`].
+    pub depth_image_near_mm: f32,
+    /// Depth mapped to the end of the colormap, in millimeters.
+    pub depth_image_far_mm: f32,
+    /// Smooth the depth image with the SDK's edge-preserving spatial filter before the point
+    /// cloud is generated, see [`SpatialFilter`]. The cloud jitters less, at the cost of some
+    /// rounding at the edges of objects. `None`, the default, leaves depth unfiltered.
+    pub spatial_filter: Option<SpatialFilter>,
+    /// Blend depth across frames with the SDK's temporal filter, after the spatial filter,
+    /// see [`TemporalFilter`]. Removes the flicker on flat surfaces that remains after
+    /// spatial smoothing, so together they give a cloud steady enough to measure. `None`, the
+    /// default, leaves depth unfiltered.
+    pub temporal_filter: Option<TemporalFilter>,
+    /// Fill the pixels missing from the depth image, e.g. at object edges and on dark or
+    /// reflective surfaces, with the SDK's hole filling filter, so they become plausible
+    /// points rather than gaps in the surface. Runs last, after
+    /// [`Self::spatial_filter`] and [`Self::temporal_filter`], so the filled values come from
+    /// smoothed depth. `None`, the default, leaves the holes.
+    pub hole_filling: Option<HoleFilling>,
+    /// Points closer than this, in millimeters, are dropped before being sent.
+    pub depth_min_mm: Option<f32>,
+    /// Points farther than this, in millimeters, are dropped before being sent.
+    pub depth_max_mm: Option<f32>,
+    /// Drop the points outside this box on the capture thread, saving the cost of sending,
+    /// converting and uploading them. The box is in world space as the renderer places the
+    /// points, after [`Self::point_to_world`] and [`Self::camera_transform`], but before the
+    /// cloud entity's own `Transform`. Unlike the [`CropBox`] resource it is read when capture
+    /// starts, see [`OrbbecRx::reconfigure`]. Disabled by default.
+    pub capture_crop: CropBox,
+    /// Keep only every nth point the SDK generates, as it is copied out, before any other
+    /// filter. Much cheaper than [`Self::voxel_size_mm`] for thinning the cloud, though the
+    /// points kept aren't spread evenly in space. `1`, the default, keeps every point. Can be
+    /// changed without restarting with [`OrbbecRx::set_subsample`].
+    pub subsample: usize,
+    /// Downsample the cloud to one point per cube of this size, in millimeters, before it is
+    /// sent. Larger values give a coarser cloud with fewer points, see
+    /// [`voxel_downsample`](crate::filter::voxel_downsample).
+    pub voxel_size_mm: Option<f32>,
+    /// Estimate a normal for each point from its neighbors within this radius, in
+    /// millimeters, so the cloud is drawn lit. Costly for dense clouds, see
+    /// [`estimate_normals`](crate::normals::estimate_normals). Not supported by the
+    /// `gpu_transform` feature.
+    pub normal_radius_mm: Option<f32>,
+    /// Fade each point by its [`Frame::confidence`], drawing the cloud translucent, so noisy
+    /// edges blend away. Clouds without confidence stay opaque. Off by default, as translucent
+    /// clouds are sorted and blended rather than drawn opaque. Clouds with confidence are
+    /// converted on the CPU even with the `gpu_transform` feature.
+    pub confidence_alpha: bool,
+    /// Drop the points whose [`Frame::confidence`], from 0 to 1, is below this. Points without
+    /// confidence are always kept. Like [`Self::confidence_alpha`], clouds with confidence
+    /// are converted on the CPU even with the `gpu_transform` feature.
+    pub min_confidence: f32,
+}
+
+impl Default for OrbbecConfig {
+    fn default() -> Self {
+        Self {
+            device: DeviceSource::default(),
+            serials: Vec::new(),
+            separate_clouds: false,
+            latest_only: false,
+            frame_queue: 2,
+            drop_policy: DropPolicy::default(),
+            render_style: RenderStyle::default(),
+            size_attenuation: None,
+            translucent_depth: TranslucentDepth::default(),
+            blend_mode: BlendMode::default(),
+            world_scale: 0.001,
+            flip_y: true,
+            extrinsic: Transform::IDENTITY,
+            capture_color_image: false,
+            capture_depth_image: false,
+            capture_ir_image: false,
+            enable_imu: false,
+            level_to_gravity: false,
+            gravity_smoothing: 0.9,
+            mirror_depth: false,
+            mirror_color: false,
+            color_exposure: None,
+            color_gain: None,
+            laser_enabled: true,
+            laser_power: None,
+            record_to: None,
+            playback_from: None,
+            playback_loop: true,
+            load_from: None,
+            connect_to: None,
+            reconnect: true,
+            no_frames_timeout: Some(Duration::from_secs(2)),
+            restart_on_no_frames: false,
+            log_severity: LogSeverity::default(),
+            sync_mode: None,
+            color_profile: StreamProfileSpec::default(),
+            depth_profile: StreamProfileSpec::default(),
+            align_mode: AlignMode::default(),
+            disabled_sensors: Vec::new(),
+            color_mode: ColorMode::default(),
+            height_colormap_range: -1.0..1.0,
+            depth_image_near_mm: 500.0,
+            depth_image_far_mm: 4000.0,
+            spatial_filter: None,
+            temporal_filter: None,
+            hole_filling: None,
+            depth_min_mm: None,
+            depth_max_mm: None,
+            capture_crop: CropBox::default(),
+            subsample: 1,
+            voxel_size_mm: None,
+            normal_radius_mm: None,
+            confidence_alpha: false,
+            min_confidence: 0.2,
+        }
+    }
+}
+
+/// Where to find the device to capture from, see [`OrbbecConfig::device`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceSource {
+    /// A device connected over USB: the one with this serial number or, when `None`, the
+    /// SDK's default device, which is nondeterministic when several cameras are connected.
+    Usb { serial: Option<String> },
+    /// A device connected over Ethernet, such as the Femto Mega, at this IP address and port.
+    /// If it can't be reached capture stops with an error, see [`OrbbecRx::try_get_error`].
+    Net { ip: String, port: u16 },
+}
+
+impl Default for DeviceSource {
+    fn default() -> Self {
+        Self::Usb { serial: None }
+    }
+}
+
+impl fmt::Display for DeviceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usb {
+                serial: Some(serial),
+            } => write!(f, "usb:{serial}"),
+            Self::Usb { serial: None } => write!(f, "usb:default"),
+            Self::Net { ip, port } => write!(f, "{ip}:{port}"),
+        }
+    }
+}
+
+/// One of several cameras to capture from, see [`OrbbecConfig::serials`].
+#[derive(Debug, Clone, Default)]
+pub struct CameraSerial {
+    pub serial: String,
+    /// Places this camera's cloud in the shared world frame, applied after the points are
+    /// converted with [`OrbbecConfig::point_to_world`] and before
+    /// [`OrbbecConfig::extrinsic`].
+    pub transform: Transform,
+    /// This camera's [`OrbbecConfig::sync_mode`], e.g. [`SyncMode::Primary`] for one camera
+    /// and [`SyncMode::Secondary`] for the others.
+    pub sync_mode: Option<SyncMode>,
+}
+
+impl OrbbecConfig {
+    /// The transform placing the cloud of the camera at `camera`, an index into
+    /// [`Self::serials`], after [`Self::point_to_world`]: its [`CameraSerial::transform`]
+    /// followed by [`Self::extrinsic`]. Just the extrinsic for a single camera.
+    pub fn camera_transform(&self, camera: usize) -> Transform {
+        let camera = self
+            .serials
+            .get(camera)
+            .map(|camera| camera.transform)
+            .unwrap_or_default();
+        self.extrinsic * camera
+    }
+
+    /// The config of each capture thread, one per camera.
+    fn capture_configs(&self) -> Vec<OrbbecConfig> {
+        if self.serials.is_empty()
+            || self.playback_from.is_some()
+            || self.load_from.is_some()
+            || self.connect_to.is_some()
+        {
+            return vec![self.clone()];
+        }
+
+        self.serials
+            .iter()
+            .enumerate()
+            .map(|(index, camera)| {
+                let mut config = self.clone();
+                config.device = DeviceSource::Usb {
+                    serial: Some(camera.serial.clone()),
+                };
+                config.sync_mode = camera.sync_mode.or(self.sync_mode);
+                if index > 0 {
+                    config.capture_color_image = false;
+                    config.capture_depth_image = false;
+                    config.capture_ir_image = false;
+                    config.enable_imu = false;
+                    config.record_to = None;
+                }
+                config
+            })
+            .collect()
+    }
+
+    /// Whether `sensor` isn't in [`Self::disabled_sensors`].
+    pub fn sensor_enabled(&self, sensor: SensorType) -> bool {
+        !self.disabled_sensors.contains(&sensor)
+    }
+
+    /// Converts a point position from the SDK's millimeters into world space.
+    pub fn point_to_world(&self, position: Vec3) -> Vec3 {
+        let y = if self.flip_y { -position.y } else { position.y };
+        Vec3::new(position.x, y, position.z) * self.world_scale
+    }
+
+    /// Whether a depth, in millimeters, is within [`Self::depth_min_mm`] and
+    /// [`Self::depth_max_mm`].
+    pub fn in_depth_range(&self, z_mm: f32) -> bool {
+        self.depth_min_mm.map_or(true, |min| z_mm >= min)
+            && self.depth_max_mm.map_or(true, |max| z_mm <= max)
+    }
+
+    /// Whether a point of the camera at `camera`, positioned in the SDK's millimeters, is
+    /// within [`Self::capture_crop`].
+    pub fn in_capture_crop(&self, camera: usize, position: Vec3) -> bool {
+        let world = self.point_to_world(position);
+        self.capture_crop
+            .contains(self.camera_transform(camera).transform_point(world))
+    }
+
+    /// Colors a depth, in millimeters, with the turbo colormap spread over
+    /// [`Self::depth_image_near_mm`] to [`Self::depth_image_far_mm`].
+    pub fn depth_color(&self, depth_mm: f32) -> Color {
+        let t = (depth_mm - self.depth_image_near_mm)
+            / (self.depth_image_far_mm - self.depth_image_near_mm);
+        let [r, g, b] = turbo(t);
+        Color::srgb(r, g, b)
+    }
+
+    /// The color of a point according to [`Self::color_mode`]. `position` is in the SDK's
+    /// millimeters, `world` the same point in world space, and `color` the point's own color
+    /// if it has one. Uncolored points fall back to the depth colormap in [`ColorMode::Rgb`].
+    pub fn point_color(&self, position: Vec3, world: Vec3, color: Option<Color>) -> Color {
+        match self.color_mode {
+            ColorMode::Rgb => color.unwrap_or_else(|| self.depth_color(position.length())),
+            ColorMode::DepthColormap => self.depth_color(position.length()),
+            // Colored from `ClusterLabels` by the caller, which knows the point's cluster.
+            ColorMode::Cluster => cluster_color(None),
+            ColorMode::HeightColormap => {
+                let range = &self.height_colormap_range;
+                let [r, g, b] = turbo((world.y - range.start) / (range.end - range.start));
+                Color::srgb(r, g, b)
+            }
+        }
+    }
+}
+
+/// A requested video stream profile, see [`OrbbecConfig::color_profile`]. `None` fields
+/// match any value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamProfileSpec {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<u32>,
+    pub format: Option<StreamFormat>,
+}
+
+impl StreamProfileSpec {
+    /// Whether every field is unset, leaving the choice to the device.
+    pub fn is_any(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether `profile` satisfies every field that is set.
+    pub fn matches(&self, profile: &StreamProfile) -> bool {
+        self.width.map_or(true, |width| width == profile.width)
+            && self.height.map_or(true, |height| height == profile.height)
+            && self.fps.map_or(true, |fps| fps == profile.fps)
+            && self.format.map_or(true, |format| format == profile.format)
+    }
+}
+
+/// A video stream profile offered by a device, see [`DeviceInfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamProfile {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub format: StreamFormat,
+}
+
+impl fmt::Display for StreamProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} at {} fps, format {:?}",
+            self.width, self.height, self.fps, self.format
+        )
+    }
+}
+
+/// The pixel format of a video stream, see [`StreamProfile`]. Each maps to the SDK's
+/// `OB_FORMAT_*` of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamFormat {
+    Yuyv,
+    Uyvy,
+    Nv12,
+    Nv21,
+    I420,
+    Mjpg,
+    Rgb,
+    Bgr,
+    Rgba,
+    Bgra,
+    Gray,
+    Y8,
+    Y16,
+    Z16,
+    /// Any other format, by the SDK's `OBFormat` value.
+    Other(u32),
+}
+
+#[cfg(feature = "sdk")]
+impl StreamFormat {
+    fn from_ob(format: ob::OBFormat) -> Self {
+        match format {
+            ob::OBFormat_OB_FORMAT_YUYV => StreamFormat::Yuyv,
+            ob::OBFormat_OB_FORMAT_UYVY => StreamFormat::Uyvy,
+            ob::OBFormat_OB_FORMAT_NV12 => StreamFormat::Nv12,
+            ob::OBFormat_OB_FORMAT_NV21 => StreamFormat::Nv21,
+            ob::OBFormat_OB_FORMAT_I420 => StreamFormat::I420,
+            ob::OBFormat_OB_FORMAT_MJPG => StreamFormat::Mjpg,
+            ob::OBFormat_OB_FORMAT_RGB => StreamFormat::Rgb,
+            ob::OBFormat_OB_FORMAT_BGR => StreamFormat::Bgr,
+            ob::OBFormat_OB_FORMAT_RGBA => StreamFormat::Rgba,
+            ob::OBFormat_OB_FORMAT_BGRA => StreamFormat::Bgra,
+            ob::OBFormat_OB_FORMAT_GRAY => StreamFormat::Gray,
+            ob::OBFormat_OB_FORMAT_Y8 => StreamFormat::Y8,
+            ob::OBFormat_OB_FORMAT_Y16 => StreamFormat::Y16,
+            ob::OBFormat_OB_FORMAT_Z16 => StreamFormat::Z16,
+            other => StreamFormat::Other(other as u32),
+        }
+    }
+
+    fn to_ob(self) -> ob::OBFormat {
+        match self {
+            StreamFormat::Yuyv => ob::OBFormat_OB_FORMAT_YUYV,
+            StreamFormat::Uyvy => ob::OBFormat_OB_FORMAT_UYVY,
+            StreamFormat::Nv12 => ob::OBFormat_OB_FORMAT_NV12,
+            StreamFormat::Nv21 => ob::OBFormat_OB_FORMAT_NV21,
+            StreamFormat::I420 => ob::OBFormat_OB_FORMAT_I420,
+            StreamFormat::Mjpg => ob::OBFormat_OB_FORMAT_MJPG,
+            StreamFormat::Rgb => ob::OBFormat_OB_FORMAT_RGB,
+            StreamFormat::Bgr => ob::OBFormat_OB_FORMAT_BGR,
+            StreamFormat::Rgba => ob::OBFormat_OB_FORMAT_RGBA,
+            StreamFormat::Bgra => ob::OBFormat_OB_FORMAT_BGRA,
+            StreamFormat::Gray => ob::OBFormat_OB_FORMAT_GRAY,
+            StreamFormat::Y8 => ob::OBFormat_OB_FORMAT_Y8,
+            StreamFormat::Y16 => ob::OBFormat_OB_FORMAT_Y16,
+            StreamFormat::Z16 => ob::OBFormat_OB_FORMAT_Z16,
+            StreamFormat::Other(format) => format as ob::OBFormat,
+        }
+    }
+}
+
+/// Reads the width, height, frame rate and format of each video profile in `list`.
+#[cfg(feature = "sdk")]
+unsafe fn video_profiles(
+    list: *mut ob::ob_stream_profile_list,
+    error: &mut *mut ob::ob_error,
+) -> Result<Vec<StreamProfile>, OrbbecError> {
+    let count = ob::ob_stream_profile_list_count(list, error);
+    check_error(error)?;
+    let mut profiles = Vec::new();
+    for index in 0..count {
+        let profile = ob::ob_stream_profile_list_get_profile(list, index as c_int, error);
+        check_error(error)?;
+        let read = read_video_profile(profile, error);
+        ob::ob_delete_stream_profile(profile, error);
+        check_error(error)?;
+        profiles.push(read?);
+    }
+    Ok(profiles)
+}
+
+#[cfg(feature = "sdk")]
+unsafe fn read_video_profile(
+    profile: *mut ob::ob_stream_profile,
+    error: &mut *mut ob::ob_error,
+) -> Result<StreamProfile, OrbbecError> {
+    let width = ob::ob_video_stream_profile_width(profile, error);
+    check_error(error)?;
+    let height = ob::ob_video_stream_profile_height(profile, error);
+    check_error(error)?;
+    let fps = ob::ob_video_stream_profile_fps(profile, error);
+    check_error(error)?;
+    let format = ob::ob_stream_profile_format(profile, error);
+    check_error(error)?;
+    Ok(StreamProfile {
+        width,
+        height,
+        fps,
+        format: StreamFormat::from_ob(format),
+    })
+}
+
+/// A kind of sensor a device can have, see [`DeviceInfo::sensors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SensorType {
+    Ir,
+    Color,
+    Depth,
+    Accel,
+    Gyro,
+    IrLeft,
+    IrRight,
+    RawPhase,
+}
+
+impl SensorType {
+    #[cfg(feature = "sdk")]
+    #[cfg_attr(feature = "mock", allow(dead_code))]
+    fn from_ob(sensor_type: ob::OBSensorType) -> Option<Self> {
+        match sensor_type {
+            ob::OBSensorType_OB_SENSOR_IR => Some(SensorType::Ir),
+            ob::OBSensorType_OB_SENSOR_COLOR => Some(SensorType::Color),
+            ob::OBSensorType_OB_SENSOR_DEPTH => Some(SensorType::Depth),
+            ob::OBSensorType_OB_SENSOR_ACCEL => Some(SensorType::Accel),
+            ob::OBSensorType_OB_SENSOR_GYRO => Some(SensorType::Gyro),
+            ob::OBSensorType_OB_SENSOR_IR_LEFT => Some(SensorType::IrLeft),
+            ob::OBSensorType_OB_SENSOR_IR_RIGHT => Some(SensorType::IrRight),
+            ob::OBSensorType_OB_SENSOR_RAW_PHASE => Some(SensorType::RawPhase),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "sdk")]
+    #[cfg_attr(feature = "mock", allow(dead_code))]
+    fn to_ob(self) -> ob::OBSensorType {
+        match self {
+            SensorType::Ir => ob::OBSensorType_OB_SENSOR_IR,
+            SensorType::Color => ob::OBSensorType_OB_SENSOR_COLOR,
+            SensorType::Depth => ob::OBSensorType_OB_SENSOR_DEPTH,
+            SensorType::Accel => ob::OBSensorType_OB_SENSOR_ACCEL,
+            SensorType::Gyro => ob::OBSensorType_OB_SENSOR_GYRO,
+            SensorType::IrLeft => ob::OBSensorType_OB_SENSOR_IR_LEFT,
+            SensorType::IrRight => ob::OBSensorType_OB_SENSOR_IR_RIGHT,
+            SensorType::RawPhase => ob::OBSensorType_OB_SENSOR_RAW_PHASE,
+        }
+    }
+
+    /// Whether the sensor streams images, and so has [`StreamProfile`]s. The IMU's don't.
+    pub fn is_video(self) -> bool {
+        !matches!(self, SensorType::Accel | SensorType::Gyro)
+    }
+}
+
+/// A sensor of a device, see [`DeviceInfo::sensors`].
+#[derive(Debug, Clone)]
+pub struct SensorInfo {
+    pub sensor_type: SensorType,
+    /// The profiles it can stream. Empty for the IMU's sensors, see [`SensorType::is_video`].
+    pub profiles: Vec<StreamProfile>,
+}
+
+/// Bytes per pixel of uncompressed video `format`, or `None` for compressed and unknown
+/// formats, whose size depends on the content.
+#[cfg(feature = "sdk")]
+#[cfg_attr(feature = "mock", allow(dead_code))]
+fn bytes_per_pixel(format: StreamFormat) -> Option<f32> {
+    match format {
+        StreamFormat::Y8 | StreamFormat::Gray => Some(1.0),
+        StreamFormat::Nv12 | StreamFormat::Nv21 | StreamFormat::I420 => Some(1.5),
+        StreamFormat::Y16 | StreamFormat::Z16 | StreamFormat::Yuyv | StreamFormat::Uyvy => {
+            Some(2.0)
+        }
+        StreamFormat::Rgb | StreamFormat::Bgr => Some(3.0),
+        StreamFormat::Rgba | StreamFormat::Bgra => Some(4.0),
+        StreamFormat::Mjpg | StreamFormat::Other(_) => None,
+    }
+}
+
+/// How much video a USB 2.0 link carries in practice, in bytes per second: well below its
+/// nominal 480 Mbit/s once protocol overhead is taken out.
+#[cfg(feature = "sdk")]
+#[cfg_attr(feature = "mock", allow(dead_code))]
+const USB2_VIDEO_BANDWIDTH: f32 = 35_000_000.0;
+
+/// How the depth stream is aligned to the color stream (D2C), see
+/// [`OrbbecConfig::align_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AlignMode {
+    /// Prefer hardware alignment, then software alignment, then no alignment, depending on
+    /// what the device supports for the color profile.
+    #[default]
+    Auto,
+    /// Align in hardware, failing to start if the device can't.
+    HardwareOnly,
+    /// Align in software on the host, failing to start if the device can't.
+    SoftwareOnly,
+    /// Don't align. Points are then colored from a color image that doesn't line up with
+    /// the depth image.
+    Disabled,
+}
+
+/// How the points of the cloud are colored, see [`OrbbecConfig::color_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// The color of each point from the color stream.
+    #[default]
+    Rgb,
+    /// The distance of each point from the camera, through the turbo colormap.
+    DepthColormap,
+    /// The height of each point in world space, through the turbo colormap.
+    HeightColormap,
+    /// A distinct color for each of the [`ObjectClusters`](crate::ObjectClusters), cycling
+    /// through a palette, with points outside any cluster in gray. Requires
+    /// [`OrbbecClusterPlugin`](crate::OrbbecClusterPlugin), and colors on the CPU even with
+    /// the `gpu_transform` feature.
+    Cluster,
+}
+
+/// How each point of the cloud is drawn.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub enum RenderStyle {
+    /// An instanced cube per point.
+    #[default]
+    Cubes,
+    /// A single pixel per point, drawn with a point list topology. One vertex per point
+    /// rather than a cube's 36, so much cheaper for dense clouds.
+    Points,
+    /// A round, camera-facing quad per point, `size` world units across.
+    Billboard { size: f32 },
+}
+
+/// Limits on the size of billboards on screen, see [`OrbbecConfig::size_attenuation`].
+/// Billboards still shrink with distance through the perspective projection, but only down
+/// to `min_size` and up to `max_size` pixels across, so the cloud keeps a similar apparent
+/// density at any distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeAttenuation {
+    /// Smallest size, in physical pixels.
+    pub min_size: f32,
+    /// Largest size, in physical pixels.
+    pub max_size: f32,
+}
+
+impl Default for SizeAttenuation {
+    fn default() -> Self {
+        Self {
+            min_size: 2.0,
+            max_size: 16.0,
+        }
+    }
+}
+
+/// Parameters of the SDK's spatial filter, see [`OrbbecConfig::spatial_filter`]. The filter
+/// smooths each depth pixel with its neighbors along the rows and columns of the image, but
+/// not across jumps in depth, so object edges stay in place.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpatialFilter {
+    /// How much of each neighbor is blended in, from 0 to 1. Lower values smooth more.
+    pub alpha: f32,
+    /// How many times the image is filtered, from 1 to 5. More passes smooth more.
+    pub magnitude: u8,
+    /// How far neighbors are taken from, in pixels.
+    pub radius: u16,
+    /// Neighbors differing by more than this, in the depth image's units, are across an edge
+    /// and left out.
+    pub disp_diff: u16,
+}
+
+impl Default for SpatialFilter {
+    /// The SDK's defaults.
+    fn default() -> Self {
+        Self {
+            alpha: 0.5,
+            magnitude: 1,
+            radius: 1,
+            disp_diff: 160,
+        }
+    }
+}
+
+/// Parameters of the SDK's temporal filter, see [`OrbbecConfig::temporal_filter`]. Each depth
+/// pixel is blended with its filtered value from the previous frames, unless it moved by more
+/// than [`Self::delta`], in which case it starts over from the new value, so moving objects
+/// don't leave ghosts behind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemporalFilter {
+    /// The weight of the new frame, from 0.1 to 0.9. Lower values smooth more but follow
+    /// changes more slowly.
+    pub alpha: f32,
+    /// How much a pixel can change, as a fraction of its depth from 0.1 to 0.5, and still be
+    /// blended rather than reset.
+    pub delta: f32,
+}
+
+impl Default for TemporalFilter {
+    /// The SDK's defaults.
+    fn default() -> Self {
+        Self {
+            alpha: 0.4,
+            delta: 0.1,
+        }
+    }
+}
+
+/// Where the SDK's hole filling filter takes the depth of a missing pixel from, see
+/// [`OrbbecConfig::hole_filling`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HoleFilling {
+    /// The valid pixel above it, which suits holes in the shadow of an object.
+    Top,
+    /// The nearest of its valid neighbors, which keeps holes from growing the background
+    /// into foreground objects.
+    #[default]
+    Nearest,
+    /// The farthest of its valid neighbors, which keeps foreground objects from bleeding
+    /// into the background.
+    Farthest,
+}
+
+impl HoleFilling {
+    #[cfg(feature = "sdk")]
+    #[cfg_attr(feature = "mock", allow(dead_code))]
+    fn to_ob(self) -> ob::OBHoleFillingMode {
+        match self {
+            HoleFilling::Top => ob::OBHoleFillingMode_OB_HOLE_FILL_TOP,
+            HoleFilling::Nearest => ob::OBHoleFillingMode_OB_HOLE_FILL_NEAREST,
+            // Spelled that way by the SDK.
+            HoleFilling::Farthest => ob::OBHoleFillingMode_OB_HOLE_FILL_FAREST,
+        }
+    }
+}
+
+/// How overlapping points of a translucent cloud are resolved, see
+/// [`OrbbecConfig::translucent_depth`]. Opaque clouds always write and test depth.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TranslucentDepth {
+    /// Blend every point in draw order without writing depth. Clouds are sorted against
+    /// each other, but not the points within them, so a far point drawn after a near one
+    /// shows through it and dense clouds look see-through.
+    #[default]
+    Blended,
+    /// Blend in draw order, but write depth, so points drawn after a nearer one are hidden.
+    /// Points drawn before it still show through, but as a cheap middle ground it removes
+    /// most of the see-through look.
+    DepthWrite,
+    /// Draw the cloud's depth alone in the opaque pass first, then blend only the nearest
+    /// point at each pixel. Overlapping points occlude correctly at the cost of drawing the
+    /// cloud twice, and the cloud hides what is behind it even where it is faint.
+    Prepass,
+}
+
+/// How points are blended, see [`OrbbecConfig::blend_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Opaque clouds occlude what is behind them, [`TranslucentPoints`](crate::TranslucentPoints)
+    /// clouds blend by their alpha as set by [`OrbbecConfig::translucent_depth`].
+    #[default]
+    Alpha,
+    /// Every cloud adds its colors, weighted by alpha, to what is behind it, so overlapping
+    /// points brighten rather than occlude each other and dense regions glow. Points are
+    /// still hidden behind other geometry, but never write depth, so
+    /// [`OrbbecConfig::translucent_depth`] doesn't apply. With an HDR camera and
+    /// [`Bloom`](bevy::core_pipeline::bloom::BloomSettings), the densest regions bloom.
+    Additive,
+}
+
+impl RenderStyle {
+    /// The scale written to each instance, applied to the mesh drawn per point. Cubes are
+    /// sized in millimeters so they follow the world scale, billboards are sized in world
+    /// units.
+    pub fn instance_scale(&self, world_scale: f32) -> f32 {
+        match self {
+            RenderStyle::Billboard { size } => *size,
+            _ => world_scale,
+        }
+    }
+}
+
+/// A point cloud captured by the device, along with when it was captured.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Index into [`OrbbecConfig::serials`] of the camera that captured the frame, `0` when
+    /// capturing from a single camera.
+    pub camera: usize,
+    /// The points, positioned in the SDK's millimeters.
+    pub points: PointPayload,
+    /// How confident the source is in each point, in the same order as [`Self::points`],
+    /// from 0 for not at all to 255 for fully. `None` when the source doesn't report it,
+    /// which is the case for live devices, as the SDK's point cloud filter doesn't carry
+    /// per-pixel confidence, and for clouds received over the network.
+    pub confidence: Option<Vec<u8>>,
+    /// Timestamp of the depth frame the points were generated from, in microseconds on the
+    /// device's clock.
+    pub device_timestamp_us: u64,
+    /// Sequence number of the depth frame the points were generated from, counted by the
+    /// source. Gaps mean frames were lost before reaching the capture thread, see
+    /// [`OrbbecRx::frames_dropped`]. `None` for clouds loaded from a file or received over the
+    /// network.
+    pub index: Option<u64>,
+    /// When the capture thread received the frameset.
+    pub system_time: SystemTime,
+}
+
+impl Frame {
+    /// Keeps the points whose position, in millimeters, passes `f`, along with their
+    /// confidence.
+    pub(crate) fn retain_positions(&mut self, mut f: impl FnMut(Vec3) -> bool) {
+        let Some(confidence) = &mut self.confidence else {
+            self.points.retain_positions(f);
+            return;
+        };
+        let keep: Vec<_> = self
+            .points
+            .iter()
+            .map(|(position, _)| f(position))
+            .collect();
+        let mut keep_points = keep.iter();
+        self.points
+            .retain_positions(|_| *keep_points.next().unwrap());
+        let mut keep_confidence = keep.iter();
+        confidence.retain(|_| *keep_confidence.next().unwrap());
+    }
+}
+
+/// Whether the capture thread is receiving from the device or [`OrbbecConfig::connect_to`].
+/// Kept up to date as a resource by [`OrbbecFramePlugin`](crate::frame::OrbbecFramePlugin),
+/// and always `Disconnected` when replaying a file. With several cameras, the worst state of
+/// those still capturing, see [`OrbbecRx::connection_state`].
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    Connected,
+    /// The device was disconnected and is waited on, see [`OrbbecConfig::reconnect`].
+    Reconnecting,
+    /// The device is connected but hasn't delivered a frame for
+    /// [`OrbbecConfig::no_frames_timeout`], e.g. because the laser is blocked or the device
+    /// is faulting. Back to `Connected` once frames arrive again.
+    NoFrames,
+}
+
+/// The color sensor's exposure settings, read back from the device when capture starts. Each
+/// is `None` when the device doesn't support reading it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ColorControls {
+    pub auto_exposure: Option<bool>,
+    pub exposure: Option<i32>,
+    pub gain: Option<i32>,
+}
+
+/// A point of a [`Frame`] with its color, converted from the SDK's `OBColorPoint` on the
+/// capture thread.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorPoint {
+    /// Position in millimeters, in the SDK's axes.
+    pub pos: Vec3,
+    /// sRGB color.
+    pub color: [u8; 3],
+}
+
+impl ColorPoint {
+    /// The point's color as a Bevy [`Color`].
+    pub fn color(&self) -> Color {
+        let [r, g, b] = self.color;
+        Color::srgb_u8(r, g, b)
+    }
+}
+
+#[cfg(feature = "sdk")]
+impl From<ob::OBColorPoint> for ColorPoint {
+    fn from(point: ob::OBColorPoint) -> Self {
+        Self {
+            pos: Vec3::new(point.x, point.y, point.z),
+            // The SDK reports channels as floats in the 0-255 range.
+            color: [point.r, point.g, point.b].map(|c| c.round() as u8),
+        }
+    }
+}
+
+/// The points of a [`Frame`], colored when the device streams color.
+#[derive(Debug, Clone)]
+pub enum PointPayload {
+    /// Points colored from the color stream aligned to depth.
+    Color(Vec<ColorPoint>),
+    /// Positions only, in millimeters, from a device or recording without a color stream.
+    Depth(Vec<Vec3>),
+}
+
+impl PointPayload {
+    pub fn len(&self) -> usize {
+        match self {
+            PointPayload::Color(points) => points.len(),
+            PointPayload::Depth(points) => points.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The position, in millimeters, and color of each point. Points are `None` when
+    /// uncolored.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (Vec3, Option<Color>)> + '_> {
+        match self {
+            PointPayload::Color(points) => {
+                Box::new(points.iter().map(|p| (p.pos, Some(p.color()))))
+            }
+            PointPayload::Depth(points) => Box::new(points.iter().map(|&p| (p, None))),
+        }
+    }
+
+    /// Keeps the points whose depth, in millimeters, passes `f`.
+    fn retain_depth(&mut self, mut f: impl FnMut(f32) -> bool) {
+        self.retain_positions(|p| f(p.z));
+    }
+
+    /// Keeps the points whose position, in millimeters, passes `f`.
+    pub(crate) fn retain_positions(&mut self, mut f: impl FnMut(Vec3) -> bool) {
+        match self {
+            PointPayload::Color(points) => points.retain(|p| f(p.pos)),
+            PointPayload::Depth(points) => points.retain(|&p| f(p)),
+        }
+    }
+
+    /// Keeps every `step`th point.
+    fn subsample(&self, step: usize) -> Self {
+        match self {
+            PointPayload::Color(points) => {
+                PointPayload::Color(points.iter().step_by(step.max(1)).copied().collect())
+            }
+            PointPayload::Depth(points) => {
+                PointPayload::Depth(points.iter().step_by(step.max(1)).copied().collect())
+            }
+        }
+    }
+
+    /// See [`voxel_downsample`].
+    fn voxel_downsample(&self, voxel_size_mm: f32) -> Self {
+        match self {
+            PointPayload::Color(points) => {
+                PointPayload::Color(voxel_downsample(points, voxel_size_mm))
+            }
+            PointPayload::Depth(points) => {
+                PointPayload::Depth(voxel_downsample_xyz(points, voxel_size_mm))
+            }
+        }
+    }
+}
+
+/// What the capture thread does with a new point cloud when [`OrbbecConfig::frame_queue`]
+/// clouds are already waiting for the app.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Drop the oldest queued cloud to make room, so the app always gets the newest ones.
+    #[default]
+    DropOldest,
+    /// Drop the new cloud, keeping those already queued.
+    DropNewest,
+    /// Wait until the app takes a cloud. Nothing is lost, but capture stalls along with the
+    /// app and the device drops frames instead.
+    Block,
+}
+
+/// How much the SDK logs, see [`OrbbecConfig::log_severity`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogSeverity {
+    Debug,
+    Info,
+    Warn,
+    #[default]
+    Error,
+    Fatal,
+    /// Log nothing.
+    Off,
+}
+
+impl LogSeverity {
+    #[cfg(feature = "sdk")]
+    #[cfg_attr(feature = "mock", allow(dead_code))]
+    fn to_ob(self) -> ob::OBLogSeverity {
+        match self {
+            LogSeverity::Debug => ob::OBLogSeverity_OB_LOG_SEVERITY_DEBUG,
+            LogSeverity::Info => ob::OBLogSeverity_OB_LOG_SEVERITY_INFO,
+            LogSeverity::Warn => ob::OBLogSeverity_OB_LOG_SEVERITY_WARN,
+            LogSeverity::Error => ob::OBLogSeverity_OB_LOG_SEVERITY_ERROR,
+            LogSeverity::Fatal => ob::OBLogSeverity_OB_LOG_SEVERITY_FATAL,
+            LogSeverity::Off => ob::OBLogSeverity_OB_LOG_SEVERITY_OFF,
+        }
+    }
+}
+
+/// How a device times its capture relative to other devices, see [`OrbbecConfig::sync_mode`].
+/// Each maps to the SDK's `OB_MULTI_DEVICE_SYNC_MODE_*` of the same name. Not every device
+/// supports every mode; an unsupported mode is skipped with a warning.
+///
+/// To capture from several cameras at the same instant, wire the primary's sync out to the
+/// first secondary's sync in, and chain further secondaries from the previous one's sync
+/// out, or use Orbbec's sync hub. Then make one camera [`SyncMode::Primary`] and the others
+/// [`SyncMode::Secondary`], see [`CameraSerial::sync_mode`]. Secondaries only capture when
+/// triggered, so start them first: their streams are silent until the primary is up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Capture independently, with the color and depth sensors not synchronized.
+    FreeRun,
+    /// Capture independently, with the color and depth sensors synchronized.
+    Standalone,
+    /// Capture on its own clock and send a trigger signal to the other devices.
+    Primary,
+    /// Capture when triggered through the sync in port, and pass the signal on.
+    Secondary,
+    /// Like [`Self::Secondary`], but keep capturing on the last trigger's timing when the
+    /// signal is lost.
+    SecondarySynced,
+    /// Capture when [`OrbbecRx::trigger_capture`] is called.
+    SoftwareTriggering,
+    /// Capture when triggered through the sync in port, without passing the signal on.
+    HardwareTriggering,
+}
+
+impl SyncMode {
+    #[cfg(feature = "sdk")]
+    #[cfg_attr(feature = "mock", allow(dead_code))]
+    fn to_ob(self) -> ob::OBMultiDeviceSyncMode {
+        match self {
+            SyncMode::FreeRun => ob::OBMultiDeviceSyncMode_OB_MULTI_DEVICE_SYNC_MODE_FREE_RUN,
+            SyncMode::Standalone => ob::OBMultiDeviceSyncMode_OB_MULTI_DEVICE_SYNC_MODE_STANDALONE,
+            SyncMode::Primary => ob::OBMultiDeviceSyncMode_OB_MULTI_DEVICE_SYNC_MODE_PRIMARY,
+            SyncMode::Secondary => ob::OBMultiDeviceSyncMode_OB_MULTI_DEVICE_SYNC_MODE_SECONDARY,
+            SyncMode::SecondarySynced => {
+                ob::OBMultiDeviceSyncMode_OB_MULTI_DEVICE_SYNC_MODE_SECONDARY_SYNCED
+            }
+            SyncMode::SoftwareTriggering => {
+                ob::OBMultiDeviceSyncMode_OB_MULTI_DEVICE_SYNC_MODE_SOFTWARE_TRIGGERING
+            }
+            SyncMode::HardwareTriggering => {
+                ob::OBMultiDeviceSyncMode_OB_MULTI_DEVICE_SYNC_MODE_HARDWARE_TRIGGERING
+            }
+        }
+    }
+
+    /// Whether frames only arrive when triggered, so a silent stream isn't a fault.
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    fn is_triggered(self) -> bool {
+        matches!(
+            self,
+            SyncMode::Secondary
+                | SyncMode::SecondarySynced
+                | SyncMode::SoftwareTriggering
+                | SyncMode::HardwareTriggering
+        )
+    }
+}
+
+/// Sending half of a camera's point cloud queue, bounded to [`OrbbecConfig::frame_queue`].
+struct FrameTx {
+    tx: SyncSender<Frame>,
+    /// The app's end, shared so the camera's oldest frame can be dropped to make room.
+    rx: Arc<Mutex<Receiver<Frame>>>,
+    /// Tells the app which camera's queue to take the next frame from, once per queued frame.
+    tx_ready: Sender<usize>,
+    drop_policy: DropPolicy,
+}
+
+/// The capture thread's ends of the channels shared with [`OrbbecRx`].
+struct WorkerChannels {
+    /// Index of the camera this thread captures from, see [`Frame::camera`].
+    camera: usize,
+    tx: FrameTx,
+    /// Counts every point cloud generated, including those dropped before reaching the app.
+    frames_captured: Arc<AtomicU64>,
+    /// Counts the frames missing from the device's sequence, see [`Self::track_index`].
+    frames_dropped: Arc<AtomicU64>,
+    /// Bounded to a single image, as only the latest one is displayed.
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    tx_color: SyncSender<RgbaImage>,
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    tx_depth: SyncSender<RgbaImage>,
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    tx_ir: SyncSender<RgbaImage>,
+    rx_shutdown: Receiver<()>,
+    /// Set by [`OrbbecRx::pause`], while which no frames are pulled from the device.
+    paused: Arc<AtomicBool>,
+    /// Set by [`OrbbecRx::set_voxel_size_mm`], see [`Self::voxel_size_mm`].
+    voxel_size: Arc<AtomicU32>,
+    /// Set by [`OrbbecRx::set_subsample`], see [`Self::subsample`].
+    subsample: Arc<AtomicUsize>,
+    /// Counts the calls to [`OrbbecRx::trigger_capture`].
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    triggers: Arc<AtomicU64>,
+    /// Configs pushed by [`OrbbecRx::reconfigure`].
+    rx_reconfigure: Receiver<OrbbecConfig>,
+    /// Sent once, after the pipeline has started.
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    tx_camera_param: Sender<OrbbecCameraParam>,
+    /// Written once the device has been configured.
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    color_controls: Arc<Mutex<ColorControls>>,
+    /// Written from the SDK's sensor callbacks, see [`imu_callback`].
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    imu: Arc<Mutex<Option<OrbbecImu>>>,
+    /// Written from the capture loop, see [`Orbbec::read_temperature`].
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    temperature: Arc<Mutex<Option<OrbbecTemperature>>>,
+    /// Written once the streams have started, see [`Orbbec::read_opened_device`].
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    device: Arc<Mutex<Option<OpenedDevice>>>,
+    /// The state of each camera, written by the device and network sources.
+    connection: Arc<Mutex<Vec<ConnectionState>>>,
+}
+
+/// Why [`WorkerChannels::wait_for_device`] returned.
+enum DeviceWait {
+    Found,
+    Reconfigured(OrbbecConfig),
+    ShutDown,
+}
+
+impl WorkerChannels {
+    /// Returns the newest pending config once none has arrived for [`RECONFIGURE_DEBOUNCE`],
+    /// starting from `config`.
+    fn settled_config(&self, mut config: OrbbecConfig) -> OrbbecConfig {
+        while let Ok(newer) = self.rx_reconfigure.recv_timeout(RECONFIGURE_DEBOUNCE) {
+            config = newer;
+        }
+        config
+    }
+
+    fn set_connection_state(&self, state: ConnectionState) {
+        lock(&self.connection)[self.camera] = state;
+    }
+
+    /// Polls until `device` is connected, waiting longer after every attempt. Network devices
+    /// can't be listed, so for them this only waits before the next attempt to open them.
+    fn wait_for_device(&self, device: &DeviceSource) -> DeviceWait {
+        let mut interval = RECONNECT_MIN_INTERVAL;
+        loop {
+            // Wake immediately on shutdown or reconfiguration.
+            if let Ok(config) = self.rx_reconfigure.try_recv() {
+                return DeviceWait::Reconfigured(config);
+            }
+            match self.rx_shutdown.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return DeviceWait::ShutDown,
+            }
+
+            let found = match device {
+                #[cfg(feature = "sdk")]
+                DeviceSource::Usb { serial } => enumerate_devices().iter().any(|device| {
+                    serial
+                        .as_deref()
+                        .map_or(true, |serial| device.serial_number == serial)
+                }),
+                // Nothing captures from USB devices without the SDK.
+                #[cfg(not(feature = "sdk"))]
+                DeviceSource::Usb { .. } => true,
+                DeviceSource::Net { .. } => true,
+            };
+            if found {
+                return DeviceWait::Found;
+            }
+            debug!(target: LOG_TARGET, ?interval, "Device not found, retrying");
+            interval = (interval * 2).min(RECONNECT_MAX_INTERVAL);
+        }
+    }
+
+    /// The voxel size to downsample with, in millimeters: the one set with
+    /// [`OrbbecRx::set_voxel_size_mm`] if any, otherwise [`OrbbecConfig::voxel_size_mm`].
+    fn voxel_size_mm(&self, config: &OrbbecConfig) -> Option<f32> {
+        match f32::from_bits(self.voxel_size.load(Ordering::Relaxed)) {
+            // NaN when unset.
+            size if size.is_nan() => config.voxel_size_mm,
+            size => (size > 0.0).then_some(size),
+        }
+    }
+
+    /// The subsampling step: the one set with [`OrbbecRx::set_subsample`] if any, otherwise
+    /// [`OrbbecConfig::subsample`].
+    fn subsample(&self, config: &OrbbecConfig) -> usize {
+        match self.subsample.load(Ordering::Relaxed) {
+            // Zero when unset.
+            0 => config.subsample.max(1),
+            step => step,
+        }
+    }
+
+    /// Counts the frames skipped between the `previous` frame index and `index` as dropped,
+    /// then makes `index` the previous one. An index that doesn't increase, as when the
+    /// counter wraps around or restarts with playback, starts the sequence over without
+    /// counting anything.
+    #[cfg_attr(any(feature = "mock", not(feature = "sdk")), allow(dead_code))]
+    fn track_index(&self, previous: &mut Option<u64>, index: u64) {
+        if let Some(previous) = previous.filter(|&previous| index > previous) {
+            self.frames_dropped
+                .fetch_add(index - previous - 1, Ordering::Relaxed);
+        }
+        *previous = Some(index);
+    }
+
+    /// Counts the frame as captured and queues it following the [`DropPolicy`], returning
+    /// `false` once the receiving side has gone away or, while blocked, shutdown was
+    /// requested.
+    fn send(&self, mut frame: Frame) -> bool {
+        self.frames_captured.fetch_add(1, Ordering::Relaxed);
+        loop {
+            // Held while queueing and announcing the frame, so the app never looks for a
+            // frame it was told about before it is there.
+            let queue = lock(&self.tx.rx);
+            frame = match self.tx.tx.try_send(frame) {
+                Ok(()) => return self.tx.tx_ready.send(self.camera).is_ok(),
+                Err(TrySendError::Disconnected(_)) => return false,
+                Err(TrySendError::Full(frame)) => frame,
+            };
+            match self.tx.drop_policy {
+                DropPolicy::DropOldest => {
+                    // Only this camera's queue, so a fast camera can't evict a slow one's
+                    // frames. The app was told about the dropped frame, which the new one
+                    // replaces. Only this thread sends, so there is room now.
+                    if queue.try_recv().is_ok() {
+                        let _ = self.tx.tx.try_send(frame);
+                        return true;
+                    }
+                    // The app took a frame in the meantime, so there is room already.
+                }
+                DropPolicy::DropNewest => return true,
+                DropPolicy::Block => {
+                    drop(queue);
+                    match self.rx_shutdown.recv_timeout(BLOCKED_SEND_POLL_INTERVAL) {
+                        Err(RecvTimeoutError::Timeout) => {}
+                        _ => return false,
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sdk")]
+unsafe fn c_str_to_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+}
+
+/// Converts a raised `ob_error` into an [`OrbbecError`], deleting the SDK error and resetting
+/// the pointer so it can be reused for the next call.
+#[cfg(feature = "sdk")]
+unsafe fn check_error(error: &mut *mut ob::ob_error) -> Result<(), OrbbecError> {
+    if error.is_null() {
+        return Ok(());
+    }
+
+    let err = OrbbecError {
+        function: c_str_to_string(ob::ob_error_function(*error)),
+        args: c_str_to_string(ob::ob_error_args(*error)),
+        message: c_str_to_string(ob::ob_error_message(*error)),
+        exception_type: ExceptionType::from_ob(ob::ob_error_exception_type(*error)),
+    };
+    ob::ob_delete_error(*error);
+    *error = null_mut();
+    Err(err)
+}
+
+/// Checks that the intrinsics of the `stream` camera describe an image, as the SDK can report
+/// zeroed ones for a stream it failed to calibrate.
+#[cfg(feature = "sdk")]
+#[cfg_attr(feature = "mock", allow(dead_code))]
+fn check_intrinsic(stream: &str, intrinsic: &ob::OBCameraIntrinsic) -> Result<(), OrbbecError> {
+    let focal_lengths = [intrinsic.fx, intrinsic.fy];
+    if focal_lengths.iter().all(|f| f.is_finite() && *f > 0.0)
+        && intrinsic.width > 0
+        && intrinsic.height > 0
+    {
+        return Ok(());
+    }
+    Err(OrbbecError::invalid_value(
+        "ob_pipeline_get_camera_param",
+        format!(
+            "{stream} fx: {}, fy: {}, size: {}x{}",
+            intrinsic.fx, intrinsic.fy, intrinsic.width, intrinsic.height
+        ),
+        format!("the device reported invalid {stream} intrinsics, its calibration may be missing"),
+    ))
+}
+
+#[cfg(feature = "sdk")]
+#[cfg_attr(feature = "mock", allow(dead_code))]
+struct Orbbec {
+    config: OrbbecConfig,
+    error: *mut ob::ob_error,
+    context: *mut ob::ob_context,
+    device_list: *mut ob::ob_device_list,
+    device: *mut ob::ob_device,
+    pipeline: *mut ob::ob_pipeline,
+    ob_config: *mut ob::ob_config,
+    color_profile: *mut ob::ob_stream_profile,
+    color_profiles: *mut ob::ob_stream_profile_list,
+    depth_profile: *mut ob::ob_stream_profile,
+    depth_profiles: *mut ob::ob_stream_profile_list,
+    ir_profile: *mut ob::ob_stream_profile,
+    ir_profiles: *mut ob::ob_stream_profile_list,
+    point_cloud: *mut ob::ob_filter,
+    /// Applied to the depth frame, in order, before the point cloud filter. See
+    /// [`Self::filter_depth`].
+    depth_filters: Vec<*mut ob::ob_filter>,
+    accel: ImuSensor,
+    gyro: ImuSensor,
+    /// Kept alive until the IMU sensors are stopped, as their callbacks write to it.
+    imu: Option<Arc<Mutex<Option<OrbbecImu>>>>,
+    /// Whether the pipeline is recording to [`OrbbecConfig::record_to`].
+    recording: bool,
+    playback: *mut ob::ob_playback,
+    /// Set from the playback state callback once the file has been played to the end. Boxed
+    /// so its address stays valid for the callback.
+    playback_ended: Box<AtomicBool>,
+}
+
+/// A started accelerometer or gyroscope.
+#[cfg(feature = "sdk")]
+struct ImuSensor {
+    sensor: *mut ob::ob_sensor,
+    profiles: *mut ob::ob_stream_profile_list,
+    profile: *mut ob::ob_stream_profile,
+    started: bool,
+}
+
+#[cfg(feature = "sdk")]
+impl ImuSensor {
+    fn new() -> Self {
+        Self {
+            sensor: null_mut(),
+            profiles: null_mut(),
+            profile: null_mut(),
+            started: false,
+        }
+    }
+
+    /// Starts the sensor's default profile, delivering frames to [`imu_callback`].
+    unsafe fn start(
+        &mut self,
+        device: *mut ob::ob_device,
+        sensor_type: ob::OBSensorType,
+        imu: &Arc<Mutex<Option<OrbbecImu>>>,
+        error: &mut *mut ob::ob_error,
+    ) -> Result<(), OrbbecError> {
+        self.sensor = ob::ob_device_get_sensor(device, sensor_type, error);
+        check_error(error).map_err(|err| {
+            OrbbecError::invalid_value(
+                &err.function,
+                err.args,
+                format!(
+                    "enable_imu is set but the device has no IMU: {}",
+                    err.message
+                ),
+            )
+        })?;
+
+        self.profiles = ob::ob_sensor_get_stream_profile_list(self.sensor, error);
+        check_error(error)?;
+        self.profile = ob::ob_stream_profile_list_get_profile(
+            self.profiles,
             ob::OB_PROFILE_DEFAULT as c_int,
-            &mut error,
+            error,
+        );
+        check_error(error)?;
+
+        ob::ob_sensor_start(
+            self.sensor,
+            self.profile,
+            Some(imu_callback),
+            Arc::as_ptr(imu) as *mut c_void,
+            error,
+        );
+        check_error(error)?;
+        self.started = true;
+
+        Ok(())
+    }
+
+    /// Stops the sensor and releases its handles, discarding any error.
+    unsafe fn release(&mut self, error: &mut *mut ob::ob_error) {
+        if self.started {
+            ob::ob_sensor_stop(self.sensor, error);
+            let _ = check_error(error);
+            self.started = false;
+        }
+
+        if !self.profile.is_null() {
+            ob::ob_delete_stream_profile(self.profile, error);
+            let _ = check_error(error);
+        }
+
+        if !self.profiles.is_null() {
+            ob::ob_delete_stream_profile_list(self.profiles, error);
+            let _ = check_error(error);
+        }
+
+        if !self.sensor.is_null() {
+            ob::ob_delete_sensor(self.sensor, error);
+            let _ = check_error(error);
+        }
+    }
+}
+
+/// Called by the SDK when playback of a `.bag` file changes state. `user_data` points to
+/// [`Orbbec::playback_ended`].
+#[cfg(feature = "sdk")]
+unsafe extern "C" fn playback_state_callback(state: ob::ob_media_state, user_data: *mut c_void) {
+    if state == ob::OBMediaState_OB_MEDIA_END {
+        let ended = &*(user_data as *const AtomicBool);
+        ended.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Converts a path for the SDK, which takes UTF-8 C strings.
+#[cfg(feature = "sdk")]
+fn path_to_c_string(function: &str, path: &Path) -> Result<CString, OrbbecError> {
+    path.to_str()
+        .and_then(|path| CString::new(path).ok())
+        .ok_or_else(|| {
+            OrbbecError::invalid_value(
+                function,
+                path.display().to_string(),
+                "path must be valid UTF-8 without nul bytes",
+            )
+        })
+}
+
+/// Called by the SDK on its own thread for every accelerometer and gyroscope frame.
+/// `user_data` points to the `Mutex<Option<OrbbecImu>>` passed to [`ImuSensor::start`].
+#[cfg(feature = "sdk")]
+unsafe extern "C" fn imu_callback(frame: *mut ob::ob_frame, user_data: *mut c_void) {
+    let imu = &*(user_data as *const Mutex<Option<OrbbecImu>>);
+    let mut error = null_mut();
+
+    if let Err(err) = read_imu_frame(frame, imu, &mut error) {
+        warn!(target: LOG_TARGET, "Failed to read IMU frame: {err}");
+    }
+
+    ob::ob_delete_frame(frame, &mut error);
+    let _ = check_error(&mut error);
+}
+
+/// Merges an accelerometer or gyroscope frame into the latest sample.
+#[cfg(feature = "sdk")]
+unsafe fn read_imu_frame(
+    frame: *mut ob::ob_frame,
+    imu: &Mutex<Option<OrbbecImu>>,
+    error: &mut *mut ob::ob_error,
+) -> Result<(), OrbbecError> {
+    let frame_type = ob::ob_frame_get_type(frame, error);
+    check_error(error)?;
+    let timestamp_us = ob::ob_frame_time_stamp_us(frame, error);
+    check_error(error)?;
+
+    let mut imu = lock(&imu);
+    let sample = imu.get_or_insert_with(OrbbecImu::default);
+    match frame_type {
+        ob::OBFrameType_OB_FRAME_ACCEL => {
+            let value = ob::ob_accel_frame_value(frame, error);
+            check_error(error)?;
+            sample.linear_acceleration = Vec3::new(value.x, value.y, value.z);
+        }
+        ob::OBFrameType_OB_FRAME_GYRO => {
+            let value = ob::ob_gyro_frame_value(frame, error);
+            check_error(error)?;
+            sample.angular_velocity = Vec3::new(value.x, value.y, value.z);
+        }
+        _ => return Ok(()),
+    }
+    sample.timestamp_us = timestamp_us;
+
+    Ok(())
+}
+
+#[cfg(feature = "sdk")]
+#[cfg_attr(feature = "mock", allow(dead_code))]
+impl Orbbec {
+    fn new(config: OrbbecConfig) -> Self {
+        Self {
+            config,
+            error: null_mut(),
+            context: null_mut(),
+            device_list: null_mut(),
+            device: null_mut(),
+            pipeline: null_mut(),
+            ob_config: null_mut(),
+            color_profile: null_mut(),
+            color_profiles: null_mut(),
+            depth_profile: null_mut(),
+            depth_profiles: null_mut(),
+            ir_profile: null_mut(),
+            ir_profiles: null_mut(),
+            point_cloud: null_mut(),
+            depth_filters: Vec::new(),
+            accel: ImuSensor::new(),
+            gyro: ImuSensor::new(),
+            imu: None,
+            recording: false,
+            playback: null_mut(),
+            playback_ended: Box::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Captures until shut down, returning the new config if stopped by
+    /// [`OrbbecRx::reconfigure`] instead.
+    unsafe fn run(
+        &mut self,
+        channels: &WorkerChannels,
+    ) -> Result<Option<OrbbecConfig>, OrbbecError> {
+        ob::ob_set_logger_severity(self.config.log_severity.to_ob(), &mut self.error);
+        check_error(&mut self.error)?;
+
+        if let Some(path) = self.config.playback_from.clone() {
+            self.start_playback(&path)?;
+        } else {
+            // pipeline, used to open the Color and Depth streams after connecting the device
+            self.pipeline = match self.config.device.clone() {
+                DeviceSource::Usb {
+                    serial: Some(serial),
+                } => {
+                    self.open_device(&serial)?;
+                    ob::ob_create_pipeline_with_device(self.device, &mut self.error)
+                }
+                DeviceSource::Usb { serial: None } => ob::ob_create_pipeline(&mut self.error),
+                DeviceSource::Net { ip, port } => {
+                    self.open_net_device(&ip, port)?;
+                    ob::ob_create_pipeline_with_device(self.device, &mut self.error)
+                }
+            };
+            check_error(&mut self.error)?;
+
+            self.configure_device(channels)?;
+            self.start_streams()?;
+            channels.set_connection_state(ConnectionState::Connected);
+            if channels.camera == 0 {
+                // Only informative, so not worth stopping capture over.
+                match self.read_opened_device() {
+                    Ok(device) => *lock(&channels.device) = Some(device),
+                    Err(err) => {
+                        warn!(target: LOG_TARGET, "Failed to read the device info: {err}")
+                    }
+                }
+            }
+        }
+
+        if let Some(path) = self.config.record_to.clone() {
+            self.start_recording(&path)?;
+        }
+
+        // Create a point cloud Filter object (device parameters will be obtained inside the Pipeline when the point cloud filter is created, so try to configure
+        // the device before creating the filter)
+        self.point_cloud = ob::ob_create_pointcloud_filter(&mut self.error);
+        check_error(&mut self.error)?;
+
+        // Obtain the current open-stream camera parameters from the pipeline and pass them to the point cloud filter
+        let camera_param: ob::ob_camera_param =
+            ob::ob_pipeline_get_camera_param(self.pipeline, &mut self.error);
+        check_error(&mut self.error)?;
+        // A zeroed param raises no error, but puts every point at the origin.
+        check_intrinsic("depth", &camera_param.depthIntrinsic)?;
+        if !self.color_profile.is_null() {
+            check_intrinsic("color", &camera_param.rgbIntrinsic)?;
+        }
+        ob::ob_pointcloud_filter_set_camera_param(self.point_cloud, camera_param, &mut self.error);
+        check_error(&mut self.error)?;
+        self.create_depth_filters()?;
+        if channels.camera == 0 {
+            let _ = channels.tx_camera_param.send(camera_param.into());
+        }
+
+        if self.config.enable_imu && self.config.playback_from.is_none() {
+            self.start_imu(&channels.imu)?;
+        }
+
+        // Only the first camera's temperature is reported, and not from recordings.
+        let mut next_temperature_poll =
+            (channels.camera == 0 && self.config.playback_from.is_none()).then(Instant::now);
+
+        // Loop to get the frame and send the point cloud
+        let mut reconfigure = None;
+        let mut previous_index = None;
+        let mut last_frameset = Instant::now();
+        let mut stalled = false;
+        let mut triggers = channels.triggers.load(Ordering::Relaxed);
+        let software_triggered = self.config.sync_mode == Some(SyncMode::SoftwareTriggering)
+            && self.config.playback_from.is_none();
+        loop {
+            // Stop when asked to, or when the handle has gone away without asking.
+            if !matches!(channels.rx_shutdown.try_recv(), Err(TryRecvError::Empty)) {
+                break;
+            }
+            if let Ok(config) = channels.rx_reconfigure.try_recv() {
+                reconfigure = Some(config);
+                break;
+            }
+
+            // The streams are left running so resuming doesn't restart the device, the SDK
+            // drops the frames nobody waits for.
+            if channels.paused.load(Ordering::Relaxed) {
+                // The frames dropped meanwhile weren't lost to the device.
+                previous_index = None;
+                last_frameset = Instant::now();
+                match channels.rx_shutdown.recv_timeout(PAUSE_POLL_INTERVAL) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+
+            if self.playback_ended.swap(false, Ordering::Relaxed) {
+                if !self.config.playback_loop {
+                    info!(
+                        target: LOG_TARGET,
+                        frames = channels.frames_captured.load(Ordering::Relaxed),
+                        "Playback finished"
+                    );
+                    break;
+                }
+                // Restarting the pipeline plays the file from the beginning.
+                self.restart_pipeline()?;
+            }
+
+            if let Some(next_poll) = next_temperature_poll.filter(|&t| t <= Instant::now()) {
+                next_temperature_poll = match self.read_temperature() {
+                    Ok(Some(temperature)) => {
+                        *lock(&channels.temperature) = Some(temperature);
+                        Some(next_poll + TEMPERATURE_POLL_INTERVAL)
+                    }
+                    Ok(None) => {
+                        debug!(target: LOG_TARGET, "The device doesn't report its temperature");
+                        None
+                    }
+                    // Not worth stopping the capture for.
+                    Err(err) => {
+                        warn!(
+                            target: LOG_TARGET,
+                            "Failed to read the device temperature, no longer polling: {err}"
+                        );
+                        None
+                    }
+                };
+            }
+
+            if software_triggered {
+                let requested = channels.triggers.load(Ordering::Relaxed);
+                if requested != triggers {
+                    triggers = requested;
+                    ob::ob_device_trigger_capture(self.device()?, &mut self.error);
+                    if let Err(err) = check_error(&mut self.error) {
+                        warn!(target: LOG_TARGET, "Failed to trigger a capture: {err}");
+                    }
+                }
+            }
+
+            // Waiting for one frame
+            let frameset: *mut ob::ob_frame =
+                ob::ob_pipeline_wait_for_frameset(self.pipeline, FRAME_TIMEOUT_MS, &mut self.error);
+            check_error(&mut self.error)?;
+            if frameset.is_null() {
+                // Triggered devices are silent between triggers.
+                let timeout = self.config.no_frames_timeout.filter(|_| {
+                    self.config.playback_from.is_none()
+                        && !self.config.sync_mode.is_some_and(SyncMode::is_triggered)
+                });
+                if timeout.is_some_and(|timeout| last_frameset.elapsed() >= timeout) {
+                    if !stalled {
+                        warn!(
+                            target: LOG_TARGET,
+                            elapsed = ?last_frameset.elapsed(),
+                            "The device stopped delivering frames"
+                        );
+                        channels.set_connection_state(ConnectionState::NoFrames);
+                        stalled = true;
+                    }
+                    if self.config.restart_on_no_frames {
+                        info!(target: LOG_TARGET, "Restarting the pipeline");
+                        self.restart_pipeline()?;
+                        last_frameset = Instant::now();
+                    }
+                }
+                continue;
+            }
+            last_frameset = Instant::now();
+            if stalled {
+                info!(target: LOG_TARGET, "The device is delivering frames again");
+                channels.set_connection_state(ConnectionState::Connected);
+                stalled = false;
+            }
+            let system_time = SystemTime::now();
+
+            // Filtered in place, so the depth image shows the same depth as the cloud.
+            let points = self
+                .filter_depth(frameset)
+                .and_then(|()| self.process_frameset(frameset, channels));
+            let color_image = if self.config.capture_color_image {
+                self.color_image(frameset)
+            } else {
+                Ok(None)
+            };
+            let depth_image = if self.config.capture_depth_image {
+                self.depth_image(frameset)
+            } else {
+                Ok(None)
+            };
+            let ir_image = if self.config.capture_ir_image {
+                self.ir_image(frameset)
+            } else {
+                Ok(None)
+            };
+
+            // Destroy frameSet to reclaim memory
+            ob::ob_delete_frame(frameset, &mut self.error);
+            check_error(&mut self.error)?;
+
+            if let Some(image) = color_image? {
+                // Dropped if the previous image hasn't been picked up yet.
+                let _ = channels.tx_color.try_send(image);
+            }
+            if let Some(image) = depth_image? {
+                let _ = channels.tx_depth.try_send(image);
+            }
+            if let Some(image) = ir_image? {
+                let _ = channels.tx_ir.try_send(image);
+            }
+
+            if let Some((points, device_timestamp_us, index)) = points? {
+                channels.track_index(&mut previous_index, index);
+                let frame = Frame {
+                    camera: channels.camera,
+                    points,
+                    confidence: None,
+                    device_timestamp_us,
+                    index: Some(index),
+                    system_time,
+                };
+                if !channels.send(frame) {
+                    // The receiving side has gone away, nothing left to do.
+                    break;
+                }
+            }
+        }
+
+        if self.recording {
+            ob::ob_pipeline_stop_record(self.pipeline, &mut self.error);
+            self.recording = false;
+            check_error(&mut self.error)?;
+        }
+
+        // stop pipeline
+        ob::ob_pipeline_stop(self.pipeline, &mut self.error);
+        check_error(&mut self.error)?;
+
+        Ok(reconfigure)
+    }
+
+    /// Replaces `self.depth_profiles` with the depth profiles supporting the given alignment
+    /// to the color profile, returning whether there are any.
+    ///
+    /// Can't leak a list: the previous one is deleted and its field cleared before the new one
+    /// is requested, and the new one is stored in the field as soon as it exists. Whichever
+    /// list is held when an early `?` returns is deleted by `Drop for Orbbec`, as the list
+    /// left on success is on the next call or on drop.
+    unsafe fn load_d2c_depth_profiles(
+        &mut self,
+        mode: ob::OBAlignMode,
+    ) -> Result<bool, OrbbecError> {
+        if !self.depth_profiles.is_null() {
+            ob::ob_delete_stream_profile_list(self.depth_profiles, &mut self.error);
+            self.depth_profiles = null_mut();
+            check_error(&mut self.error)?;
+        }
+
+        self.depth_profiles = ob::ob_get_d2c_depth_profile_list(
+            self.pipeline,
+            self.color_profile,
+            mode,
+            &mut self.error,
+        );
+        check_error(&mut self.error)?;
+        let count = ob::ob_stream_profile_list_count(self.depth_profiles, &mut self.error);
+        check_error(&mut self.error)?;
+
+        Ok(count > 0)
+    }
+
+    /// Stops and starts the pipeline again with the same streams.
+    unsafe fn restart_pipeline(&mut self) -> Result<(), OrbbecError> {
+        ob::ob_pipeline_stop(self.pipeline, &mut self.error);
+        check_error(&mut self.error)?;
+        // Playback pipelines are started without a config.
+        if self.ob_config.is_null() {
+            ob::ob_pipeline_start(self.pipeline, &mut self.error);
+        } else {
+            ob::ob_pipeline_start_with_config(self.pipeline, self.ob_config, &mut self.error);
+        }
+        check_error(&mut self.error)
+    }
+
+    /// Configures the color, depth and IR streams of a live device and starts the pipeline.
+    unsafe fn start_streams(&mut self) -> Result<(), OrbbecError> {
+        // Create config to configure the resolution, frame rate, and format of Color and Depth streams
+        self.ob_config = ob::ob_create_config(&mut self.error);
+        check_error(&mut self.error)?;
+
+        self.color_profiles = ob::ob_pipeline_get_stream_profile_list(
+            self.pipeline,
+            OBSensorType_OB_SENSOR_COLOR,
+            &mut self.error,
+        );
+        match check_error(&mut self.error) {
+            // Streamed like a depth only device, the profile list is deleted on drop.
+            Ok(()) if !self.config.sensor_enabled(SensorType::Color) => {
+                info!(
+                    target: LOG_TARGET,
+                    "The color sensor is disabled, streaming uncolored points"
+                );
+            }
+            Ok(()) => {
+                let spec = self.config.color_profile;
+                if !spec.is_any() {
+                    self.color_profile = self.find_video_profile(self.color_profiles, spec);
+                    if self.color_profile.is_null() {
+                        self.report_unmatched_profile("color_profile", spec, self.color_profiles);
+                    }
+                }
+
+                if self.color_profile.is_null() {
+                    // Open the default profile of Color Sensor, which can be configured
+                    // through the configuration file
+                    self.color_profile = ob::ob_stream_profile_list_get_profile(
+                        self.color_profiles,
+                        ob::OB_PROFILE_DEFAULT as c_int,
+                        &mut self.error,
+                    );
+                    check_error(&mut self.error)?;
+                }
+
+                // enable stream
+                ob::ob_config_enable_stream(self.ob_config, self.color_profile, &mut self.error);
+                check_error(&mut self.error)?;
+            }
+            // Depth only devices stream uncolored points, with depth left unaligned below.
+            Err(err) => {
+                info!(
+                    target: LOG_TARGET,
+                    "The device has no color sensor, streaming uncolored points: {err}"
+                );
+                self.color_profiles = null_mut();
+            }
+        }
+
+        // Configure depth flow
+        let mut align_mode: ob::OBAlignMode = ob::OBAlignMode_ALIGN_DISABLE;
+
+        let candidates: &[ob::OBAlignMode] = match self.config.align_mode {
+            AlignMode::Auto => &[
+                ob::OBAlignMode_ALIGN_D2C_HW_MODE,
+                ob::OBAlignMode_ALIGN_D2C_SW_MODE,
+            ],
+            AlignMode::HardwareOnly => &[ob::OBAlignMode_ALIGN_D2C_HW_MODE],
+            AlignMode::SoftwareOnly => &[ob::OBAlignMode_ALIGN_D2C_SW_MODE],
+            AlignMode::Disabled => &[],
+        };
+        if !self.color_profile.is_null() {
+            // Find depth profiles supporting depth to color alignment, in order of preference
+            for &mode in candidates {
+                if self.load_d2c_depth_profiles(mode)? {
+                    align_mode = mode;
+                    break;
+                }
+            }
+        }
+
+        if align_mode == ob::OBAlignMode_ALIGN_DISABLE {
+            if !matches!(
+                self.config.align_mode,
+                AlignMode::Auto | AlignMode::Disabled
+            ) {
+                return Err(OrbbecError::invalid_value(
+                    "ob_get_d2c_depth_profile_list",
+                    format!("{:?}", self.config.align_mode),
+                    if self.color_profile.is_null() {
+                        "alignment was forced but the device has no color stream to align to"
+                    } else {
+                        "the device has no depth profile supporting the forced alignment mode"
+                    },
+                ));
+            }
+
+            if !self.depth_profiles.is_null() {
+                ob::ob_delete_stream_profile_list(self.depth_profiles, &mut self.error);
+                self.depth_profiles = null_mut();
+                check_error(&mut self.error)?;
+            }
+            self.depth_profiles = ob::ob_pipeline_get_stream_profile_list(
+                self.pipeline,
+                ob::OBSensorType_OB_SENSOR_DEPTH,
+                &mut self.error,
+            );
+            check_error(&mut self.error)?;
+        }
+
+        let list_count = ob::ob_stream_profile_list_count(self.depth_profiles, &mut self.error);
+        check_error(&mut self.error)?;
+        if list_count > 0 {
+            let spec = self.config.depth_profile;
+            if !spec.is_any() {
+                // Prefer the color frame rate for whatever the spec leaves open.
+                if spec.fps.is_none() && !self.color_profile.is_null() {
+                    let color_fps =
+                        ob::ob_video_stream_profile_fps(self.color_profile, &mut self.error);
+                    check_error(&mut self.error)?;
+                    let spec = StreamProfileSpec {
+                        fps: Some(color_fps),
+                        ..spec
+                    };
+                    self.depth_profile = self.find_video_profile(self.depth_profiles, spec);
+                }
+                if self.depth_profile.is_null() {
+                    self.depth_profile = self.find_video_profile(self.depth_profiles, spec);
+                }
+                if self.depth_profile.is_null() {
+                    self.report_unmatched_profile("depth_profile", spec, self.depth_profiles);
+                }
+            }
+
+            if self.depth_profile.is_null() && !self.color_profile.is_null() {
+                // Select the profile with the same frame rate as color.
+                let color_fps =
+                    ob::ob_video_stream_profile_fps(self.color_profile, &mut self.error);
+                check_error(&mut self.error)?;
+                self.depth_profile = ob::ob_stream_profile_list_get_video_stream_profile(
+                    self.depth_profiles,
+                    ob::OB_WIDTH_ANY as c_int,
+                    ob::OB_HEIGHT_ANY as c_int,
+                    ob::OBFormat_OB_FORMAT_UNKNOWN,
+                    color_fps as c_int,
+                    &mut self.error,
+                );
+                check_error(&mut self.error)?;
+            }
+
+            if self.depth_profile.is_null() {
+                // If no matching profile is found, select the default profile.
+                self.depth_profile = ob::ob_stream_profile_list_get_profile(
+                    self.depth_profiles,
+                    ob::OB_PROFILE_DEFAULT as c_int,
+                    &mut self.error,
+                );
+                check_error(&mut self.error)?;
+            }
+
+            // enable stream
+            ob::ob_config_enable_stream(self.ob_config, self.depth_profile, &mut self.error);
+            check_error(&mut self.error)?;
+
+            // Turn on D2C alignment, which needs to be turned on when generating RGBD point clouds
+            ob::ob_config_set_align_mode(self.ob_config, align_mode, &mut self.error);
+            check_error(&mut self.error)?;
+        }
+
+        if self.config.capture_ir_image && self.config.sensor_enabled(SensorType::Ir) {
+            self.enable_ir_stream()?;
+        }
+        self.check_bandwidth()?;
+
+        // Start the pipeline with config
+        ob::ob_pipeline_start_with_config(self.pipeline, self.ob_config, &mut self.error);
+        check_error(&mut self.error).map_err(|err| {
+            // Only blamed on IR when its stream was enabled, it may be skipped or disabled.
+            if !self.ir_profile.is_null() {
+                OrbbecError::invalid_value(
+                    &err.function,
+                    err.args,
+                    format!(
+                        "failed to start the IR stream alongside depth, the device may not \
+                         support both at once, try disabling capture_ir_image: {}",
+                        err.message
+                    ),
+                )
+            } else {
+                err
+            }
+        })?;
+
+        Ok(())
+    }
+
+    /// Fails if the enabled streams need more bandwidth than the device's link carries, which
+    /// would otherwise only show as dropped frames once the pipeline runs. Only checked over
+    /// USB 2.0, as the SDK reports no limit and faster links carry any profile combination.
+    /// Compressed streams are left out of the estimate.
+    unsafe fn check_bandwidth(&mut self) -> Result<(), OrbbecError> {
+        let device = self.device()?;
+        let info = ob::ob_device_get_device_info(device, &mut self.error);
+        check_error(&mut self.error)?;
+        let connection = c_str_to_string(ob::ob_device_info_connection_type(info, &mut self.error));
+        let connection = check_error(&mut self.error).map(|()| connection);
+        ob::ob_delete_device_info(info, &mut self.error);
+        check_error(&mut self.error)?;
+        if !connection?.starts_with("USB2") {
+            return Ok(());
+        }
+
+        let mut streams = Vec::new();
+        let mut bandwidth = 0.0;
+        for (name, profile) in [
+            ("color", self.color_profile),
+            ("depth", self.depth_profile),
+            ("IR", self.ir_profile),
+        ] {
+            if profile.is_null() {
+                continue;
+            }
+            let profile = read_video_profile(profile, &mut self.error)?;
+            if let Some(bytes) = bytes_per_pixel(profile.format) {
+                bandwidth += (profile.width * profile.height * profile.fps) as f32 * bytes;
+            }
+            streams.push(format!("{name}: {profile}"));
+        }
+        if bandwidth > USB2_VIDEO_BANDWIDTH {
+            return Err(OrbbecError::invalid_value(
+                "ob_pipeline_start_with_config",
+                streams.join(", "),
+                format!(
+                    "the streams need about {:.0} MB/s but the device is connected over USB 2.0, \
+                     which carries about {:.0} MB/s, disable a sensor with disabled_sensors or \
+                     pick smaller profiles",
+                    bandwidth / 1e6,
+                    USB2_VIDEO_BANDWIDTH / 1e6
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the profile in `profiles` matching `spec`, or null if there is none.
+    unsafe fn find_video_profile(
+        &mut self,
+        profiles: *mut ob::ob_stream_profile_list,
+        spec: StreamProfileSpec,
+    ) -> *mut ob::ob_stream_profile {
+        let profile = ob::ob_stream_profile_list_get_video_stream_profile(
+            profiles,
+            spec.width
+                .map_or(ob::OB_WIDTH_ANY as c_int, |width| width as c_int),
+            spec.height
+                .map_or(ob::OB_HEIGHT_ANY as c_int, |height| height as c_int),
+            spec.format
+                .map_or(ob::OBFormat_OB_FORMAT_UNKNOWN, StreamFormat::to_ob),
+            spec.fps.map_or(ob::OB_FPS_ANY as c_int, |fps| fps as c_int),
+            &mut self.error,
+        );
+        // The SDK raises an error when nothing matches.
+        match check_error(&mut self.error) {
+            Ok(()) => profile,
+            Err(_) => null_mut(),
+        }
+    }
+
+    /// Warns that the `field` spec matched none of `profiles`, listing them so a valid one
+    /// can be picked.
+    unsafe fn report_unmatched_profile(
+        &mut self,
+        field: &str,
+        spec: StreamProfileSpec,
+        profiles: *mut ob::ob_stream_profile_list,
+    ) {
+        let available = match video_profiles(profiles, &mut self.error) {
+            Ok(available) => available
+                .iter()
+                .map(|profile| format!("\n  {profile}"))
+                .collect::<String>(),
+            Err(err) => format!(" unknown, {err}"),
+        };
+        warn!(
+            target: LOG_TARGET,
+            "{field} {spec:?} matches no profile of the device, using the default. \
+             Available:{available}"
         );
     }
 
-    // enable stream
-    if !color_profile.is_null() {
-        ob::ob_config_enable_stream(ob_config, color_profile, &mut error);
-        check_error(error);
+    /// Creates a pipeline that plays back a `.bag` file in place of a device, and starts it
+    /// with the streams that were recorded.
+    unsafe fn start_playback(&mut self, path: &Path) -> Result<(), OrbbecError> {
+        let c_path = path_to_c_string("ob_create_pipeline_with_playback_file", path)?;
+
+        self.pipeline = ob::ob_create_pipeline_with_playback_file(c_path.as_ptr(), &mut self.error);
+        check_error(&mut self.error)?;
+
+        self.playback = ob::ob_pipeline_get_playback(self.pipeline, &mut self.error);
+        check_error(&mut self.error)?;
+        ob::ob_set_playback_state_callback(
+            self.playback,
+            Some(playback_state_callback),
+            &*self.playback_ended as *const AtomicBool as *mut c_void,
+            &mut self.error,
+        );
+        check_error(&mut self.error)?;
+
+        ob::ob_pipeline_start(self.pipeline, &mut self.error);
+        check_error(&mut self.error)?;
+        info!(target: LOG_TARGET, "Playing back {}", path.display());
+
+        Ok(())
+    }
+
+    /// Starts recording the pipeline's streams to a `.bag` file.
+    unsafe fn start_recording(&mut self, path: &Path) -> Result<(), OrbbecError> {
+        let c_path = path_to_c_string("ob_pipeline_start_record", path)?;
+
+        ob::ob_pipeline_start_record(self.pipeline, c_path.as_ptr(), &mut self.error);
+        check_error(&mut self.error)?;
+        self.recording = true;
+        info!(target: LOG_TARGET, "Recording to {}", path.display());
+
+        Ok(())
+    }
+
+    /// Finds the connected device with the given serial number and stores it in `self.device`.
+    unsafe fn open_device(&mut self, serial: &str) -> Result<(), OrbbecError> {
+        const FUNCTION: &str = "ob_device_list_get_device_by_serial_number";
+
+        let c_serial = CString::new(serial).map_err(|_| {
+            OrbbecError::invalid_value(FUNCTION, serial, "serial number contains a nul byte")
+        })?;
+
+        self.context = ob::ob_create_context(&mut self.error);
+        check_error(&mut self.error)?;
+
+        self.device_list = ob::ob_query_device_list(self.context, &mut self.error);
+        check_error(&mut self.error)?;
+
+        self.device = ob::ob_device_list_get_device_by_serial_number(
+            self.device_list,
+            c_serial.as_ptr(),
+            &mut self.error,
+        );
+        // The SDK raises its own error when the serial is unknown, but it doesn't say which
+        // serial was requested, so report that instead.
+        if check_error(&mut self.error).is_err() || self.device.is_null() {
+            return Err(OrbbecError::invalid_value(
+                FUNCTION,
+                serial,
+                format!("no connected device with serial number {serial:?}"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Connects to the network device at `ip` and `port` and stores it in `self.device`.
+    unsafe fn open_net_device(&mut self, ip: &str, port: u16) -> Result<(), OrbbecError> {
+        const FUNCTION: &str = "ob_create_net_device";
+        let address = format!("{ip}:{port}");
+
+        let c_ip = CString::new(ip).map_err(|_| {
+            OrbbecError::invalid_value(FUNCTION, &address, "IP address contains a nul byte")
+        })?;
+
+        self.context = ob::ob_create_context(&mut self.error);
+        check_error(&mut self.error)?;
+
+        self.device = ob::ob_create_net_device(self.context, c_ip.as_ptr(), port, &mut self.error);
+        // As with serials, the SDK's error doesn't say which address was unreachable.
+        check_error(&mut self.error).map_err(|err| OrbbecError {
+            args: address.clone(),
+            ..err
+        })?;
+        if self.device.is_null() {
+            return Err(OrbbecError::invalid_value(
+                FUNCTION,
+                &address,
+                format!("no device reachable at {address}"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The device the pipeline streams from.
+    unsafe fn device(&mut self) -> Result<*mut ob::ob_device, OrbbecError> {
+        // Without a serial the pipeline picked the device, ask it which one.
+        if self.device.is_null() {
+            self.device = ob::ob_pipeline_get_device(self.pipeline, &mut self.error);
+            check_error(&mut self.error)?;
+        }
+        Ok(self.device)
+    }
+
+    /// Applies the device properties from the config. Must run before the pipeline starts.
+    unsafe fn configure_device(&mut self, channels: &WorkerChannels) -> Result<(), OrbbecError> {
+        if let Some(mode) = self.config.sync_mode {
+            // Like a bad property, a sync mode the device can't take is only reported.
+            match self.set_sync_mode(mode) {
+                Ok(true) => {}
+                Ok(false) => warn!(
+                    target: LOG_TARGET,
+                    "sync_mode {mode:?} isn't supported by the device, ignoring"
+                ),
+                Err(err) => {
+                    warn!(target: LOG_TARGET, "Failed to set sync_mode to {mode:?}: {err}")
+                }
+            }
+        }
+
+        for (property, name, value) in [
+            (
+                ob::OBPropertyID_OB_PROP_DEPTH_MIRROR_BOOL,
+                "mirror_depth",
+                self.config.mirror_depth,
+            ),
+            (
+                ob::OBPropertyID_OB_PROP_COLOR_MIRROR_BOOL,
+                "mirror_color",
+                self.config.mirror_color,
+            ),
+        ] {
+            // Still cleared when off, the device may remember it from a previous session.
+            if !self.set_bool_property(property, value)? && value {
+                warn!(
+                    target: LOG_TARGET,
+                    "{name} is set but the device doesn't support it, ignoring"
+                );
+            }
+        }
+
+        // Still written when on, so a laser turned off by a previous session comes back.
+        if !self.set_bool_property(
+            ob::OBPropertyID_OB_PROP_LASER_BOOL,
+            self.config.laser_enabled,
+        )? && !self.config.laser_enabled
+        {
+            warn!(
+                target: LOG_TARGET,
+                "laser_enabled is off but the device can't turn its laser off, ignoring"
+            );
+        }
+        if let Some(power) = self.config.laser_power {
+            let property = ob::OBPropertyID_OB_PROP_LASER_POWER_LEVEL_CONTROL_INT;
+            match self.set_int_property(property, power) {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!(
+                        target: LOG_TARGET,
+                        "laser_power is set but the device doesn't support it, ignoring"
+                    )
+                }
+                Err(err) => {
+                    warn!(target: LOG_TARGET, "Failed to set laser_power to {power}: {err}")
+                }
+            }
+        }
+
+        let manual_exposure = [
+            (
+                ob::OBPropertyID_OB_PROP_COLOR_EXPOSURE_INT,
+                "color_exposure",
+                self.config.color_exposure,
+            ),
+            (
+                ob::OBPropertyID_OB_PROP_COLOR_GAIN_INT,
+                "color_gain",
+                self.config.color_gain,
+            ),
+        ];
+        if manual_exposure.iter().any(|(_, _, value)| value.is_some()) {
+            // Manual values are ignored, or overwritten, while auto-exposure is on.
+            let auto_exposure = ob::OBPropertyID_OB_PROP_COLOR_AUTO_EXPOSURE_BOOL;
+            if !self.set_bool_property(auto_exposure, false)? {
+                warn!(
+                    target: LOG_TARGET,
+                    "The device can't turn off color auto-exposure, manual values may not apply"
+                );
+            }
+        }
+        for (property, name, value) in manual_exposure {
+            let Some(value) = value else {
+                continue;
+            };
+            // A bad exposure shouldn't stop the whole capture, only report it.
+            match self.set_int_property(property, value) {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!(
+                        target: LOG_TARGET,
+                        "{name} is set but the device doesn't support it, ignoring"
+                    )
+                }
+                Err(err) => warn!(target: LOG_TARGET, "Failed to set {name} to {value}: {err}"),
+            }
+        }
+
+        if channels.camera != 0 {
+            return Ok(());
+        }
+        *lock(&channels.color_controls) = ColorControls {
+            auto_exposure: self
+                .get_bool_property(ob::OBPropertyID_OB_PROP_COLOR_AUTO_EXPOSURE_BOOL)?,
+            exposure: self.get_int_property(ob::OBPropertyID_OB_PROP_COLOR_EXPOSURE_INT)?,
+            gain: self.get_int_property(ob::OBPropertyID_OB_PROP_COLOR_GAIN_INT)?,
+        };
+
+        Ok(())
+    }
+
+    /// Switches the device to the sync `mode`, keeping its other sync settings, and enables
+    /// the trigger out port when primary. Returns `false` without changing anything if the
+    /// device doesn't support the mode.
+    unsafe fn set_sync_mode(&mut self, mode: SyncMode) -> Result<bool, OrbbecError> {
+        let device = self.device()?;
+        let supported =
+            ob::ob_device_get_supported_multi_device_sync_mode_bitmap(device, &mut self.error);
+        check_error(&mut self.error)?;
+        // The modes are bit flags, so the bitmap has the bit of each supported mode set.
+        if u32::from(supported) & mode.to_ob() as u32 == 0 {
+            return Ok(false);
+        }
+
+        let mut sync_config = ob::ob_device_get_multi_device_sync_config(device, &mut self.error);
+        check_error(&mut self.error)?;
+        sync_config.syncMode = mode.to_ob();
+        sync_config.triggerOutEnable = mode == SyncMode::Primary;
+        ob::ob_device_set_multi_device_sync_config(device, &sync_config, &mut self.error);
+        check_error(&mut self.error)?;
+        Ok(true)
+    }
+
+    /// Whether the device supports the property with the given permission.
+    unsafe fn is_property_supported(
+        &mut self,
+        property: ob::OBPropertyID,
+        permission: ob::OBPermissionType,
+    ) -> Result<bool, OrbbecError> {
+        let device = self.device()?;
+        let supported =
+            ob::ob_device_is_property_supported(device, property, permission, &mut self.error);
+        check_error(&mut self.error)?;
+        Ok(supported)
+    }
+
+    /// Sets a boolean device property, returning `false` without setting it if the device
+    /// doesn't support it.
+    unsafe fn set_bool_property(
+        &mut self,
+        property: ob::OBPropertyID,
+        value: bool,
+    ) -> Result<bool, OrbbecError> {
+        if !self.is_property_supported(property, ob::OBPermissionType_OB_PERMISSION_WRITE)? {
+            return Ok(false);
+        }
+        ob::ob_device_set_bool_property(self.device, property, value, &mut self.error);
+        check_error(&mut self.error)?;
+        Ok(true)
+    }
+
+    /// Sets an integer device property, returning `false` without setting it if the device
+    /// doesn't support it.
+    unsafe fn set_int_property(
+        &mut self,
+        property: ob::OBPropertyID,
+        value: i32,
+    ) -> Result<bool, OrbbecError> {
+        if !self.is_property_supported(property, ob::OBPermissionType_OB_PERMISSION_WRITE)? {
+            return Ok(false);
+        }
+        ob::ob_device_set_int_property(self.device, property, value, &mut self.error);
+        check_error(&mut self.error)?;
+        Ok(true)
     }
 
-    // Configure depth flow
-    let mut depth_profile: *mut ob::ob_stream_profile = null_mut();
-    let mut align_mode: ob::OBAlignMode = ob::OBAlignMode_ALIGN_DISABLE;
-    let mut depth_profiles: *mut ob::ob_stream_profile_list = null_mut();
+    /// Reads a boolean device property, or `None` if the device doesn't support reading it.
+    unsafe fn get_bool_property(
+        &mut self,
+        property: ob::OBPropertyID,
+    ) -> Result<Option<bool>, OrbbecError> {
+        if !self.is_property_supported(property, ob::OBPermissionType_OB_PERMISSION_READ)? {
+            return Ok(None);
+        }
+        let value = ob::ob_device_get_bool_property(self.device, property, &mut self.error);
+        check_error(&mut self.error)?;
+        Ok(Some(value))
+    }
 
-    if !color_profile.is_null() {
-        // Try find supported depth to color align hardware mode profile
-        depth_profiles = ob::ob_get_d2c_depth_profile_list(
-            ob_pipeline,
-            color_profile,
-            ob::OBAlignMode_ALIGN_D2C_HW_MODE,
-            &mut error,
-        );
-        check_error(error);
-        let mut d2c_count = ob::ob_stream_profile_list_count(depth_profiles, &mut error);
-        check_error(error);
-        if d2c_count > 0 {
-            align_mode = ob::OBAlignMode_ALIGN_D2C_HW_MODE;
+    /// Reads an integer device property, or `None` if the device doesn't support reading it.
+    unsafe fn get_int_property(
+        &mut self,
+        property: ob::OBPropertyID,
+    ) -> Result<Option<i32>, OrbbecError> {
+        if !self.is_property_supported(property, ob::OBPermissionType_OB_PERMISSION_READ)? {
+            return Ok(None);
+        }
+        let value = ob::ob_device_get_int_property(self.device, property, &mut self.error);
+        check_error(&mut self.error)?;
+        Ok(Some(value))
+    }
+
+    /// Reads the name, serial number and firmware of the device, and the profiles chosen for
+    /// its streams. Must run after [`Self::start_streams`].
+    unsafe fn read_opened_device(&mut self) -> Result<OpenedDevice, OrbbecError> {
+        let device = self.device()?;
+        let info = ob::ob_device_get_device_info(device, &mut self.error);
+        check_error(&mut self.error)?;
+        let name = c_str_to_string(ob::ob_device_info_name(info, &mut self.error));
+        let name = check_error(&mut self.error).map(|()| name);
+        let serial_number =
+            c_str_to_string(ob::ob_device_info_serial_number(info, &mut self.error));
+        let serial_number = check_error(&mut self.error).map(|()| serial_number);
+        let firmware_version =
+            c_str_to_string(ob::ob_device_info_firmware_version(info, &mut self.error));
+        let firmware_version = check_error(&mut self.error).map(|()| firmware_version);
+        // Deleted before returning any error above, so it isn't leaked.
+        ob::ob_delete_device_info(info, &mut self.error);
+        check_error(&mut self.error)?;
+
+        let color_profile = if self.color_profile.is_null() {
+            None
         } else {
-            // Try find supported depth to color align software mode profile
-            depth_profiles = ob::ob_get_d2c_depth_profile_list(
-                ob_pipeline,
-                color_profile,
-                ob::OBAlignMode_ALIGN_D2C_SW_MODE,
-                &mut error,
-            );
-            check_error(error);
-            d2c_count = ob::ob_stream_profile_list_count(depth_profiles, &mut error);
-            check_error(error);
-            if d2c_count > 0 {
-                align_mode = ob::OBAlignMode_ALIGN_D2C_SW_MODE;
-            }
-        }
-    } else {
-        depth_profiles = ob::ob_pipeline_get_stream_profile_list(
-            ob_pipeline,
-            ob::OBSensorType_OB_SENSOR_DEPTH,
-            &mut error,
+            Some(read_video_profile(self.color_profile, &mut self.error)?)
+        };
+        let depth_profile = if self.depth_profile.is_null() {
+            None
+        } else {
+            Some(read_video_profile(self.depth_profile, &mut self.error)?)
+        };
+        Ok(OpenedDevice {
+            name: name?,
+            serial_number: serial_number?,
+            firmware_version: firmware_version?,
+            color_profile,
+            depth_profile,
+        })
+    }
+
+    /// Reads the device's temperatures, or `None` if the device doesn't report them.
+    unsafe fn read_temperature(&mut self) -> Result<Option<OrbbecTemperature>, OrbbecError> {
+        let property = ob::OBPropertyID_OB_STRUCT_DEVICE_TEMPERATURE;
+        if !self.is_property_supported(property, ob::OBPermissionType_OB_PERMISSION_READ)? {
+            return Ok(None);
+        }
+        let mut data: ob::OBDeviceTemperature = std::mem::zeroed();
+        let mut size = std::mem::size_of::<ob::OBDeviceTemperature>() as u32;
+        ob::ob_device_get_structured_data(
+            self.device,
+            property,
+            &mut data as *mut _ as *mut c_void,
+            &mut size,
+            &mut self.error,
         );
-        check_error(error);
+        check_error(&mut self.error)?;
+
+        // Devices leave the channels they don't have at zero.
+        let channel = |value: f32| (value.is_finite() && value != 0.0).then_some(value);
+        Ok(Some(OrbbecTemperature {
+            cpu: channel(data.cpuTemp),
+            ir: channel(data.irTemp),
+            ir_left: channel(data.irLeftTemp),
+            ir_right: channel(data.irRightTemp),
+            laser: channel(data.ldmTemp),
+            main_board: channel(data.mainBoardTemp),
+            tec: channel(data.tecTemp),
+            imu: channel(data.imuTemp),
+            color: channel(data.rgbTemp),
+            chip_top: channel(data.chipTopTemp),
+            chip_bottom: channel(data.chipBottomTemp),
+        }))
     }
 
-    let list_count = ob::ob_stream_profile_list_count(depth_profiles, &mut error);
-    check_error(error);
-    if list_count > 0 {
-        if !color_profile.is_null() {
-            // Select the profile with the same frame rate as color.
-            let color_fps = ob::ob_video_stream_profile_fps(color_profile, &mut error);
-            check_error(error);
-            depth_profile = ob::ob_stream_profile_list_get_video_stream_profile(
-                depth_profiles,
+    /// Starts the accelerometer and gyroscope, unless disabled, which report outside of the
+    /// pipeline's framesets.
+    unsafe fn start_imu(&mut self, imu: &Arc<Mutex<Option<OrbbecImu>>>) -> Result<(), OrbbecError> {
+        let device = self.device()?;
+        let imu = self.imu.insert(imu.clone());
+        if self.config.sensor_enabled(SensorType::Accel) {
+            self.accel.start(
+                device,
+                ob::OBSensorType_OB_SENSOR_ACCEL,
+                imu,
+                &mut self.error,
+            )?;
+        }
+        if self.config.sensor_enabled(SensorType::Gyro) {
+            self.gyro.start(
+                device,
+                ob::OBSensorType_OB_SENSOR_GYRO,
+                imu,
+                &mut self.error,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Enables the IR sensor's stream, at the depth stream's frame rate when it offers one.
+    unsafe fn enable_ir_stream(&mut self) -> Result<(), OrbbecError> {
+        self.ir_profiles = ob::ob_pipeline_get_stream_profile_list(
+            self.pipeline,
+            ob::OBSensorType_OB_SENSOR_IR,
+            &mut self.error,
+        );
+        check_error(&mut self.error).map_err(|err| {
+            OrbbecError::invalid_value(
+                &err.function,
+                err.args,
+                format!(
+                    "capture_ir_image is set but the device has no IR sensor: {}",
+                    err.message
+                ),
+            )
+        })?;
+
+        if !self.depth_profile.is_null() {
+            let depth_fps = ob::ob_video_stream_profile_fps(self.depth_profile, &mut self.error);
+            check_error(&mut self.error)?;
+            self.ir_profile = ob::ob_stream_profile_list_get_video_stream_profile(
+                self.ir_profiles,
                 ob::OB_WIDTH_ANY as c_int,
                 ob::OB_HEIGHT_ANY as c_int,
                 ob::OBFormat_OB_FORMAT_UNKNOWN,
-                color_fps as c_int,
-                &mut error,
+                depth_fps as c_int,
+                &mut self.error,
             );
-            check_error(error);
+            // No profile at that frame rate, fall back to the default below.
+            if check_error(&mut self.error).is_err() {
+                self.ir_profile = null_mut();
+            }
         }
 
-        if depth_profile.is_null() {
-            // If no matching profile is found, select the default profile.
-            depth_profile = ob::ob_stream_profile_list_get_profile(
-                depth_profiles,
+        if self.ir_profile.is_null() {
+            self.ir_profile = ob::ob_stream_profile_list_get_profile(
+                self.ir_profiles,
                 ob::OB_PROFILE_DEFAULT as c_int,
-                &mut error,
+                &mut self.error,
+            );
+            check_error(&mut self.error)?;
+        }
+
+        ob::ob_config_enable_stream(self.ob_config, self.ir_profile, &mut self.error);
+        check_error(&mut self.error)?;
+
+        Ok(())
+    }
+
+    /// Copies the color frame out of the frameset as an RGBA image.
+    unsafe fn color_image(
+        &mut self,
+        frameset: *mut ob::ob_frame,
+    ) -> Result<Option<RgbaImage>, OrbbecError> {
+        let color_frame: *mut ob::ob_frame = ob::ob_frameset_color_frame(frameset, &mut self.error);
+        check_error(&mut self.error)?;
+        if color_frame.is_null() {
+            return Ok(None);
+        }
+
+        let width = ob::ob_video_frame_width(color_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let height = ob::ob_video_frame_height(color_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let format = ob::ob_frame_format(color_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let data_size = ob::ob_frame_data_size(color_frame, &mut self.error) as usize;
+        check_error(&mut self.error)?;
+        let data = ob::ob_frame_data(color_frame, &mut self.error) as *const u8;
+        check_error(&mut self.error)?;
+        let data = std::slice::from_raw_parts(data, data_size);
+
+        let image = match format {
+            ob::OBFormat_OB_FORMAT_RGB => Some(RgbaImage::from_rgb(width, height, data)),
+            ob::OBFormat_OB_FORMAT_BGR => Some(RgbaImage::from_bgr(width, height, data)),
+            ob::OBFormat_OB_FORMAT_MJPG => RgbaImage::from_mjpeg(data),
+            _ => {
+                warn!(
+                    target: LOG_TARGET,
+                    "Unsupported color frame format {format}, color image capture disabled"
+                );
+                self.config.capture_color_image = false;
+                None
+            }
+        };
+
+        ob::ob_delete_frame(color_frame, &mut self.error);
+        check_error(&mut self.error)?;
+
+        Ok(image)
+    }
+
+    /// Colorizes the depth frame of the frameset as an RGBA image.
+    unsafe fn depth_image(
+        &mut self,
+        frameset: *mut ob::ob_frame,
+    ) -> Result<Option<RgbaImage>, OrbbecError> {
+        let depth_frame: *mut ob::ob_frame = ob::ob_frameset_depth_frame(frameset, &mut self.error);
+        check_error(&mut self.error)?;
+        if depth_frame.is_null() {
+            return Ok(None);
+        }
+
+        let width = ob::ob_video_frame_width(depth_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let height = ob::ob_video_frame_height(depth_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let format = ob::ob_frame_format(depth_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let value_scale = ob::ob_depth_frame_get_value_scale(depth_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let data_size = ob::ob_frame_data_size(depth_frame, &mut self.error) as usize;
+        check_error(&mut self.error)?;
+        let data = ob::ob_frame_data(depth_frame, &mut self.error) as *const u16;
+        check_error(&mut self.error)?;
+
+        let image = match format {
+            ob::OBFormat_OB_FORMAT_Y16 | ob::OBFormat_OB_FORMAT_Z16 => {
+                let depth = std::slice::from_raw_parts(data, data_size / 2);
+                Some(RgbaImage::from_depth(
+                    width,
+                    height,
+                    depth,
+                    value_scale,
+                    self.config.depth_image_near_mm,
+                    self.config.depth_image_far_mm,
+                ))
+            }
+            _ => {
+                warn!(
+                    target: LOG_TARGET,
+                    "Unsupported depth frame format {format}, depth image capture disabled"
+                );
+                self.config.capture_depth_image = false;
+                None
+            }
+        };
+
+        ob::ob_delete_frame(depth_frame, &mut self.error);
+        check_error(&mut self.error)?;
+
+        Ok(image)
+    }
+
+    /// Copies the IR frame out of the frameset as a grayscale RGBA image.
+    unsafe fn ir_image(
+        &mut self,
+        frameset: *mut ob::ob_frame,
+    ) -> Result<Option<RgbaImage>, OrbbecError> {
+        let ir_frame: *mut ob::ob_frame = ob::ob_frameset_ir_frame(frameset, &mut self.error);
+        check_error(&mut self.error)?;
+        if ir_frame.is_null() {
+            return Ok(None);
+        }
+
+        let width = ob::ob_video_frame_width(ir_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let height = ob::ob_video_frame_height(ir_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let format = ob::ob_frame_format(ir_frame, &mut self.error);
+        check_error(&mut self.error)?;
+        let data_size = ob::ob_frame_data_size(ir_frame, &mut self.error) as usize;
+        check_error(&mut self.error)?;
+        let data = ob::ob_frame_data(ir_frame, &mut self.error);
+        check_error(&mut self.error)?;
+
+        let image = match format {
+            ob::OBFormat_OB_FORMAT_Y8 => {
+                let ir = std::slice::from_raw_parts(data as *const u8, data_size);
+                Some(RgbaImage::from_gray(width, height, ir))
+            }
+            ob::OBFormat_OB_FORMAT_Y16 => {
+                let ir = std::slice::from_raw_parts(data as *const u16, data_size / 2);
+                Some(RgbaImage::from_gray16(width, height, ir))
+            }
+            _ => {
+                warn!(
+                    target: LOG_TARGET,
+                    "Unsupported IR frame format {format}, IR image capture disabled"
+                );
+                self.config.capture_ir_image = false;
+                None
+            }
+        };
+
+        ob::ob_delete_frame(ir_frame, &mut self.error);
+        check_error(&mut self.error)?;
+
+        Ok(image)
+    }
+
+    /// Creates the filters of [`Self::depth_filters`] the config asks for. Spatial filtering
+    /// comes first, so the temporal filter blends depth that is already smoothed rather than
+    /// carrying single frame noise into the following frames. Holes are filled last, from
+    /// the smoothed depth, and so the other filters don't treat the filled values as
+    /// measurements. The depth range is applied to the points afterwards, so the filters see
+    /// the whole image, as they would otherwise smooth toward the removed pixels.
+    unsafe fn create_depth_filters(&mut self) -> Result<(), OrbbecError> {
+        if let Some(params) = self.config.spatial_filter {
+            let filter = ob::ob_create_spatial_advanced_filter(&mut self.error);
+            check_error(&mut self.error)?;
+            // Kept before configuring it, so it is deleted on failure.
+            self.depth_filters.push(filter);
+            ob::ob_spatial_advanced_filter_set_filter_params(
+                filter,
+                ob::ob_spatial_advanced_filter_params {
+                    magnitude: params.magnitude,
+                    alpha: params.alpha,
+                    disp_diff: params.disp_diff,
+                    radius: params.radius,
+                },
+                &mut self.error,
             );
-            check_error(error);
-        }
-
-        // enable stream
-        ob::ob_config_enable_stream(ob_config, depth_profile, &mut error);
-        check_error(error);
-
-        // Turn on D2C alignment, which needs to be turned on when generating RGBD point clouds
-        ob::ob_config_set_align_mode(ob_config, align_mode, &mut error);
-        check_error(error);
-    }
-
-    // Get the device handle
-    let ob_device: *mut ob::ob_device = ob::ob_pipeline_get_device(ob_pipeline, &mut error);
-    check_error(error);
-
-    // Start the pipeline with config
-    ob::ob_pipeline_start_with_config(ob_pipeline, ob_config, &mut error);
-    check_error(error);
-
-    // Create a point cloud Filter object (device parameters will be obtained inside the Pipeline when the point cloud filter is created, so try to configure
-    // the device before creating the filter)
-    let point_cloud: *mut ob::ob_filter = ob::ob_create_pointcloud_filter(&mut error);
-    check_error(error);
-
-    // Obtain the current open-stream camera parameters from the pipeline and pass them to the point cloud filter
-    let camera_param: ob::ob_camera_param =
-        ob::ob_pipeline_get_camera_param(ob_pipeline, &mut error);
-    check_error(error);
-    ob::ob_pointcloud_filter_set_camera_param(point_cloud, camera_param, &mut error);
-    check_error(error);
-
-    let mut count = 0;
-    let mut points_created = false;
-
-    // Loop to get the frame and save the point cloud
-    loop {
-        bevy::prelude::info!("count: {}", count);
-                count          = 0;
-                points_created = false;
-                // Limit up to 10 repetitions
-                    // Waiting for one frame, the timeout is 100ms
-                    let frameset: *mut ob::ob_frame = ob::ob_pipeline_wait_for_frameset(ob_pipeline, 100, &mut error);
-                    check_error(error);
-                    if !frameset.is_null() {
-                        // get depth value scale
-                        let depth_frame: *mut ob::ob_frame = ob::ob_frameset_depth_frame(frameset, &mut error);
-                        check_error(error);
-                        if depth_frame.is_null() {
-                            continue;
+            check_error(&mut self.error)?;
+        }
+        if let Some(params) = self.config.temporal_filter {
+            let filter = ob::ob_create_temporal_filter(&mut self.error);
+            check_error(&mut self.error)?;
+            self.depth_filters.push(filter);
+            ob::ob_temporal_filter_set_weight(filter, params.alpha, &mut self.error);
+            check_error(&mut self.error)?;
+            ob::ob_temporal_filter_set_diff_scale(filter, params.delta, &mut self.error);
+            check_error(&mut self.error)?;
+        }
+        if let Some(mode) = self.config.hole_filling {
+            let filter = ob::ob_create_hole_filling_filter(&mut self.error);
+            check_error(&mut self.error)?;
+            self.depth_filters.push(filter);
+            ob::ob_hole_filling_filter_set_mode(filter, mode.to_ob(), &mut self.error);
+            check_error(&mut self.error)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the depth frame of `frameset` through [`Self::depth_filters`] and puts the result
+    /// back in its place.
+    unsafe fn filter_depth(&mut self, frameset: *mut ob::ob_frame) -> Result<(), OrbbecError> {
+        if self.depth_filters.is_empty() {
+            return Ok(());
+        }
+        let mut depth_frame: *mut ob::ob_frame =
+            ob::ob_frameset_depth_frame(frameset, &mut self.error);
+        check_error(&mut self.error)?;
+        if depth_frame.is_null() {
+            return Ok(());
+        }
+
+        for &filter in &self.depth_filters {
+            let filtered = ob::ob_filter_process(filter, depth_frame, &mut self.error);
+            let processed = check_error(&mut self.error);
+            ob::ob_delete_frame(depth_frame, &mut self.error);
+            processed?;
+            check_error(&mut self.error)?;
+            if filtered.is_null() {
+                // Nothing to put back, so the frameset keeps its unfiltered depth.
+                return Ok(());
+            }
+            depth_frame = filtered;
+        }
+
+        ob::ob_frameset_push_frame(
+            frameset,
+            ob::OBFrameType_OB_FRAME_DEPTH,
+            depth_frame,
+            &mut self.error,
+        );
+        let pushed = check_error(&mut self.error);
+        ob::ob_delete_frame(depth_frame, &mut self.error);
+        pushed?;
+        check_error(&mut self.error)
+    }
+
+    /// Generates the point cloud for the frameset, returned along with the depth frame's
+    /// device timestamp and frame index. `None` when the frameset has no depth frame or the
+    /// point cloud filter produced nothing.
+    unsafe fn process_frameset(
+        &mut self,
+        frameset: *mut ob::ob_frame,
+        channels: &WorkerChannels,
+    ) -> Result<Option<(PointPayload, u64, u64)>, OrbbecError> {
+        let depth_frame: *mut ob::ob_frame = ob::ob_frameset_depth_frame(frameset, &mut self.error);
+        check_error(&mut self.error)?;
+        if depth_frame.is_null() {
+            return Ok(None);
+        }
+
+        // get depth value scale, timestamp and index, deleting the depth frame even when
+        // reading any of them fails
+        let read = |error: &mut *mut ob::ob_error| {
+            let depth_value_scale: f32 = ob::ob_depth_frame_get_value_scale(depth_frame, error);
+            check_error(error)?;
+            let device_timestamp_us = ob::ob_frame_time_stamp_us(depth_frame, error);
+            check_error(error)?;
+            let index = ob::ob_frame_index(depth_frame, error);
+            check_error(error)?;
+            Ok::<_, OrbbecError>((depth_value_scale, device_timestamp_us, index))
+        };
+        let read = read(&mut self.error);
+        ob::ob_delete_frame(depth_frame, &mut self.error);
+        let (depth_value_scale, device_timestamp_us, index) = read?;
+        check_error(&mut self.error)?;
+
+        // point position value multiply depth value scale to convert uint to millimeter (for some devices, the default depth value uint is not
+        // millimeter)
+        ob::ob_pointcloud_filter_set_position_data_scale(
+            self.point_cloud,
+            depth_value_scale,
+            &mut self.error,
+        );
+        check_error(&mut self.error)?;
+
+        // Only color the points when there is a color frame to take the colors from, which
+        // isn't the case for depth only devices and recordings.
+        let color_frame: *mut ob::ob_frame = ob::ob_frameset_color_frame(frameset, &mut self.error);
+        check_error(&mut self.error)?;
+        let colored = !color_frame.is_null();
+        if colored {
+            ob::ob_delete_frame(color_frame, &mut self.error);
+            check_error(&mut self.error)?;
+        }
+
+        let point_format = if colored {
+            ob::OBFormat_OB_FORMAT_RGB_POINT
+        } else {
+            ob::OBFormat_OB_FORMAT_POINT
+        };
+        ob::ob_pointcloud_filter_set_point_format(self.point_cloud, point_format, &mut self.error);
+        check_error(&mut self.error)?;
+
+        let points_frame: *mut ob::ob_frame =
+            ob::ob_filter_process(self.point_cloud, frameset, &mut self.error);
+        check_error(&mut self.error)?;
+        if points_frame.is_null() {
+            return Ok(None);
+        }
+
+        // Subsampled while copying, so the skipped points are never copied. The points are
+        // copied out before the frame is deleted, which happens even when reading it fails.
+        let step = channels.subsample(&self.config);
+        let copy = |error: &mut *mut ob::ob_error| {
+            let data_size = ob::ob_frame_data_size(points_frame, error) as usize;
+            check_error(error)?;
+            let data = ob::ob_frame_data(points_frame, error);
+            check_error(error)?;
+            Ok::<_, OrbbecError>(if colored {
+                PointPayload::Color(copy_points(data, data_size, step, ColorPoint::from))
+            } else {
+                PointPayload::Depth(copy_points(data, data_size, step, |p: ob::OBPoint| {
+                    Vec3::new(p.x, p.y, p.z)
+                }))
+            })
+        };
+        let copied = copy(&mut self.error);
+        ob::ob_delete_frame(points_frame, &mut self.error);
+        let mut points = copied?;
+        check_error(&mut self.error)?;
+
+        points.retain_depth(|z| self.config.in_depth_range(z));
+        if self.config.capture_crop.enabled {
+            let camera = channels.camera;
+            points.retain_positions(|p| self.config.in_capture_crop(camera, p));
+        }
+        if let Some(voxel_size_mm) = channels.voxel_size_mm(&self.config) {
+            points = points.voxel_downsample(voxel_size_mm);
+        }
+
+        Ok(Some((points, device_timestamp_us, index)))
+    }
+}
+
+/// Copies every `step`th point out of a point cloud frame's data, converting each from the
+/// SDK's point type `T`.
+#[cfg(feature = "sdk")]
+unsafe fn copy_points<T: Copy, U>(
+    data: *const c_void,
+    data_size: usize,
+    step: usize,
+    convert: impl FnMut(T) -> U,
+) -> Vec<U> {
+    std::slice::from_raw_parts(data as *const T, data_size / std::mem::size_of::<T>())
+        .iter()
+        .step_by(step.max(1))
+        .copied()
+        .map(convert)
+        .collect()
+}
+
+#[cfg(feature = "sdk")]
+impl Drop for Orbbec {
+    fn drop(&mut self) {
+        // Cleanup is best effort: any error raised while tearing down is discarded so that
+        // the remaining handles still get released.
+        unsafe {
+            if !self.error.is_null() {
+                ob::ob_delete_error(self.error);
+                self.error = null_mut();
+            }
+
+            // Stop the IMU callbacks before anything they depend on goes away.
+            self.accel.release(&mut self.error);
+            self.gyro.release(&mut self.error);
+
+            if !self.point_cloud.is_null() {
+                ob::ob_delete_filter(self.point_cloud, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+            for filter in self.depth_filters.drain(..) {
+                ob::ob_delete_filter(filter, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.playback.is_null() {
+                ob::ob_delete_playback(self.playback, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            // Finalize the recording if capture stopped on an error.
+            if self.recording {
+                ob::ob_pipeline_stop_record(self.pipeline, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            // destroy pipeline
+            if !self.pipeline.is_null() {
+                ob::ob_delete_pipeline(self.pipeline, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.device.is_null() {
+                ob::ob_delete_device(self.device, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.device_list.is_null() {
+                ob::ob_delete_device_list(self.device_list, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.context.is_null() {
+                ob::ob_delete_context(self.context, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            // destroy config
+            if !self.ob_config.is_null() {
+                ob::ob_delete_config(self.ob_config, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            // destroy profile
+            if !self.depth_profile.is_null() {
+                ob::ob_delete_stream_profile(self.depth_profile, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            // destroy profile
+            if !self.color_profile.is_null() {
+                ob::ob_delete_stream_profile(self.color_profile, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            // destroy profile list
+            if !self.color_profiles.is_null() {
+                ob::ob_delete_stream_profile_list(self.color_profiles, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.depth_profiles.is_null() {
+                ob::ob_delete_stream_profile_list(self.depth_profiles, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.ir_profile.is_null() {
+                ob::ob_delete_stream_profile(self.ir_profile, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.ir_profiles.is_null() {
+                ob::ob_delete_stream_profile_list(self.ir_profiles, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+        }
+    }
+}
+
+/// A connected device, as reported by [`enumerate_devices`].
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub serial_number: String,
+    pub pid: i32,
+    pub vid: i32,
+    pub firmware_version: String,
+    /// Profiles of the color stream, to pick [`OrbbecConfig::color_profile`] from. Empty for
+    /// devices without a color sensor.
+    pub color_profiles: Vec<StreamProfile>,
+    /// Profiles of the depth stream, to pick [`OrbbecConfig::depth_profile`] from. Not all
+    /// of them support alignment to every color profile.
+    pub depth_profiles: Vec<StreamProfile>,
+    /// Every sensor the device has, with its profiles, to pick
+    /// [`OrbbecConfig::disabled_sensors`] from.
+    pub sensors: Vec<SensorInfo>,
+}
+
+/// The device a capture thread opened and the profiles it streams, see [`OrbbecRx::device`].
+#[derive(Debug, Clone)]
+pub struct OpenedDevice {
+    pub name: String,
+    pub serial_number: String,
+    pub firmware_version: String,
+    /// `None` for devices without a color sensor.
+    pub color_profile: Option<StreamProfile>,
+    pub depth_profile: Option<StreamProfile>,
+}
+
+/// Lists the devices currently connected. This doesn't start any stream, so it can be used
+/// to pick a serial for [`OrbbecConfig`] before [`OrbbecRx`] is created.
+#[cfg(feature = "sdk")]
+pub fn enumerate_devices() -> Vec<DeviceInfo> {
+    let mut devices = Vec::new();
+    let mut enumeration = DeviceEnumeration::new();
+    if let Err(err) = unsafe { enumeration.run(&mut devices) } {
+        error!(target: LOG_TARGET, "Failed to enumerate devices: {err}");
+    }
+    devices
+}
+
+#[cfg(feature = "sdk")]
+struct DeviceEnumeration {
+    error: *mut ob::ob_error,
+    context: *mut ob::ob_context,
+    device_list: *mut ob::ob_device_list,
+    device: *mut ob::ob_device,
+    device_info: *mut ob::ob_device_info,
+}
+
+#[cfg(feature = "sdk")]
+impl DeviceEnumeration {
+    fn new() -> Self {
+        Self {
+            error: null_mut(),
+            context: null_mut(),
+            device_list: null_mut(),
+            device: null_mut(),
+            device_info: null_mut(),
+        }
+    }
+
+    unsafe fn run(&mut self, devices: &mut Vec<DeviceInfo>) -> Result<(), OrbbecError> {
+        self.context = ob::ob_create_context(&mut self.error);
+        check_error(&mut self.error)?;
+
+        self.device_list = ob::ob_query_device_list(self.context, &mut self.error);
+        check_error(&mut self.error)?;
+
+        let count = ob::ob_device_list_device_count(self.device_list, &mut self.error);
+        check_error(&mut self.error)?;
+
+        for index in 0..count {
+            self.device = ob::ob_device_list_get_device(self.device_list, index, &mut self.error);
+            check_error(&mut self.error)?;
+
+            self.device_info = ob::ob_device_get_device_info(self.device, &mut self.error);
+            check_error(&mut self.error)?;
+
+            let name = c_str_to_string(ob::ob_device_info_name(self.device_info, &mut self.error));
+            check_error(&mut self.error)?;
+            let serial_number = c_str_to_string(ob::ob_device_info_serial_number(
+                self.device_info,
+                &mut self.error,
+            ));
+            check_error(&mut self.error)?;
+            let pid = ob::ob_device_info_pid(self.device_info, &mut self.error);
+            check_error(&mut self.error)?;
+            let vid = ob::ob_device_info_vid(self.device_info, &mut self.error);
+            check_error(&mut self.error)?;
+            let firmware_version = c_str_to_string(ob::ob_device_info_firmware_version(
+                self.device_info,
+                &mut self.error,
+            ));
+            check_error(&mut self.error)?;
+            let color_profiles = self.sensor_profiles(OBSensorType_OB_SENSOR_COLOR);
+            let depth_profiles = self.sensor_profiles(ob::OBSensorType_OB_SENSOR_DEPTH);
+            let sensors = self.sensors()?;
+
+            devices.push(DeviceInfo {
+                name,
+                serial_number,
+                pid,
+                vid,
+                firmware_version,
+                color_profiles,
+                depth_profiles,
+                sensors,
+            });
+
+            ob::ob_delete_device_info(self.device_info, &mut self.error);
+            self.device_info = null_mut();
+            check_error(&mut self.error)?;
+
+            ob::ob_delete_device(self.device, &mut self.error);
+            self.device = null_mut();
+            check_error(&mut self.error)?;
+        }
+
+        Ok(())
+    }
+
+    /// The sensors of the current device. Sensor types this crate doesn't know are skipped.
+    unsafe fn sensors(&mut self) -> Result<Vec<SensorInfo>, OrbbecError> {
+        let list = ob::ob_device_get_sensor_list(self.device, &mut self.error);
+        check_error(&mut self.error)?;
+        let mut sensor_types = Vec::new();
+        let count = ob::ob_sensor_list_get_sensor_count(list, &mut self.error);
+        let counted = check_error(&mut self.error);
+        if counted.is_ok() {
+            for index in 0..count {
+                let sensor_type = ob::ob_sensor_list_get_sensor_type(list, index, &mut self.error);
+                if check_error(&mut self.error).is_ok() {
+                    sensor_types.extend(SensorType::from_ob(sensor_type));
+                }
+            }
+        }
+        // Deleted before returning the error above, so it isn't leaked.
+        ob::ob_delete_sensor_list(list, &mut self.error);
+        check_error(&mut self.error)?;
+        counted?;
+
+        Ok(sensor_types
+            .into_iter()
+            .map(|sensor_type| SensorInfo {
+                sensor_type,
+                profiles: if sensor_type.is_video() {
+                    self.sensor_profiles(sensor_type.to_ob())
+                } else {
+                    Vec::new()
+                },
+            })
+            .collect())
+    }
+
+    /// The video profiles of the current device's sensor, empty if it has no such sensor or
+    /// they can't be read.
+    unsafe fn sensor_profiles(&mut self, sensor_type: ob::OBSensorType) -> Vec<StreamProfile> {
+        let sensor = ob::ob_device_get_sensor(self.device, sensor_type, &mut self.error);
+        if check_error(&mut self.error).is_err() {
+            return Vec::new();
+        }
+        let list = ob::ob_sensor_get_stream_profile_list(sensor, &mut self.error);
+        let profiles = match check_error(&mut self.error) {
+            Ok(()) => {
+                let profiles = video_profiles(list, &mut self.error);
+                ob::ob_delete_stream_profile_list(list, &mut self.error);
+                let _ = check_error(&mut self.error);
+                profiles.unwrap_or_else(|err| {
+                    warn!(target: LOG_TARGET, "Failed to read stream profiles: {err}");
+                    Vec::new()
+                })
+            }
+            Err(_) => Vec::new(),
+        };
+        ob::ob_delete_sensor(sensor, &mut self.error);
+        let _ = check_error(&mut self.error);
+        profiles
+    }
+}
+
+#[cfg(feature = "sdk")]
+impl Drop for DeviceEnumeration {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.error.is_null() {
+                ob::ob_delete_error(self.error);
+                self.error = null_mut();
+            }
+
+            if !self.device_info.is_null() {
+                ob::ob_delete_device_info(self.device_info, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.device.is_null() {
+                ob::ob_delete_device(self.device, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.device_list.is_null() {
+                ob::ob_delete_device_list(self.device_list, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+
+            if !self.context.is_null() {
+                ob::ob_delete_context(self.context, &mut self.error);
+                let _ = check_error(&mut self.error);
+            }
+        }
+    }
+}
+
+/// Handle to the capture thread. Point clouds produced by the device are received through
+/// [`OrbbecRx::get_data`] / [`OrbbecRx::try_get_data`], and any error that stopped the thread
+/// through [`OrbbecRx::try_get_error`].
+///
+/// A `Receiver` isn't `Sync`, which resources must be, so each is behind a `Mutex`. Only the
+/// app locks them, uncontended, except for each camera's point cloud queue which is shared
+/// with its capture thread, see [`DropPolicy::DropOldest`].
+#[derive(Resource)]
+pub struct OrbbecRx {
+    /// Each camera's queue of point clouds, bounded separately so a fast camera can't crowd
+    /// out a slow one.
+    rx: Vec<Arc<Mutex<Receiver<Frame>>>>,
+    /// The camera of each queued point cloud, in the order they were queued.
+    rx_ready: Mutex<Receiver<usize>>,
+    latest_only: bool,
+    /// The newest frame of each camera not yet returned, in [`OrbbecConfig::latest_only`]
+    /// mode.
+    latest: Mutex<Vec<Frame>>,
+    frames_captured: Arc<AtomicU64>,
+    frames_dropped: Arc<AtomicU64>,
+    paused: Arc<AtomicBool>,
+    voxel_size: Arc<AtomicU32>,
+    subsample: Arc<AtomicUsize>,
+    triggers: Arc<AtomicU64>,
+    rx_color: Mutex<Receiver<RgbaImage>>,
+    rx_depth: Mutex<Receiver<RgbaImage>>,
+    rx_ir: Mutex<Receiver<RgbaImage>>,
+    rx_error: Mutex<Receiver<OrbbecError>>,
+    rx_camera_param: Mutex<Receiver<OrbbecCameraParam>>,
+    color_controls: Arc<Mutex<ColorControls>>,
+    imu: Arc<Mutex<Option<OrbbecImu>>>,
+    temperature: Arc<Mutex<Option<OrbbecTemperature>>>,
+    device: Arc<Mutex<Option<OpenedDevice>>>,
+    connection: Arc<Mutex<Vec<ConnectionState>>>,
+    tx_shutdown: Vec<Sender<()>>,
+    tx_reconfigure: Vec<Sender<OrbbecConfig>>,
+    jh: Vec<JoinHandle<()>>,
+}
+
+impl Default for OrbbecRx {
+    fn default() -> Self {
+        Self::with_config(OrbbecConfig::default())
+    }
+}
+
+impl OrbbecRx {
+    /// Spawns a capture thread per camera using the given configuration.
+    pub fn with_config(config: OrbbecConfig) -> Self {
+        let configs = config.capture_configs();
+        let latest_only = config.latest_only;
+        let (tx_ready, rx_ready) = channel();
+        let (tx_color, rx_color) = sync_channel(1);
+        let (tx_depth, rx_depth) = sync_channel(1);
+        let (tx_ir, rx_ir) = sync_channel(1);
+        let (tx_error, rx_error) = channel();
+        let (tx_camera_param, rx_camera_param) = channel();
+        let frames_captured = Arc::new(AtomicU64::new(0));
+        let frames_dropped = Arc::new(AtomicU64::new(0));
+        let paused = Arc::new(AtomicBool::new(false));
+        let voxel_size = Arc::new(AtomicU32::new(f32::NAN.to_bits()));
+        let subsample = Arc::new(AtomicUsize::new(0));
+        let triggers = Arc::new(AtomicU64::new(0));
+        let color_controls = Arc::new(Mutex::new(ColorControls::default()));
+        let imu = Arc::new(Mutex::new(None));
+        let temperature = Arc::new(Mutex::new(None));
+        let device = Arc::new(Mutex::new(None));
+        let connection = Arc::new(Mutex::new(vec![ConnectionState::default(); configs.len()]));
+
+        let mut rx = Vec::new();
+        let mut tx_shutdown = Vec::new();
+        let mut tx_reconfigure = Vec::new();
+        let mut jh = Vec::new();
+        for (camera, config) in configs.into_iter().enumerate() {
+            let (tx, queue) = sync_channel(config.frame_queue.max(1));
+            let queue = Arc::new(Mutex::new(queue));
+            rx.push(queue.clone());
+            let tx = FrameTx {
+                tx,
+                rx: queue,
+                tx_ready: tx_ready.clone(),
+                drop_policy: config.drop_policy,
+            };
+            let (shutdown, rx_shutdown) = channel();
+            tx_shutdown.push(shutdown);
+            let (reconfigure, rx_reconfigure) = channel();
+            tx_reconfigure.push(reconfigure);
+            let channels = WorkerChannels {
+                camera,
+                tx,
+                frames_captured: frames_captured.clone(),
+                frames_dropped: frames_dropped.clone(),
+                tx_color: tx_color.clone(),
+                tx_depth: tx_depth.clone(),
+                tx_ir: tx_ir.clone(),
+                rx_shutdown,
+                paused: paused.clone(),
+                voxel_size: voxel_size.clone(),
+                subsample: subsample.clone(),
+                triggers: triggers.clone(),
+                rx_reconfigure,
+                tx_camera_param: tx_camera_param.clone(),
+                color_controls: color_controls.clone(),
+                imu: imu.clone(),
+                temperature: temperature.clone(),
+                device: device.clone(),
+                connection: connection.clone(),
+            };
+            let tx_error = tx_error.clone();
+
+            jh.push(std::thread::spawn(move || {
+                let _span = info_span!(
+                    target: LOG_TARGET,
+                    "capture",
+                    camera,
+                    device = %config.device,
+                )
+                .entered();
+                let mut config = config;
+                loop {
+                    let run = || {
+                        let config = config.clone();
+                        match (config.load_from.clone(), config.connect_to.clone()) {
+                            (Some(path), _) => file::run(config, &path, &channels),
+                            #[cfg(feature = "websocket")]
+                            (None, Some(url)) => remote::run(config, &url, &channels),
+                            #[cfg(not(feature = "websocket"))]
+                            (None, Some(url)) => Err(OrbbecError::invalid_value(
+                                "connect_to",
+                                url,
+                                "Receiving over the network requires the websocket feature",
+                            )),
+                            #[cfg(all(feature = "sdk", not(feature = "mock")))]
+                            (None, None) => unsafe { Orbbec::new(config).run(&channels) },
+                            #[cfg(feature = "mock")]
+                            (None, None) => mock::run(config, &channels),
+                            #[cfg(not(any(feature = "sdk", feature = "mock")))]
+                            (None, None) => Err(OrbbecError::invalid_value(
+                                "device",
+                                config.device.to_string(),
+                                "Capturing from a device requires the sdk feature",
+                            )),
                         }
+                    };
+                    // A panic is reported like any other error rather than silently ending
+                    // the stream. The worker, and so the device, is dropped while unwinding.
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(run))
+                        .unwrap_or_else(|payload| Err(OrbbecError::panicked(payload)));
 
-                        // get depth value scale
-                        let depth_value_scale: f32 = ob::ob_depth_frame_get_value_scale(depth_frame, &mut error);
-                        check_error(error);
-
-                        // delete depth frame
-                        ob::ob_delete_frame(depth_frame, &mut error);
-                        check_error(error);
-
-                        // point position value multiply depth value scale to convert uint to millimeter (for some devices, the default depth value uint is not
-                        // millimeter)
-                        ob::ob_pointcloud_filter_set_position_data_scale(point_cloud, depth_value_scale, &mut error);
-                        check_error(error);
-
-                        ob::ob_pointcloud_filter_set_point_format(point_cloud, ob::OBFormat_OB_FORMAT_RGB_POINT, &mut error);
-                        check_error(error);
-                        let points_frame: *mut ob::ob_frame = ob::ob_filter_process(point_cloud, frameset, &mut error);
-                        check_error(error);
-                        if !points_frame.is_null() {
-                            // PUSH
-                            let points_size = ob::ob_frame_data_size(points_frame, &mut error) as usize / std::mem::size_of::<ob::OBColorPoint>();
-                            check_error(error);
-
-                            let points = ob::ob_frame_data(points_frame, &mut error) as *mut ob::OBColorPoint;
-                            let points = std::slice::from_raw_parts_mut(points, points_size);
-                            check_error(error);
-
-                            for point in points.iter() {
-                                let point = *point;
-                                println!("point: x: {:?}", point);
+                    // The device is closed when the worker is dropped, and reopened with the
+                    // new config on the same thread.
+                    match result {
+                        Ok(Some(reconfigure)) => {
+                            config = channels.settled_config(reconfigure);
+                            info!(
+                                target: LOG_TARGET,
+                                frames = channels.frames_captured.load(Ordering::Relaxed),
+                                "Restarting with new config"
+                            );
+                        }
+                        Ok(None) => break,
+                        Err(err) if err.is_disconnected() && config.reconnect => {
+                            warn!(
+                                target: LOG_TARGET,
+                                frames = channels.frames_captured.load(Ordering::Relaxed),
+                                "Disconnected: {err}"
+                            );
+                            channels.set_connection_state(ConnectionState::Reconnecting);
+                            match channels.wait_for_device(&config.device) {
+                                DeviceWait::Found => {
+                                    info!(target: LOG_TARGET, "Reconnecting");
+                                }
+                                DeviceWait::Reconfigured(reconfigure) => {
+                                    config = channels.settled_config(reconfigure);
+                                }
+                                DeviceWait::ShutDown => break,
                             }
-
-                            ob::ob_delete_frame(points_frame, &mut error);
-                            check_error(error);
-                            points_created = true;
                         }
-                        ob::ob_delete_frame(frameset, &mut error);  // Destroy frameSet to reclaim memory
-                        check_error(error);
-                        if points_created {
+                        Err(err) => {
+                            error!(target: LOG_TARGET, "{err}");
+                            let _ = tx_error.send(err);
                             break;
                         }
                     }
+                }
+                channels.set_connection_state(ConnectionState::Disconnected);
+            }));
+        }
+
+        Self {
+            rx,
+            rx_ready: Mutex::new(rx_ready),
+            latest_only,
+            latest: Mutex::new(Vec::new()),
+            frames_captured,
+            frames_dropped,
+            paused,
+            voxel_size,
+            subsample,
+            triggers,
+            rx_color: Mutex::new(rx_color),
+            rx_depth: Mutex::new(rx_depth),
+            rx_ir: Mutex::new(rx_ir),
+            rx_error: Mutex::new(rx_error),
+            rx_camera_param: Mutex::new(rx_camera_param),
+            color_controls,
+            imu,
+            temperature,
+            device,
+            connection,
+            tx_shutdown,
+            tx_reconfigure,
+            jh,
+        }
+    }
+
+    /// Starts capturing with `config`, waits for the first point cloud and stops again, e.g.
+    /// to scan a static scene. With several cameras, whichever delivers first is returned.
+    ///
+    /// Fails with the capture thread's error if it stops, or when no point cloud arrives
+    /// within `timeout`. The device is closed before returning either way.
+    pub fn capture_once(config: OrbbecConfig, timeout: Duration) -> Result<Frame, OrbbecError> {
+        let deadline = Instant::now() + timeout;
+        // Dropped on return, which stops the capture threads and closes the device.
+        let orbbec = Self::with_config(config);
+        loop {
+            if let Some(err) = orbbec.try_get_error() {
+                return Err(err);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(OrbbecError {
+                    function: "capture_once".to_string(),
+                    args: format!("{timeout:?}"),
+                    message: "no point cloud arrived before the timeout".to_string(),
+                    exception_type: ExceptionType::Io,
+                });
+            }
+            let received =
+                lock(&orbbec.rx_ready).recv_timeout(remaining.min(CAPTURE_ONCE_POLL_INTERVAL));
+            match received {
+                Ok(camera) => {
+                    if let Some(frame) = orbbec.take_frame(camera) {
+                        return Ok(frame);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                // Every thread stopped, either with an error picked up above or, e.g. at the
+                // end of a recording, without a frame.
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(orbbec.try_get_error().unwrap_or_else(|| OrbbecError {
+                        function: "capture_once".to_string(),
+                        args: String::new(),
+                        message: "capture stopped before a point cloud arrived".to_string(),
+                        exception_type: ExceptionType::Io,
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Blocks until the next point cloud is available, returning `None` once every capture
+    /// thread has stopped.
+    pub fn get_data(&self) -> Option<Frame> {
+        lock(&self.rx_ready)
+            .iter()
+            .find_map(|camera| self.take_frame(camera))
+    }
+
+    /// Returns the next point cloud if one is ready, without blocking.
+    ///
+    /// Queued frames are returned in order. Up to [`OrbbecConfig::frame_queue`] frames per
+    /// camera are held for the app, and [`OrbbecConfig::drop_policy`] decides what happens to
+    /// frames captured while the queue is full, so memory stays bounded if the app stalls.
+    /// With [`OrbbecConfig::latest_only`] only the newest queued frame of each camera is
+    /// returned and older ones are skipped, so the cloud never lags behind the camera.
+    ///
+    /// Always `None` while the capture is [paused](Self::pause).
+    pub fn try_get_data(&self) -> Option<Frame> {
+        if self.is_paused() {
+            return None;
+        }
+        let rx_ready = lock(&self.rx_ready);
+        if self.latest_only {
+            let mut latest = lock(&self.latest);
+            for frame in rx_ready
+                .try_iter()
+                .filter_map(|camera| self.take_frame(camera))
+            {
+                latest.retain(|latest| latest.camera != frame.camera);
+                latest.push(frame);
+            }
+            latest.pop()
+        } else {
+            rx_ready
+                .try_iter()
+                .find_map(|camera| self.take_frame(camera))
+        }
+    }
+
+    /// Takes the oldest point cloud queued by `camera`, which the capture thread announced
+    /// through [`Self::rx_ready`].
+    fn take_frame(&self, camera: usize) -> Option<Frame> {
+        lock(&self.rx[camera]).try_recv().ok()
+    }
 
-        // Wait for up to 100ms for a frameset in blocking mode.
-        let frameset = ob::ob_pipeline_wait_for_frameset(ob_pipeline, 100, &mut error);
-        check_error(error);
-        if !frameset.is_null() {
-            ob::ob_delete_frame(frameset, &mut error); // Destroy frameSet to reclaim memory
-            check_error(error);
-            break;
+    /// Restarts capture with a new config without spawning new threads, e.g. to change the
+    /// stream profiles, filters or device properties. Changes arriving in quick succession are
+    /// applied once they settle.
+    ///
+    /// Cameras can't be added or removed this way, and [`OrbbecConfig::latest_only`],
+    /// [`OrbbecConfig::frame_queue`] and [`OrbbecConfig::drop_policy`] keep the values they
+    /// were created with. Update the [`OrbbecConfig`] resource too, so the app's view
+    /// of the config matches.
+    pub fn reconfigure(&self, config: OrbbecConfig) {
+        let configs = config.capture_configs();
+        if configs.len() != self.tx_reconfigure.len() {
+            warn!(
+                target: LOG_TARGET,
+                "Reconfiguring from {} to {} cameras isn't supported, recreate OrbbecRx instead",
+                self.tx_reconfigure.len(),
+                configs.len()
+            );
+        }
+        for (tx_reconfigure, config) in self.tx_reconfigure.iter().zip(configs) {
+            // Fails if the thread already exited, e.g. after a device error.
+            let _ = tx_reconfigure.send(config);
         }
     }
 
-    ob::ob_delete_filter(point_cloud, &mut error);
-    check_error(error);
+    /// Stops pulling frames from the device, leaving it running so [`resume`](Self::resume)
+    /// continues without reopening it. The last cloud received stays on screen.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Continues capturing after [`pause`](Self::pause).
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Downsamples the clouds with this voxel size, in millimeters, instead of
+    /// [`OrbbecConfig::voxel_size_mm`], taking effect from the next cloud without restarting
+    /// capture. Zero keeps every point, and `None` goes back to the config's. Not applied to
+    /// clouds received over the network, which the sender already filtered.
+    pub fn set_voxel_size_mm(&self, voxel_size_mm: Option<f32>) {
+        let size = voxel_size_mm.map_or(f32::NAN, |size| size.max(0.0));
+        self.voxel_size.store(size.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Keeps only every `step`th point instead of [`OrbbecConfig::subsample`], taking effect
+    /// from the next cloud without restarting capture, e.g. for an adaptive controller like
+    /// [`OrbbecDecimationPlugin`](crate::OrbbecDecimationPlugin) to tune. `None` goes back to
+    /// the config's. Not applied to clouds received over the network.
+    pub fn set_subsample(&self, step: Option<usize>) {
+        let step = step.map_or(0, |step| step.max(1));
+        self.subsample.store(step, Ordering::Relaxed);
+    }
+
+    /// Triggers a capture on every device in [`SyncMode::SoftwareTriggering`], on the capture
+    /// threads' next iteration. Does nothing for devices in other modes.
+    pub fn trigger_capture(&self) {
+        self.triggers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of point clouds the capture thread has generated so far, including any that
+    /// were dropped before the app received them.
+    pub fn frames_captured(&self) -> u64 {
+        self.frames_captured.load(Ordering::Relaxed)
+    }
+
+    /// The number of frames the devices dropped before the capture threads received them,
+    /// e.g. for lack of USB bandwidth, counted from gaps in [`Frame::index`]. Frames skipped
+    /// while [paused](Self::pause) or dropped for the app, see [`OrbbecConfig::drop_policy`],
+    /// aren't included.
+    pub fn frames_dropped(&self) -> u64 {
+        self.frames_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the latest color image, if a new one has arrived since the last call. Only
+    /// produced when [`OrbbecConfig::capture_color_image`] is set.
+    pub fn try_get_color_image(&self) -> Option<RgbaImage> {
+        lock(&self.rx_color).try_recv().ok()
+    }
+
+    /// Returns the latest colorized depth image, if a new one has arrived since the last call.
+    /// Only produced when [`OrbbecConfig::capture_depth_image`] is set.
+    pub fn try_get_depth_image(&self) -> Option<RgbaImage> {
+        lock(&self.rx_depth).try_recv().ok()
+    }
+
+    /// Returns the latest IR image, if a new one has arrived since the last call. Only produced
+    /// when [`OrbbecConfig::capture_ir_image`] is set.
+    pub fn try_get_ir_image(&self) -> Option<RgbaImage> {
+        lock(&self.rx_ir).try_recv().ok()
+    }
+
+    /// Returns the camera parameters of the open streams once the pipeline has started, see
+    /// [`OrbbecCameraParam`].
+    pub fn try_get_camera_param(&self) -> Option<OrbbecCameraParam> {
+        lock(&self.rx_camera_param).try_recv().ok()
+    }
+
+    /// The color sensor's exposure settings when capture started, see
+    /// [`OrbbecConfig::color_exposure`].
+    pub fn color_controls(&self) -> ColorControls {
+        *lock(&self.color_controls)
+    }
+
+    /// The color exposure when capture started, or `None` if unknown.
+    pub fn color_exposure(&self) -> Option<i32> {
+        self.color_controls().exposure
+    }
+
+    /// The color gain when capture started, or `None` if unknown.
+    pub fn color_gain(&self) -> Option<i32> {
+        self.color_controls().gain
+    }
+
+    /// Returns the latest IMU sample, or `None` until one has arrived. Only produced when
+    /// [`OrbbecConfig::enable_imu`] is set.
+    pub fn imu(&self) -> Option<OrbbecImu> {
+        *lock(&self.imu)
+    }
+
+    /// The latest temperature reading, if the device reports its temperature.
+    pub fn temperature(&self) -> Option<OrbbecTemperature> {
+        *lock(&self.temperature)
+    }
+
+    /// The first camera's device and stream profiles, once its streams have started. `None`
+    /// when replaying a file or receiving over the network.
+    pub fn device(&self) -> Option<OpenedDevice> {
+        lock(&self.device).clone()
+    }
 
-    // stop pipeline
-    ob::ob_pipeline_stop(ob_pipeline, &mut error);
-    check_error(error);
+    /// Whether the devices or network source are connected, see [`OrbbecConfig::connect_to`].
+    /// With several cameras, the worst state of those whose capture thread is still running,
+    /// so one camera's `NoFrames` or `Reconnecting` isn't hidden by another's `Connected`.
+    /// `Disconnected` once every thread has stopped.
+    pub fn connection_state(&self) -> ConnectionState {
+        lock(&self.connection)
+            .iter()
+            .copied()
+            .filter(|&state| state != ConnectionState::Disconnected)
+            .max_by_key(|state| match state {
+                ConnectionState::Connected => 0,
+                ConnectionState::NoFrames => 1,
+                ConnectionState::Reconnecting => 2,
+                ConnectionState::Disconnected => 3,
+            })
+            .unwrap_or_default()
+    }
 
-    // destroy pipeline
-    ob::ob_delete_pipeline(ob_pipeline, &mut error);
-    check_error(error);
+    /// Returns the error that stopped the capture thread, if any, including a panic of the
+    /// thread, see [`OrbbecError::function`].
+    pub fn try_get_error(&self) -> Option<OrbbecError> {
+        lock(&self.rx_error).try_recv().ok()
+    }
+}
 
-    // destroy config
-    ob::ob_delete_config(ob_config, &mut error);
-    check_error(error);
+impl Drop for OrbbecRx {
+    fn drop(&mut self) {
+        // The send fails if the thread already exited, e.g. after a device error, in which
+        // case there is nothing to stop.
+        for tx_shutdown in &self.tx_shutdown {
+            let _ = tx_shutdown.send(());
+        }
+        for jh in self.jh.drain(..) {
+            if jh.join().is_err() {
+                error!(target: LOG_TARGET, "Orbbec capture thread panicked");
+            }
+        }
+    }
+}
 
-    // destroy profile
-    ob::ob_delete_stream_profile(depth_profile, &mut error);
-    check_error(error);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // destroy profile
-    ob::ob_delete_stream_profile(color_profile, &mut error);
-    check_error(error);
+    #[test]
+    fn color_point_is_linear_rgba() {
+        let point = ColorPoint {
+            pos: Vec3::new(1.0, 2.0, 3.0),
+            color: [255, 128, 0],
+        };
+        let linear = point.color().to_linear();
+        assert_eq!(linear, LinearRgba::from(Srgba::rgb_u8(255, 128, 0)));
+        assert_eq!(linear.red, 1.0);
+        assert!((linear.green - 0.2158).abs() < 1e-4);
+        assert_eq!(linear.blue, 0.0);
+        assert_eq!(linear.alpha, 1.0);
+    }
 
-    // destroy profile list
-    ob::ob_delete_stream_profile_list(color_profiles, &mut error);
-    check_error(error);
+    #[cfg(feature = "sdk")]
+    #[test]
+    fn color_point_keeps_sdk_channels() {
+        use crate::render::InstanceData;
 
-    ob::ob_delete_stream_profile_list(depth_profiles, &mut error);
-    check_error(error);
+        let point = ColorPoint::from(ob::OBColorPoint {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+            r: 255.0,
+            g: 128.0,
+            b: 0.0,
+        });
+        assert_eq!(point.pos, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(point.color, [255, 128, 0]);
+        assert_eq!(
+            point.color().to_linear(),
+            LinearRgba::from(Srgba::rgb_u8(255, 128, 0))
+        );
+        assert_eq!(InstanceData::pack_color(point.color()), [255, 128, 0, 255]);
+    }
 }
@@ -0,0 +1,440 @@
+//! A fluent way to put an [`OrbbecConfig`] together, checking that its settings make sense
+//! together before capture starts rather than failing on the capture thread.
+
+use super::{AlignMode, CameraSerial, ColorMode, OrbbecConfig, OrbbecError, RenderStyle};
+use super::{DeviceSource, DropPolicy, HoleFilling, LogSeverity, SpatialFilter, StreamProfileSpec};
+use super::{SensorType, SyncMode, TemporalFilter};
+use crate::render::CropBox;
+use bevy::{math::Vec3, transform::components::Transform};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Duration;
+
+impl OrbbecConfig {
+    /// Starts building a config from the defaults, see [`OrbbecConfigBuilder`].
+    pub fn builder() -> OrbbecConfigBuilder {
+        OrbbecConfigBuilder::default()
+    }
+}
+
+/// Builds an [`OrbbecConfig`], validated by [`Self::build`]. Settings without a method here
+/// can still be changed on the built config.
+///
+/// ```ignore
+/// let config = OrbbecConfig::builder()
+///     .serial("CP1234567890")
+///     .depth_range(300.0..3000.0)
+///     .align(AlignMode::HardwareOnly)
+///     .mirror_depth(true)
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OrbbecConfigBuilder {
+    config: OrbbecConfig,
+}
+
+impl OrbbecConfigBuilder {
+    /// Opens the USB device with this serial number, see [`OrbbecConfig::device`].
+    pub fn serial(mut self, serial: impl Into<String>) -> Self {
+        self.config.device = DeviceSource::Usb {
+            serial: Some(serial.into()),
+        };
+        self
+    }
+
+    /// Opens the network device at `ip` and `port`, see [`OrbbecConfig::device`].
+    pub fn net_device(mut self, ip: impl Into<String>, port: u16) -> Self {
+        self.config.device = DeviceSource::Net {
+            ip: ip.into(),
+            port,
+        };
+        self
+    }
+
+    /// Adds a camera to capture from, see [`OrbbecConfig::serials`].
+    pub fn camera(mut self, camera: CameraSerial) -> Self {
+        self.config.serials.push(camera);
+        self
+    }
+
+    /// See [`OrbbecConfig::separate_clouds`].
+    pub fn separate_clouds(mut self, separate_clouds: bool) -> Self {
+        self.config.separate_clouds = separate_clouds;
+        self
+    }
+
+    /// See [`OrbbecConfig::latest_only`].
+    pub fn latest_only(mut self, latest_only: bool) -> Self {
+        self.config.latest_only = latest_only;
+        self
+    }
+
+    /// Sets [`OrbbecConfig::frame_queue`] and [`OrbbecConfig::drop_policy`].
+    pub fn frame_queue(mut self, frame_queue: usize, drop_policy: DropPolicy) -> Self {
+        self.config.frame_queue = frame_queue;
+        self.config.drop_policy = drop_policy;
+        self
+    }
+
+    /// See [`OrbbecConfig::render_style`].
+    pub fn render_style(mut self, render_style: RenderStyle) -> Self {
+        self.config.render_style = render_style;
+        self
+    }
+
+    /// See [`OrbbecConfig::world_scale`].
+    pub fn world_scale(mut self, world_scale: f32) -> Self {
+        self.config.world_scale = world_scale;
+        self
+    }
+
+    /// See [`OrbbecConfig::extrinsic`].
+    pub fn extrinsic(mut self, extrinsic: Transform) -> Self {
+        self.config.extrinsic = extrinsic;
+        self
+    }
+
+    /// Captures the color, depth and IR images, see [`OrbbecConfig::capture_color_image`].
+    pub fn capture_images(mut self, color: bool, depth: bool, ir: bool) -> Self {
+        self.config.capture_color_image = color;
+        self.config.capture_depth_image = depth;
+        self.config.capture_ir_image = ir;
+        self
+    }
+
+    /// See [`OrbbecConfig::enable_imu`].
+    pub fn enable_imu(mut self, enable_imu: bool) -> Self {
+        self.config.enable_imu = enable_imu;
+        self
+    }
+
+    /// See [`OrbbecConfig::level_to_gravity`].
+    pub fn level_to_gravity(mut self, level_to_gravity: bool) -> Self {
+        self.config.level_to_gravity = level_to_gravity;
+        self
+    }
+
+    /// See [`OrbbecConfig::mirror_depth`].
+    pub fn mirror_depth(mut self, mirror_depth: bool) -> Self {
+        self.config.mirror_depth = mirror_depth;
+        self
+    }
+
+    /// See [`OrbbecConfig::mirror_color`].
+    pub fn mirror_color(mut self, mirror_color: bool) -> Self {
+        self.config.mirror_color = mirror_color;
+        self
+    }
+
+    /// Sets the manual [`OrbbecConfig::color_exposure`] and [`OrbbecConfig::color_gain`].
+    pub fn color_exposure(mut self, exposure: i32, gain: i32) -> Self {
+        self.config.color_exposure = Some(exposure);
+        self.config.color_gain = Some(gain);
+        self
+    }
+
+    /// Sets [`OrbbecConfig::laser_enabled`] and [`OrbbecConfig::laser_power`].
+    pub fn laser(mut self, enabled: bool, power: Option<i32>) -> Self {
+        self.config.laser_enabled = enabled;
+        self.config.laser_power = power;
+        self
+    }
+
+    /// See [`OrbbecConfig::record_to`].
+    pub fn record_to(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.record_to = Some(path.into());
+        self
+    }
+
+    /// See [`OrbbecConfig::playback_from`].
+    pub fn playback_from(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.playback_from = Some(path.into());
+        self
+    }
+
+    /// See [`OrbbecConfig::load_from`].
+    pub fn load_from(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.load_from = Some(path.into());
+        self
+    }
+
+    /// See [`OrbbecConfig::playback_loop`].
+    pub fn playback_loop(mut self, playback_loop: bool) -> Self {
+        self.config.playback_loop = playback_loop;
+        self
+    }
+
+    /// See [`OrbbecConfig::connect_to`].
+    pub fn connect_to(mut self, url: impl Into<String>) -> Self {
+        self.config.connect_to = Some(url.into());
+        self
+    }
+
+    /// See [`OrbbecConfig::reconnect`].
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.config.reconnect = reconnect;
+        self
+    }
+
+    /// Sets [`OrbbecConfig::no_frames_timeout`] and
+    /// [`OrbbecConfig::restart_on_no_frames`].
+    pub fn no_frames_timeout(mut self, timeout: Option<Duration>, restart: bool) -> Self {
+        self.config.no_frames_timeout = timeout;
+        self.config.restart_on_no_frames = restart;
+        self
+    }
+
+    /// See [`OrbbecConfig::log_severity`].
+    pub fn log_severity(mut self, log_severity: LogSeverity) -> Self {
+        self.config.log_severity = log_severity;
+        self
+    }
+
+    /// See [`OrbbecConfig::sync_mode`].
+    pub fn sync_mode(mut self, sync_mode: SyncMode) -> Self {
+        self.config.sync_mode = Some(sync_mode);
+        self
+    }
+
+    /// See [`OrbbecConfig::color_profile`].
+    pub fn color_profile(mut self, profile: StreamProfileSpec) -> Self {
+        self.config.color_profile = profile;
+        self
+    }
+
+    /// See [`OrbbecConfig::depth_profile`].
+    pub fn depth_profile(mut self, profile: StreamProfileSpec) -> Self {
+        self.config.depth_profile = profile;
+        self
+    }
+
+    /// See [`OrbbecConfig::align_mode`].
+    pub fn align(mut self, align_mode: AlignMode) -> Self {
+        self.config.align_mode = align_mode;
+        self
+    }
+
+    /// Adds `sensor` to [`OrbbecConfig::disabled_sensors`].
+    pub fn disable_sensor(mut self, sensor: SensorType) -> Self {
+        self.config.disabled_sensors.push(sensor);
+        self
+    }
+
+    /// See [`OrbbecConfig::color_mode`].
+    pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
+        self.config.color_mode = color_mode;
+        self
+    }
+
+    /// See [`OrbbecConfig::spatial_filter`].
+    pub fn spatial_filter(mut self, spatial_filter: SpatialFilter) -> Self {
+        self.config.spatial_filter = Some(spatial_filter);
+        self
+    }
+
+    /// See [`OrbbecConfig::temporal_filter`].
+    pub fn temporal_filter(mut self, temporal_filter: TemporalFilter) -> Self {
+        self.config.temporal_filter = Some(temporal_filter);
+        self
+    }
+
+    /// See [`OrbbecConfig::hole_filling`].
+    pub fn hole_filling(mut self, hole_filling: HoleFilling) -> Self {
+        self.config.hole_filling = Some(hole_filling);
+        self
+    }
+
+    /// Keeps the points between these depths, in millimeters, see
+    /// [`OrbbecConfig::depth_min_mm`].
+    pub fn depth_range(mut self, range_mm: Range<f32>) -> Self {
+        self.config.depth_min_mm = Some(range_mm.start);
+        self.config.depth_max_mm = Some(range_mm.end);
+        self
+    }
+
+    /// Enables [`OrbbecConfig::capture_crop`] with a box from `min` to `max`.
+    pub fn capture_crop(mut self, min: Vec3, max: Vec3) -> Self {
+        self.config.capture_crop = CropBox {
+            min,
+            max,
+            enabled: true,
+        };
+        self
+    }
+
+    /// See [`OrbbecConfig::subsample`].
+    pub fn subsample(mut self, step: usize) -> Self {
+        self.config.subsample = step;
+        self
+    }
+
+    /// See [`OrbbecConfig::voxel_size_mm`].
+    pub fn voxel_size_mm(mut self, voxel_size_mm: f32) -> Self {
+        self.config.voxel_size_mm = Some(voxel_size_mm);
+        self
+    }
+
+    /// See [`OrbbecConfig::normal_radius_mm`].
+    pub fn normal_radius_mm(mut self, normal_radius_mm: f32) -> Self {
+        self.config.normal_radius_mm = Some(normal_radius_mm);
+        self
+    }
+
+    /// See [`OrbbecConfig::min_confidence`].
+    pub fn min_confidence(mut self, min_confidence: f32) -> Self {
+        self.config.min_confidence = min_confidence;
+        self
+    }
+
+    /// Checks the settings and returns the config, or an error describing the first
+    /// combination that can't work.
+    pub fn build(self) -> Result<OrbbecConfig, OrbbecError> {
+        let config = self.config;
+        let invalid = |args: String, message: &str| {
+            Err(OrbbecError::invalid_value(
+                "OrbbecConfigBuilder::build",
+                args,
+                message,
+            ))
+        };
+
+        let sources = [
+            config.playback_from.is_some(),
+            config.load_from.is_some(),
+            config.connect_to.is_some(),
+        ];
+        let other_source = sources.contains(&true);
+        if sources.iter().filter(|&&source| source).count() > 1 {
+            return invalid(
+                format!(
+                    "playback_from: {:?}, load_from: {:?}, connect_to: {:?}",
+                    config.playback_from, config.load_from, config.connect_to
+                ),
+                "only one of playback_from, load_from and connect_to can be set",
+            );
+        }
+        if other_source && config.record_to.is_some() {
+            return invalid(
+                format!("record_to: {:?}", config.record_to),
+                "only a device can be recorded, not a file or a remote sink",
+            );
+        }
+        if config.device != DeviceSource::default() && !config.serials.is_empty() {
+            return invalid(
+                format!("device: {}", config.device),
+                "set either a single device or several cameras, not both",
+            );
+        }
+        // Files and remote sinks carry points only, so there is no color stream to align to.
+        let forced_align = matches!(
+            config.align_mode,
+            AlignMode::HardwareOnly | AlignMode::SoftwareOnly
+        );
+        if forced_align && (config.load_from.is_some() || config.connect_to.is_some()) {
+            return invalid(
+                format!("{:?}", config.align_mode),
+                "alignment was forced but the source has no color stream to align to",
+            );
+        }
+        let disabled = |sensor| !config.sensor_enabled(sensor);
+        if disabled(SensorType::Depth) {
+            return invalid(
+                format!("disabled_sensors: {:?}", config.disabled_sensors),
+                "the depth sensor can't be disabled, the point cloud is made from it",
+            );
+        }
+        if forced_align && disabled(SensorType::Color) {
+            return invalid(
+                format!("{:?}", config.align_mode),
+                "alignment was forced but the color sensor is disabled",
+            );
+        }
+        if config.capture_ir_image && disabled(SensorType::Ir) {
+            return invalid(
+                "capture_ir_image: true".to_string(),
+                "capturing the IR image needs the IR sensor, which is disabled",
+            );
+        }
+        if config.enable_imu && disabled(SensorType::Accel) && disabled(SensorType::Gyro) {
+            return invalid(
+                "enable_imu: true".to_string(),
+                "the IMU is enabled but both its accelerometer and gyroscope are disabled",
+            );
+        }
+        if config.level_to_gravity && !config.enable_imu {
+            return invalid(
+                "level_to_gravity: true".to_string(),
+                "leveling to gravity needs the IMU, see enable_imu",
+            );
+        }
+        if let (Some(min), Some(max)) = (config.depth_min_mm, config.depth_max_mm) {
+            if min > max {
+                return invalid(
+                    format!("{min}..{max}"),
+                    "the depth range ends before it starts",
+                );
+            }
+        }
+        if let Some(spatial) = config.spatial_filter {
+            if !(0.0..=1.0).contains(&spatial.alpha) || !(1..=5).contains(&spatial.magnitude) {
+                return invalid(
+                    format!("spatial_filter: {spatial:?}"),
+                    "the spatial filter's alpha must be between 0 and 1, its magnitude 1 to 5",
+                );
+            }
+        }
+        if let Some(temporal) = config.temporal_filter {
+            if !(0.1..=0.9).contains(&temporal.alpha) || !(0.1..=0.5).contains(&temporal.delta) {
+                return invalid(
+                    format!("temporal_filter: {temporal:?}"),
+                    "the temporal filter's alpha must be between 0.1 and 0.9, its delta 0.1 to 0.5",
+                );
+            }
+        }
+        let crop = config.capture_crop;
+        if crop.enabled && crop.min.cmpgt(crop.max).any() {
+            return invalid(
+                format!("capture_crop: {:?}..{:?}", crop.min, crop.max),
+                "the capture crop box ends before it starts",
+            );
+        }
+        if config.world_scale <= 0.0 {
+            return invalid(
+                format!("world_scale: {}", config.world_scale),
+                "the world scale must be positive",
+            );
+        }
+        if config.voxel_size_mm.is_some_and(|size| size <= 0.0) {
+            return invalid(
+                format!("voxel_size_mm: {:?}", config.voxel_size_mm),
+                "the voxel size must be positive",
+            );
+        }
+        if config.normal_radius_mm.is_some_and(|radius| radius <= 0.0) {
+            return invalid(
+                format!("normal_radius_mm: {:?}", config.normal_radius_mm),
+                "the normal radius must be positive",
+            );
+        }
+        if config.subsample == 0 {
+            return invalid(
+                "subsample: 0".to_string(),
+                "the subsampling step must be at least 1",
+            );
+        }
+        if config.frame_queue == 0 {
+            return invalid(
+                "frame_queue: 0".to_string(),
+                "the frame queue must hold at least one point cloud",
+            );
+        }
+        if !(0.0..=1.0).contains(&config.min_confidence) {
+            return invalid(
+                format!("min_confidence: {}", config.min_confidence),
+                "the minimum confidence must be between 0 and 1",
+            );
+        }
+
+        Ok(config)
+    }
+}
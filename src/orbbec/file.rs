@@ -0,0 +1,83 @@
+//! A point cloud source that replays the points of a PLY or PCD file, see
+//! [`OrbbecConfig::load_from`].
+
+use super::{Frame, OrbbecConfig, OrbbecError, PointPayload, WorkerChannels, LOG_TARGET};
+use crate::export::load_points;
+use bevy::prelude::*;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Roughly the frame rate of a real device.
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Sends the file's points as a frame every [`FRAME_INTERVAL`], or just once unless
+/// [`OrbbecConfig::playback_loop`] is set, until shut down or reconfigured.
+pub(super) fn run(
+    config: OrbbecConfig,
+    path: &Path,
+    channels: &WorkerChannels,
+) -> Result<Option<OrbbecConfig>, OrbbecError> {
+    let mut points = load_points(path).map_err(|err| {
+        OrbbecError::invalid_value("load_points", path.display().to_string(), err.to_string())
+    })?;
+    info!(target: LOG_TARGET, "Loaded {} points from {}", points.len(), path.display());
+
+    // Files are saved the way up they are rendered, so undo the flip applied to the SDK's
+    // points.
+    if config.flip_y {
+        match &mut points {
+            PointPayload::Color(points) => points.iter_mut().for_each(|p| p.pos.y = -p.pos.y),
+            PointPayload::Depth(points) => points.iter_mut().for_each(|p| p.y = -p.y),
+        }
+    }
+    points.retain_depth(|z| config.in_depth_range(z));
+    if config.capture_crop.enabled {
+        points.retain_positions(|p| config.in_capture_crop(channels.camera, p));
+    }
+    // Downsampled again whenever the subsampling or voxel size changes, see
+    // `OrbbecRx::set_subsample` and `OrbbecRx::set_voxel_size_mm`.
+    let mut downsampling = (1, None);
+    let mut downsampled = points.clone();
+
+    let start = Instant::now();
+    let mut sent = false;
+    loop {
+        if let Ok(config) = channels.rx_reconfigure.try_recv() {
+            return Ok(Some(config));
+        }
+
+        if !channels.paused.load(Ordering::Relaxed) && (config.playback_loop || !sent) {
+            let wanted = (channels.subsample(&config), channels.voxel_size_mm(&config));
+            if wanted != downsampling {
+                let (step, voxel_size_mm) = wanted;
+                downsampled = points.subsample(step);
+                if let Some(size) = voxel_size_mm {
+                    downsampled = downsampled.voxel_downsample(size);
+                }
+                downsampling = wanted;
+            }
+            let frame = Frame {
+                camera: channels.camera,
+                points: downsampled.clone(),
+                confidence: None,
+                index: None,
+                device_timestamp_us: start.elapsed().as_micros() as u64,
+                system_time: SystemTime::now(),
+            };
+            if !channels.send(frame) {
+                break;
+            }
+            sent = true;
+        }
+
+        // Wait out the frame interval, waking immediately on shutdown.
+        match channels.rx_shutdown.recv_timeout(FRAME_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,95 @@
+//! A synthetic point cloud source that stands in for the device when the `mock` feature is
+//! enabled, so the rest of the crate can be developed and tested without a camera.
+
+use super::{ColorPoint, Frame, OrbbecConfig, OrbbecError, PointPayload, WorkerChannels};
+use bevy::math::Vec3;
+use std::f32::consts::{PI, TAU};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Roughly the frame rate of a real device.
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+/// Sphere radius, in millimeters like the SDK's points.
+const RADIUS: f32 = 300.0;
+const RINGS: usize = 64;
+const SEGMENTS: usize = 128;
+
+/// Emits a rotating, rainbow colored sphere until shut down or reconfigured.
+pub(super) fn run(
+    _config: OrbbecConfig,
+    channels: &WorkerChannels,
+) -> Result<Option<OrbbecConfig>, OrbbecError> {
+    let start = Instant::now();
+    let mut index = 0;
+    loop {
+        if let Ok(config) = channels.rx_reconfigure.try_recv() {
+            return Ok(Some(config));
+        }
+
+        if !channels.paused.load(Ordering::Relaxed) {
+            let elapsed = start.elapsed();
+            let points = sphere(elapsed.as_secs_f32());
+            // Like a real sensor, surfaces seen at a grazing angle are the least certain.
+            let confidence = points
+                .iter()
+                .map(|point| ((point.pos.z / RADIUS).abs() * 255.0) as u8)
+                .collect();
+            let frame = Frame {
+                camera: channels.camera,
+                points: PointPayload::Color(points),
+                confidence: Some(confidence),
+                device_timestamp_us: elapsed.as_micros() as u64,
+                index: Some(index),
+                system_time: SystemTime::now(),
+            };
+            index += 1;
+            if !channels.send(frame) {
+                break;
+            }
+        }
+
+        // Wait out the frame interval, waking immediately on shutdown.
+        match channels.rx_shutdown.recv_timeout(FRAME_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    Ok(None)
+}
+
+fn sphere(time: f32) -> Vec<ColorPoint> {
+    let mut points = Vec::with_capacity(RINGS * SEGMENTS);
+    for ring in 0..RINGS {
+        let polar = PI * (ring as f32 + 0.5) / RINGS as f32;
+        for segment in 0..SEGMENTS {
+            let azimuth = TAU * segment as f32 / SEGMENTS as f32;
+            let (r, g, b) = hue_to_rgb(azimuth / TAU);
+            let azimuth = azimuth + time;
+            points.push(ColorPoint {
+                pos: Vec3::new(
+                    RADIUS * polar.sin() * azimuth.cos(),
+                    RADIUS * polar.cos(),
+                    RADIUS * polar.sin() * azimuth.sin(),
+                ),
+                color: [r, g, b].map(|c| (c * 255.0) as u8),
+            });
+        }
+    }
+    points
+}
+
+/// Fully saturated color for a hue in `0.0..1.0`.
+fn hue_to_rgb(hue: f32) -> (f32, f32, f32) {
+    let h = hue * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    }
+}
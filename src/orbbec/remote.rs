@@ -0,0 +1,117 @@
+//! A point cloud source that receives frames from a
+//! [`WebSocketSink`](crate::net::WebSocketSink), see [`OrbbecConfig::connect_to`].
+
+use super::{ConnectionState, OrbbecConfig, OrbbecError, WorkerChannels, LOG_TARGET};
+use crate::net::decode_frame;
+use bevy::prelude::*;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+use std::time::Duration;
+use tungstenite::{http::Uri, Message, WebSocket};
+
+/// How long connecting, including the WebSocket handshake, may take before it is retried.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long to wait between attempts to connect.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a read blocks before checking for shutdown again.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Why [`receive`] returned.
+enum Stopped {
+    /// The thread was shut down, or reconfigured with the given config.
+    Requested(Option<OrbbecConfig>),
+    Disconnected(tungstenite::Error),
+}
+
+/// Receives frames from `url` until shut down or reconfigured, reconnecting whenever the
+/// connection can't be made or drops.
+pub(super) fn run(
+    _config: OrbbecConfig,
+    url: &str,
+    channels: &WorkerChannels,
+) -> Result<Option<OrbbecConfig>, OrbbecError> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|err| OrbbecError::invalid_value("connect_to", url, format!("{err}")))?;
+    let Some(host) = uri.host() else {
+        return Err(OrbbecError::invalid_value(
+            "connect_to",
+            url,
+            "URL has no host",
+        ));
+    };
+    let addr = (host, uri.port_u16().unwrap_or(80));
+
+    loop {
+        match connect(url, addr) {
+            Ok(mut socket) => {
+                info!(target: LOG_TARGET, "Connected to {url}");
+                channels.set_connection_state(ConnectionState::Connected);
+                let stopped = receive(&mut socket, channels);
+                channels.set_connection_state(ConnectionState::Disconnected);
+                match stopped {
+                    Stopped::Requested(reconfigure) => return Ok(reconfigure),
+                    Stopped::Disconnected(err) => {
+                        warn!(target: LOG_TARGET, "Lost connection to {url}: {err}")
+                    }
+                }
+            }
+            Err(err) => debug!(target: LOG_TARGET, "Failed to connect to {url}: {err}"),
+        }
+
+        // Wait before reconnecting, waking immediately on shutdown.
+        if let Ok(config) = channels.rx_reconfigure.try_recv() {
+            return Ok(Some(config));
+        }
+        match channels.rx_shutdown.recv_timeout(RECONNECT_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(None),
+        }
+    }
+}
+
+fn connect(url: &str, addr: (&str, u16)) -> Result<WebSocket<TcpStream>, Box<dyn Error>> {
+    let addr = addr.to_socket_addrs()?.next().ok_or("Host not found")?;
+    let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    let (socket, _) = tungstenite::client(url, stream).map_err(|err| err.to_string())?;
+    socket.get_ref().set_read_timeout(Some(READ_TIMEOUT))?;
+    Ok(socket)
+}
+
+/// Forwards the frames received on `socket` until the connection drops or the thread is asked
+/// to stop.
+fn receive(socket: &mut WebSocket<TcpStream>, channels: &WorkerChannels) -> Stopped {
+    loop {
+        // Stop when asked to, or when the handle has gone away without asking.
+        if !matches!(channels.rx_shutdown.try_recv(), Err(TryRecvError::Empty)) {
+            return Stopped::Requested(None);
+        }
+        if let Ok(config) = channels.rx_reconfigure.try_recv() {
+            return Stopped::Requested(Some(config));
+        }
+
+        match socket.read() {
+            // Frames keep being read while paused, so the connection stays alive.
+            Ok(Message::Binary(_)) if channels.paused.load(Ordering::Relaxed) => {}
+            Ok(Message::Binary(bytes)) => match decode_frame(&bytes) {
+                Some(frame) => {
+                    if !channels.send(frame) {
+                        return Stopped::Requested(None);
+                    }
+                }
+                None => {
+                    warn!(target: LOG_TARGET, "Ignoring malformed frame of {} bytes", bytes.len())
+                }
+            },
+            // Pings are answered by tungstenite itself.
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err))
+                if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(err) => return Stopped::Disconnected(err),
+        }
+    }
+}
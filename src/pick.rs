@@ -0,0 +1,80 @@
+//! Picking individual points of the cloud with the mouse.
+
+use crate::render::InstanceMaterialData;
+use crate::trail::TrailFrame;
+use bevy::{prelude::*, window::PrimaryWindow};
+
+/// How far from the cursor, in logical pixels, a point can be and still be picked.
+const PICK_RADIUS_PX: f32 = 8.0;
+
+/// Sent when a point of the cloud is clicked.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PointPicked {
+    /// The cloud entity the point belongs to, one per camera with
+    /// [`OrbbecConfig::separate_clouds`](crate::OrbbecConfig::separate_clouds).
+    pub entity: Entity,
+    /// Index of the point in [`Self::entity`]'s [`InstanceMaterialData`].
+    pub index: usize,
+    /// Position of the point in world space.
+    pub world_pos: Vec3,
+}
+
+/// Sends a [`PointPicked`] event when the left mouse button is clicked over a point.
+pub struct OrbbecPickPlugin;
+
+impl Plugin for OrbbecPickPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PointPicked>()
+            .add_systems(Update, pick_point);
+    }
+}
+
+fn pick_point(
+    buttons: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    clouds: Query<(Entity, &InstanceMaterialData, &GlobalTransform), Without<TrailFrame>>,
+    mut picked: EventWriter<PointPicked>,
+) {
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(cursor) = windows.get_single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+    let Some((camera, camera_transform)) = cameras.iter().find(|(camera, _)| camera.is_active)
+    else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+
+    // Every point within the radius counts as under the cursor, the one nearest to the
+    // camera wins as the others are likely hidden behind it.
+    let mut nearest: Option<(f32, PointPicked)> = None;
+    for (entity, cloud, cloud_transform) in &clouds {
+        for (index, instance) in cloud.iter().enumerate() {
+            let world_pos = cloud_transform.transform_point(instance.position);
+            let distance = (world_pos - ray.origin).dot(*ray.direction);
+            if distance <= 0.0 || nearest.is_some_and(|(nearest, _)| distance >= nearest) {
+                continue;
+            }
+            let Some(screen_pos) = camera.world_to_viewport(camera_transform, world_pos) else {
+                continue;
+            };
+            if screen_pos.distance(cursor) <= PICK_RADIUS_PX {
+                let point = PointPicked {
+                    entity,
+                    index,
+                    world_pos,
+                };
+                nearest = Some((distance, point));
+            }
+        }
+    }
+
+    if let Some((_, point)) = nearest {
+        picked.send(point);
+    }
+}
@@ -0,0 +1,1080 @@
+//! A shader that renders a mesh multiple times in one draw call.
+
+use crate::bounds::OrbbecBounds;
+use crate::ground::{GroundInliers, GroundPlane, GroundPlaneSettings};
+use crate::orbbec::{BlendMode, OrbbecConfig, RenderStyle, SizeAttenuation, TranslucentDepth};
+use bevy::{
+    asset::embedded_asset,
+    core_pipeline::core_3d::{Opaque3d, Opaque3dBinKey, Transparent3d},
+    ecs::{
+        entity::{EntityHashMap, EntityHashSet},
+        query::QueryItem,
+        system::{lifetimeless::*, SystemParamItem},
+    },
+    pbr::{
+        MeshPipeline, MeshPipelineKey, RenderMeshInstances, SetMeshBindGroup, SetMeshViewBindGroup,
+    },
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        mesh::{GpuBufferInfo, GpuMesh, MeshVertexBufferLayoutRef},
+        render_asset::{RenderAssetUsages, RenderAssets},
+        render_phase::{
+            AddRenderCommand, BinnedRenderPhase, BinnedRenderPhaseType, DrawFunctions, PhaseItem,
+            PhaseItemExtraIndex, RenderCommand, RenderCommandResult, SetItemPipeline,
+            SortedRenderPhase, TrackedRenderPass,
+        },
+        render_resource::{binding_types::uniform_buffer, *},
+        renderer::{RenderDevice, RenderQueue},
+        view::ExtractedView,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    transform::TransformSystem,
+};
+use bytemuck::{Pod, Zeroable};
+use std::sync::Arc;
+
+/// The mesh drawn once per point.
+pub(crate) fn point_mesh(style: RenderStyle) -> Mesh {
+    match style {
+        RenderStyle::Cubes => Cuboid::new(0.5, 0.5, 0.5).into(),
+        // A single vertex at the origin, offset to each point by the instance data.
+        RenderStyle::Points => Mesh::new(
+            PrimitiveTopology::PointList,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0f32, 0.0, 0.0]]),
+        // A unit quad, turned to face the camera and scaled in the billboard shader.
+        RenderStyle::Billboard { .. } => Rectangle::new(1.0, 1.0).into(),
+    }
+}
+
+/// Per-instance data for a point cloud. Shared behind an [`Arc`] so extracting it into the
+/// render world every frame doesn't copy every point.
+#[derive(Component, Deref)]
+pub struct InstanceMaterialData(pub Arc<Vec<InstanceData>>);
+
+impl ExtractComponent for InstanceMaterialData {
+    type QueryData = &'static InstanceMaterialData;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        Some(InstanceMaterialData(item.0.clone()))
+    }
+}
+
+/// Renders every entity with [`InstanceMaterialData`], drawing its mesh once per instance.
+pub struct CustomMaterialPlugin;
+
+/// Draws a cloud in the transparent phase, blending by the alpha of its instance colors and
+/// sorting it with other transparent entities. Without it clouds are drawn opaque, writing
+/// depth and ignoring alpha, which is cheaper and occludes correctly. How the points of a
+/// translucent cloud occlude each other is set by [`OrbbecConfig::translucent_depth`].
+/// Every cloud is blended with [`BlendMode::Additive`], with or without it.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct TranslucentPoints;
+
+/// Multiplies the alpha of every point of a cloud, e.g. to fade it out. Only visible on
+/// [`TranslucentPoints`] clouds.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PointAlpha(pub f32);
+
+impl Default for PointAlpha {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl ExtractComponent for PointAlpha {
+    type QueryData = &'static PointAlpha;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        Some(*item)
+    }
+}
+
+impl ExtractComponent for TranslucentPoints {
+    type QueryData = ();
+    type QueryFilter = With<TranslucentPoints>;
+    type Out = Self;
+
+    fn extract_component(_item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        Some(TranslucentPoints)
+    }
+}
+
+/// Hides points outside an axis-aligned box in world space. Applied in the shader, so the box
+/// can be moved every frame without re-uploading the cloud, which suits adjusting it
+/// interactively. The hidden points are still sent and uploaded; to trim a fixed region for
+/// good, e.g. the walls around a scene, use [`OrbbecConfig::capture_crop`] instead.
+///
+/// [`OrbbecConfig::capture_crop`]: crate::OrbbecConfig::capture_crop
+#[derive(Resource, ExtractResource, Debug, Clone, Copy)]
+pub struct CropBox {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub enabled: bool,
+}
+
+impl CropBox {
+    /// Whether `point` is kept: inside the box, or anywhere while it is disabled.
+    pub fn contains(&self, point: Vec3) -> bool {
+        !self.enabled || (point.cmpge(self.min).all() && point.cmple(self.max).all())
+    }
+}
+
+impl Default for CropBox {
+    fn default() -> Self {
+        Self {
+            min: Vec3::splat(-1.0),
+            max: Vec3::splat(1.0),
+            enabled: false,
+        }
+    }
+}
+
+/// The [`CropBox`] as laid out in the shader.
+#[derive(ShaderType)]
+struct CropBoxUniform {
+    min: Vec3,
+    max: Vec3,
+    enabled: u32,
+}
+
+/// Uploads point positions quantized to 16 bits per axis within the cloud's [`OrbbecBounds`],
+/// with every point drawn at the first point's scale. This shrinks each instance from 24 to
+/// 16 bytes, at the cost of precision: positions snap to steps of the bounds' size / 65535 on
+/// each axis. A cloud spanning 10m has steps of about 0.15mm, so the error stays well under a
+/// millimeter for any cloud spanning less than about 65m. Off by default.
+#[derive(Resource, ExtractResource, Debug, Clone, Copy, Default)]
+pub struct QuantizePositions(pub bool);
+
+/// Uploads only every nth point of each cloud, picking n from how far the nearest 3D camera
+/// is from the cloud's [`OrbbecBounds`], so a distant cloud doesn't upload points too close
+/// together on screen to tell apart. Unlike [`OrbbecConfig::subsample`] and
+/// [`AdaptiveDecimation`](crate::AdaptiveDecimation) this only thins what is drawn: the
+/// cloud keeps every point for picking, export and the other consumers. Clouds transformed
+/// on the GPU, with the `gpu_transform` feature, are always drawn in full. Off by default.
+#[derive(Resource, ExtractResource, Debug, Clone)]
+pub struct PointLod {
+    pub enabled: bool,
+    /// The distance-to-step curve: each level is a distance from the camera to the bounds,
+    /// in world units, and the step to upload with from that distance on. Sorted by
+    /// distance. Closer than the first level, or inside the bounds, every point is uploaded.
+    pub levels: Vec<(f32, usize)>,
+    /// The step picked for the current view.
+    step: usize,
+}
+
+impl Default for PointLod {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            levels: vec![(2.0, 2), (4.0, 4), (8.0, 8)],
+            step: 1,
+        }
+    }
+}
+
+impl PointLod {
+    /// Uploads every `step`th point, 1 uploading every point.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// The step for a camera `distance` away from the bounds.
+    pub fn step_at(&self, distance: f32) -> usize {
+        self.levels
+            .iter()
+            .take_while(|(from, _)| distance >= *from)
+            .last()
+            .map_or(1, |(_, step)| (*step).max(1))
+    }
+}
+
+fn update_point_lod(
+    mut lod: ResMut<PointLod>,
+    bounds: Option<Res<OrbbecBounds>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+) {
+    let distance = bounds.filter(|_| lod.enabled).and_then(|bounds| {
+        let center = Vec3::from(bounds.center);
+        let half_extents = Vec3::from(bounds.half_extents);
+        cameras
+            .iter()
+            .filter(|(camera, _)| camera.is_active)
+            .map(|(_, transform)| {
+                // Zero inside the box, else the distance to its nearest face, edge or corner.
+                let outside = (transform.translation() - center).abs() - half_extents;
+                outside.max(Vec3::ZERO).length()
+            })
+            .min_by(f32::total_cmp)
+    });
+    let step = distance.map_or(1, |distance| lod.step_at(distance));
+    // Only touch the resource when the step changes, so it isn't extracted every frame.
+    if lod.step != step {
+        lod.step = step;
+    }
+}
+
+/// An [`InstanceData`] with its position quantized by [`Quantization`]. The fourth component
+/// only pads the position to a vertex format.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct QuantizedInstance {
+    position: [i16; 4],
+    color: [u8; 4],
+    normal: [i8; 4],
+}
+
+/// Maps positions within the cloud's bounds to the full range of an `i16`.
+struct Quantization {
+    center: Vec3,
+    half_extents: Vec3,
+}
+
+impl Quantization {
+    /// Returns `None` unless [`QuantizePositions`] is enabled and the bounds are known.
+    fn new(quantize: &QuantizePositions, bounds: Option<&OrbbecBounds>) -> Option<Self> {
+        let bounds = bounds.filter(|_| quantize.0)?;
+        Some(Self {
+            center: bounds.center.into(),
+            // Flat clouds would otherwise divide by zero.
+            half_extents: Vec3::from(bounds.half_extents).max(Vec3::splat(f32::EPSILON)),
+        })
+    }
+
+    fn quantize(&self, instance: &InstanceData) -> QuantizedInstance {
+        let normalized = (instance.position - self.center) / self.half_extents;
+        // Float to int casts saturate, so points outside stale bounds are clamped.
+        let [x, y, z] = (normalized * f32::from(i16::MAX))
+            .round()
+            .to_array()
+            .map(|v| v as i16);
+        QuantizedInstance {
+            position: [x, y, z, 0],
+            color: instance.color,
+            normal: instance.normal,
+        }
+    }
+}
+
+/// Undoes [`Quantization`] in the shader.
+#[derive(ShaderType, Default)]
+struct DequantizeUniform {
+    center: Vec3,
+    half_extents: Vec3,
+    instance_scale: f32,
+}
+
+/// [`OrbbecConfig::size_attenuation`], extracted for the billboard shader.
+#[derive(Resource, Clone, Copy, Default)]
+struct ExtractedSizeAttenuation(Option<SizeAttenuation>);
+
+impl ExtractResource for ExtractedSizeAttenuation {
+    type Source = OrbbecConfig;
+
+    fn extract_resource(config: &Self::Source) -> Self {
+        Self(config.size_attenuation)
+    }
+}
+
+/// [`OrbbecConfig::translucent_depth`], extracted to queue translucent clouds.
+#[derive(Resource, Clone, Copy, Default)]
+struct ExtractedTranslucentDepth(TranslucentDepth);
+
+impl ExtractResource for ExtractedTranslucentDepth {
+    type Source = OrbbecConfig;
+
+    fn extract_resource(config: &Self::Source) -> Self {
+        Self(config.translucent_depth)
+    }
+}
+
+/// [`OrbbecConfig::blend_mode`], extracted to queue the clouds.
+#[derive(Resource, Clone, Copy, Default)]
+struct ExtractedBlendMode(BlendMode);
+
+impl ExtractResource for ExtractedBlendMode {
+    type Source = OrbbecConfig;
+
+    fn extract_resource(config: &Self::Source) -> Self {
+        Self(config.blend_mode)
+    }
+}
+
+/// Adds the source color, weighted by its alpha, to the target's, leaving the target's alpha
+/// untouched.
+const ADDITIVE_BLENDING: BlendState = BlendState {
+    color: BlendComponent {
+        src_factor: BlendFactor::SrcAlpha,
+        dst_factor: BlendFactor::One,
+        operation: BlendOperation::Add,
+    },
+    alpha: BlendComponent {
+        src_factor: BlendFactor::Zero,
+        dst_factor: BlendFactor::One,
+        operation: BlendOperation::Add,
+    },
+};
+
+/// The [`SizeAttenuation`] as laid out in the shader.
+#[derive(ShaderType, Default)]
+struct SizeAttenuationUniform {
+    min_size: f32,
+    max_size: f32,
+    enabled: u32,
+}
+
+impl From<ExtractedSizeAttenuation> for SizeAttenuationUniform {
+    fn from(ExtractedSizeAttenuation(attenuation): ExtractedSizeAttenuation) -> Self {
+        attenuation.map_or_else(Self::default, |attenuation| Self {
+            min_size: attenuation.min_size,
+            max_size: attenuation.max_size,
+            enabled: 1,
+        })
+    }
+}
+
+/// Blends points toward [`Self::color`] with their distance from the view, to give depth cues
+/// or fade out clutter in the distance. Points closer than [`Self::start`] keep their color
+/// and points beyond [`Self::end`] take the fog's. Applied per fragment, so it costs nothing
+/// to change. Off by default.
+#[derive(Resource, ExtractResource, Debug, Clone, Copy)]
+pub struct PointFog {
+    pub enabled: bool,
+    /// Where the fog starts, in world units from the view.
+    pub start: f32,
+    /// Where the fog is complete, in world units from the view.
+    pub end: f32,
+    /// Usually the clear color, so distant points fade into the background. Its alpha is
+    /// ignored.
+    pub color: Color,
+}
+
+impl Default for PointFog {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: 2.0,
+            end: 6.0,
+            color: Color::BLACK,
+        }
+    }
+}
+
+/// The [`PointFog`] as laid out in the shader.
+#[derive(ShaderType)]
+struct FogUniform {
+    /// Linear color.
+    color: Vec4,
+    start: f32,
+    end: f32,
+    enabled: u32,
+}
+
+impl From<PointFog> for FogUniform {
+    fn from(fog: PointFog) -> Self {
+        Self {
+            color: Vec4::from_array(LinearRgba::from(fog.color).to_f32_array()),
+            start: fog.start,
+            // Keeps the blend from dividing by zero.
+            end: fog.end.max(fog.start + f32::EPSILON),
+            enabled: fog.enabled.into(),
+        }
+    }
+}
+
+/// The [`GroundPlane`] and what to do with its inliers, as laid out in the shader.
+#[derive(ShaderType, Clone, Copy, Default)]
+struct GroundPlaneUniform {
+    normal: Vec3,
+    offset: f32,
+    /// In world units.
+    threshold: f32,
+    /// `0` for [`GroundInliers::Show`], `1` for [`GroundInliers::Color`] and `2` for
+    /// [`GroundInliers::Hide`].
+    mode: u32,
+    /// Linear color.
+    color: Vec4,
+}
+
+/// [`GroundPlaneUniform`] extracted from the main world, left at its default when there's
+/// no [`GroundPlane`].
+#[derive(Resource, Default)]
+struct ExtractedGroundPlane(GroundPlaneUniform);
+
+fn extract_ground_plane(
+    mut commands: Commands,
+    plane: Extract<Option<Res<GroundPlane>>>,
+    settings: Extract<Option<Res<GroundPlaneSettings>>>,
+    config: Extract<Option<Res<OrbbecConfig>>>,
+) {
+    let (Some(plane), Some(settings), Some(config)) =
+        (plane.as_deref(), settings.as_deref(), config.as_deref())
+    else {
+        commands.insert_resource(ExtractedGroundPlane::default());
+        return;
+    };
+    let (mode, color) = match settings.inliers {
+        GroundInliers::Show => (0, Color::NONE),
+        GroundInliers::Color(color) => (1, color),
+        GroundInliers::Hide => (2, Color::NONE),
+    };
+    commands.insert_resource(ExtractedGroundPlane(GroundPlaneUniform {
+        normal: plane.normal,
+        offset: plane.offset,
+        threshold: settings.inlier_threshold_mm * config.world_scale,
+        mode,
+        color: Vec4::from_array(LinearRgba::from(color).to_f32_array()),
+    }));
+}
+
+impl From<CropBox> for CropBoxUniform {
+    fn from(crop_box: CropBox) -> Self {
+        Self {
+            min: crop_box.min,
+            max: crop_box.max,
+            enabled: crop_box.enabled.into(),
+        }
+    }
+}
+
+impl ExtractComponent for RenderStyle {
+    type QueryData = &'static RenderStyle;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        Some(*item)
+    }
+}
+
+impl Plugin for CustomMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        embedded_asset!(app, "shaders/instancing.wgsl");
+        embedded_asset!(app, "shaders/billboard.wgsl");
+
+        app.init_resource::<CropBox>()
+            .init_resource::<QuantizePositions>()
+            .init_resource::<PointLod>()
+            .init_resource::<PointFog>()
+            .add_systems(
+                PostUpdate,
+                update_point_lod.after(TransformSystem::TransformPropagate),
+            )
+            .add_plugins((
+                ExtractComponentPlugin::<InstanceMaterialData>::default(),
+                ExtractComponentPlugin::<RenderStyle>::default(),
+                ExtractComponentPlugin::<TranslucentPoints>::default(),
+                ExtractComponentPlugin::<PointAlpha>::default(),
+                ExtractResourcePlugin::<CropBox>::default(),
+                ExtractResourcePlugin::<QuantizePositions>::default(),
+                ExtractResourcePlugin::<PointLod>::default(),
+                ExtractResourcePlugin::<PointFog>::default(),
+                ExtractResourcePlugin::<OrbbecBounds>::default(),
+                ExtractResourcePlugin::<ExtractedSizeAttenuation>::default(),
+                ExtractResourcePlugin::<ExtractedTranslucentDepth>::default(),
+                ExtractResourcePlugin::<ExtractedBlendMode>::default(),
+            ));
+        #[cfg(feature = "gpu_transform")]
+        app.add_plugins(crate::gpu_transform::GpuTransformPlugin);
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Opaque3d, DrawCustom>()
+            .add_render_command::<Transparent3d, DrawCustom>()
+            .init_resource::<SpecializedMeshPipelines<CustomPipeline>>()
+            .init_resource::<InstanceBufferCache>()
+            .init_resource::<ExtractedSizeAttenuation>()
+            .init_resource::<ExtractedTranslucentDepth>()
+            .init_resource::<ExtractedBlendMode>()
+            .init_resource::<CloudUniforms>()
+            .init_resource::<ExtractedGroundPlane>()
+            .add_systems(ExtractSchedule, extract_ground_plane)
+            .add_systems(
+                Render,
+                (
+                    queue_custom.in_set(RenderSet::QueueMeshes),
+                    prepare_instance_buffers.in_set(RenderSet::PrepareResources),
+                    prepare_cloud_uniforms.in_set(RenderSet::PrepareBindGroups),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp).init_resource::<CustomPipeline>();
+    }
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct InstanceData {
+    pub position: Vec3,
+    pub scale: f32,
+    /// sRGBA bytes, see [`InstanceData::pack_color`]. Packed as the per-instance data of a
+    /// large cloud is uploaded every frame.
+    pub color: [u8; 4],
+    /// Unit normal as signed bytes, see [`InstanceData::pack_normal`]. Points with a zero
+    /// normal are drawn unlit.
+    pub normal: [i8; 4],
+}
+
+impl InstanceData {
+    /// Packs a color into [`InstanceData::color`].
+    pub fn pack_color(color: Color) -> [u8; 4] {
+        color
+            .to_srgba()
+            .to_f32_array()
+            .map(|c| (c * 255.0).round() as u8)
+    }
+
+    /// The color packed into [`InstanceData::color`].
+    pub fn color(&self) -> Color {
+        let [r, g, b, a] = self.color;
+        Color::srgba_u8(r, g, b, a)
+    }
+
+    /// Packs a unit normal into [`InstanceData::normal`].
+    pub fn pack_normal(normal: Vec3) -> [i8; 4] {
+        let [x, y, z] = (normal * f32::from(i8::MAX))
+            .round()
+            .to_array()
+            .map(|v| v as i8);
+        [x, y, z, 0]
+    }
+
+    /// The normal packed into [`InstanceData::normal`], zero for unlit points.
+    pub fn normal(&self) -> Vec3 {
+        let [x, y, z, _] = self.normal;
+        Vec3::new(x.into(), y.into(), z.into()) / f32::from(i8::MAX)
+    }
+}
+
+/// The entities drawn with [`DrawCustom`].
+#[cfg(not(feature = "gpu_transform"))]
+type CloudFilter = With<InstanceMaterialData>;
+#[cfg(feature = "gpu_transform")]
+type CloudFilter = Or<(
+    With<InstanceMaterialData>,
+    With<crate::gpu_transform::RawPointCloud>,
+)>;
+
+#[allow(clippy::too_many_arguments)]
+fn queue_custom(
+    opaque_3d_draw_functions: Res<DrawFunctions<Opaque3d>>,
+    transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    custom_pipeline: Res<CustomPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<CustomPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<GpuMesh>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    material_meshes: Query<
+        (
+            Entity,
+            &RenderStyle,
+            Has<InstanceMaterialData>,
+            Has<TranslucentPoints>,
+        ),
+        CloudFilter,
+    >,
+    mut views: Query<(
+        &ExtractedView,
+        &mut BinnedRenderPhase<Opaque3d>,
+        &mut SortedRenderPhase<Transparent3d>,
+    )>,
+    quantize: Res<QuantizePositions>,
+    bounds: Option<Res<OrbbecBounds>>,
+    translucent_depth: Res<ExtractedTranslucentDepth>,
+    blend_mode: Res<ExtractedBlendMode>,
+) {
+    let draw_opaque = opaque_3d_draw_functions.read().id::<DrawCustom>();
+    let draw_transparent = transparent_3d_draw_functions.read().id::<DrawCustom>();
+    let quantized = Quantization::new(&quantize, bounds.as_deref()).is_some();
+
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+
+    for (view, mut opaque_phase, mut transparent_phase) in &mut views {
+        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+        let rangefinder = view.rangefinder3d();
+        for (entity, style, on_cpu, translucent) in &material_meshes {
+            let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                continue;
+            };
+            let mesh_key =
+                view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology());
+            let key = CustomPipelineKey {
+                mesh_key,
+                billboard: matches!(style, RenderStyle::Billboard { .. }),
+                // Instances written by the transform shader are never quantized.
+                quantized: quantized && on_cpu,
+                depth_write: false,
+                depth_only: false,
+                additive: false,
+            };
+            let mut specialize = |key| {
+                pipelines
+                    .specialize(&pipeline_cache, &custom_pipeline, key, &mesh.layout)
+                    .unwrap()
+            };
+            // Each cloud is drawn on its own, its instances already being one draw call.
+            let mut add_opaque = |pipeline| {
+                opaque_phase.add(
+                    Opaque3dBinKey {
+                        pipeline,
+                        draw_function: draw_opaque,
+                        asset_id: mesh_instance.mesh_asset_id,
+                        material_bind_group_id: None,
+                        lightmap_image: None,
+                    },
+                    entity,
+                    BinnedRenderPhaseType::UnbatchableMesh,
+                );
+            };
+            // Additive clouds are always blended, and never write depth to avoid hiding their
+            // own points.
+            let additive = blend_mode.0 == BlendMode::Additive;
+            if !translucent && !additive {
+                add_opaque(specialize(key));
+                continue;
+            }
+
+            let mode = translucent_depth.0;
+            if mode == TranslucentDepth::Prepass && !additive {
+                // The blended pass below then only passes the depth test for the nearest
+                // point, whose depth equals the one written here.
+                add_opaque(specialize(CustomPipelineKey {
+                    depth_only: true,
+                    ..key
+                }));
+            }
+            let pipeline = specialize(CustomPipelineKey {
+                mesh_key: mesh_key | MeshPipelineKey::BLEND_ALPHA,
+                depth_write: mode == TranslucentDepth::DepthWrite && !additive,
+                additive,
+                ..key
+            });
+            transparent_phase.add(Transparent3d {
+                entity,
+                pipeline,
+                draw_function: draw_transparent,
+                distance: rangefinder.distance_translation(&mesh_instance.translation),
+                batch_range: 0..1,
+                extra_index: PhaseItemExtraIndex::NONE,
+            });
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct InstanceBuffer {
+    pub(crate) buffer: Buffer,
+    /// Number of instances to draw, the buffer itself may be larger.
+    pub(crate) length: usize,
+}
+
+/// Instance buffers kept alive across frames so they can be written in place rather than
+/// reallocated. Entities in the render world are cleared every frame, so the buffers can't
+/// live on the entity itself.
+#[derive(Resource, Default)]
+struct InstanceBufferCache(EntityHashMap<Buffer>);
+
+fn prepare_instance_buffers(
+    mut commands: Commands,
+    query: Query<(Entity, &InstanceMaterialData)>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut cache: ResMut<InstanceBufferCache>,
+    quantize: Res<QuantizePositions>,
+    lod: Res<PointLod>,
+    bounds: Option<Res<OrbbecBounds>>,
+) {
+    let quantization = Quantization::new(&quantize, bounds.as_deref());
+    let step = lod.step();
+    let mut live = EntityHashSet::default();
+    for (entity, instance_data) in &query {
+        live.insert(entity);
+        let length = instance_data.len().div_ceil(step);
+        let quantized: Vec<QuantizedInstance>;
+        let thinned: Vec<InstanceData>;
+        let contents: &[u8] = match &quantization {
+            Some(quantization) => {
+                quantized = instance_data
+                    .iter()
+                    .step_by(step)
+                    .map(|i| quantization.quantize(i))
+                    .collect();
+                bytemuck::cast_slice(&quantized)
+            }
+            // Every point can be uploaded straight from the shared data, without a copy.
+            None if step == 1 => bytemuck::cast_slice(instance_data.as_slice()),
+            None => {
+                thinned = instance_data.iter().step_by(step).copied().collect();
+                bytemuck::cast_slice(&thinned)
+            }
+        };
+
+        let buffer = match cache.0.get(&entity) {
+            Some(buffer) if buffer.size() >= contents.len() as u64 => buffer.clone(),
+            _ => {
+                // Grow to the next power of two so a slowly growing cloud doesn't reallocate
+                // every frame.
+                let buffer = render_device.create_buffer(&BufferDescriptor {
+                    label: Some("instance data buffer"),
+                    size: (contents.len() as u64)
+                        .next_power_of_two()
+                        .max(COPY_BUFFER_ALIGNMENT),
+                    usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                cache.0.insert(entity, buffer.clone());
+                buffer
+            }
+        };
+        render_queue.write_buffer(&buffer, 0, contents);
+
+        commands
+            .entity(entity)
+            .insert(InstanceBuffer { buffer, length });
+    }
+
+    cache.0.retain(|entity, _| live.contains(entity));
+}
+
+/// The bind group holding the [`CropBox`], [`DequantizeUniform`], [`SizeAttenuationUniform`],
+/// [`GroundPlaneUniform`] and [`FogUniform`] uniforms, and every cloud's [`CloudUniform`],
+/// rewritten every frame.
+#[derive(Resource)]
+struct CloudBindGroup(BindGroup);
+
+/// Per-cloud values, at a dynamic offset in [`CloudBindGroup`].
+#[derive(ShaderType)]
+struct CloudUniform {
+    alpha: f32,
+}
+
+/// The [`CloudUniform`] of every cloud, kept across frames to reuse the buffer.
+#[derive(Resource, Default)]
+struct CloudUniforms(DynamicUniformBuffer<CloudUniform>);
+
+/// Offset of a cloud's [`CloudUniform`] within [`CloudUniforms`].
+#[derive(Component)]
+struct CloudUniformOffset(u32);
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_cloud_uniforms(
+    mut commands: Commands,
+    crop_box: Res<CropBox>,
+    quantize: Res<QuantizePositions>,
+    bounds: Option<Res<OrbbecBounds>>,
+    size_attenuation: Res<ExtractedSizeAttenuation>,
+    ground_plane: Res<ExtractedGroundPlane>,
+    fog: Res<PointFog>,
+    clouds: Query<&InstanceMaterialData>,
+    alphas: Query<(Entity, Option<&PointAlpha>), CloudFilter>,
+    mut cloud_uniforms: ResMut<CloudUniforms>,
+    custom_pipeline: Res<CustomPipeline>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let mut crop_box = UniformBuffer::from(CropBoxUniform::from(*crop_box));
+    crop_box.write_buffer(&render_device, &render_queue);
+
+    let dequantize = match Quantization::new(&quantize, bounds.as_deref()) {
+        Some(quantization) => DequantizeUniform {
+            center: quantization.center,
+            half_extents: quantization.half_extents,
+            instance_scale: clouds
+                .iter()
+                .find_map(|cloud| cloud.first())
+                .map_or(0.0, |instance| instance.scale),
+        },
+        None => DequantizeUniform::default(),
+    };
+    let mut dequantize = UniformBuffer::from(dequantize);
+    dequantize.write_buffer(&render_device, &render_queue);
+
+    let mut size_attenuation = UniformBuffer::from(SizeAttenuationUniform::from(*size_attenuation));
+    size_attenuation.write_buffer(&render_device, &render_queue);
+
+    let mut ground_plane = UniformBuffer::from(ground_plane.0);
+    ground_plane.write_buffer(&render_device, &render_queue);
+
+    let mut fog = UniformBuffer::from(FogUniform::from(*fog));
+    fog.write_buffer(&render_device, &render_queue);
+
+    cloud_uniforms.0.clear();
+    for (entity, alpha) in &alphas {
+        let offset = cloud_uniforms.0.push(&CloudUniform {
+            alpha: alpha.copied().unwrap_or_default().0,
+        });
+        commands.entity(entity).insert(CloudUniformOffset(offset));
+    }
+    cloud_uniforms.0.write_buffer(&render_device, &render_queue);
+
+    let (
+        Some(crop_box),
+        Some(dequantize),
+        Some(size_attenuation),
+        Some(cloud),
+        Some(ground_plane),
+        Some(fog),
+    ) = (
+        crop_box.binding(),
+        dequantize.binding(),
+        size_attenuation.binding(),
+        cloud_uniforms.0.binding(),
+        ground_plane.binding(),
+        fog.binding(),
+    )
+    else {
+        return;
+    };
+    let bind_group = render_device.create_bind_group(
+        "cloud bind group",
+        &custom_pipeline.cloud_layout,
+        &BindGroupEntries::sequential((
+            crop_box,
+            dequantize,
+            size_attenuation,
+            cloud,
+            ground_plane,
+            fog,
+        )),
+    );
+    commands.insert_resource(CloudBindGroup(bind_group));
+}
+
+/// The cloud pipelines are specialized from these shader handles. When a shader is reloaded,
+/// see the `shader_hot_reload` feature, the `PipelineCache` recompiles every pipeline built
+/// from it, or from a shader it imports, under the same id. The ids cached by
+/// `SpecializedMeshPipelines` therefore stay valid and the clouds pick up the change on the
+/// next frame, without re-specializing.
+#[derive(Resource)]
+struct CustomPipeline {
+    shader: Handle<Shader>,
+    billboard_shader: Handle<Shader>,
+    mesh_pipeline: MeshPipeline,
+    /// Layout of [`CloudBindGroup`].
+    cloud_layout: BindGroupLayout,
+}
+
+impl FromWorld for CustomPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let mesh_pipeline = world.resource::<MeshPipeline>();
+        let cloud_layout = world.resource::<RenderDevice>().create_bind_group_layout(
+            "cloud layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::VERTEX,
+                (
+                    uniform_buffer::<CropBoxUniform>(false),
+                    uniform_buffer::<DequantizeUniform>(false),
+                    uniform_buffer::<SizeAttenuationUniform>(false),
+                    uniform_buffer::<CloudUniform>(true),
+                    uniform_buffer::<GroundPlaneUniform>(false),
+                    // The fog is applied per fragment.
+                    uniform_buffer::<FogUniform>(false).visibility(ShaderStages::FRAGMENT),
+                ),
+            ),
+        );
+
+        CustomPipeline {
+            shader: world.load_asset("embedded://bevy_orbbec/shaders/instancing.wgsl"),
+            billboard_shader: world.load_asset("embedded://bevy_orbbec/shaders/billboard.wgsl"),
+            mesh_pipeline: mesh_pipeline.clone(),
+            cloud_layout,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct CustomPipelineKey {
+    mesh_key: MeshPipelineKey,
+    billboard: bool,
+    /// Whether the instance buffer holds [`QuantizedInstance`]s.
+    quantized: bool,
+    /// Write depth despite blending, see [`TranslucentDepth::DepthWrite`].
+    depth_write: bool,
+    /// Write depth only, see [`TranslucentDepth::Prepass`].
+    depth_only: bool,
+    /// Blend additively rather than by alpha, see [`BlendMode::Additive`].
+    additive: bool,
+}
+
+impl SpecializedMeshPipeline for CustomPipeline {
+    type Key = CustomPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayoutRef,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key.mesh_key, layout)?;
+
+        let shader = if key.billboard {
+            self.billboard_shader.clone()
+        } else {
+            self.shader.clone()
+        };
+
+        descriptor.layout.push(self.cloud_layout.clone());
+        descriptor.vertex.shader = shader.clone();
+        let (array_stride, position_format) = if key.quantized {
+            (
+                std::mem::size_of::<QuantizedInstance>(),
+                VertexFormat::Snorm16x4,
+            )
+        } else {
+            (std::mem::size_of::<InstanceData>(), VertexFormat::Float32x4)
+        };
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: array_stride as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                VertexAttribute {
+                    format: position_format,
+                    offset: 0,
+                    shader_location: 3, // shader locations 0-2 are taken up by Position, Normal and UV attributes
+                },
+                VertexAttribute {
+                    format: VertexFormat::Unorm8x4,
+                    offset: position_format.size(),
+                    shader_location: 4,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Snorm8x4,
+                    offset: position_format.size() + VertexFormat::Unorm8x4.size(),
+                    shader_location: 5,
+                },
+            ],
+        });
+        descriptor.fragment.as_mut().unwrap().shader = shader;
+
+        // Point meshes only carry a position, so the shader must not expect normals or UVs.
+        if key.mesh_key.primitive_topology() == PrimitiveTopology::PointList {
+            descriptor.vertex.shader_defs.push("POINT_LIST".into());
+            descriptor
+                .fragment
+                .as_mut()
+                .unwrap()
+                .shader_defs
+                .push("POINT_LIST".into());
+        }
+        if key.quantized {
+            descriptor.vertex.shader_defs.push("QUANTIZED".into());
+            descriptor
+                .fragment
+                .as_mut()
+                .unwrap()
+                .shader_defs
+                .push("QUANTIZED".into());
+        }
+        if key.depth_write || key.depth_only {
+            if let Some(depth_stencil) = descriptor.depth_stencil.as_mut() {
+                depth_stencil.depth_write_enabled = true;
+            }
+        }
+        if key.depth_only || key.additive {
+            for target in descriptor
+                .fragment
+                .as_mut()
+                .unwrap()
+                .targets
+                .iter_mut()
+                .flatten()
+            {
+                if key.additive {
+                    target.blend = Some(ADDITIVE_BLENDING);
+                } else {
+                    target.blend = None;
+                    target.write_mask = ColorWrites::empty();
+                }
+            }
+        }
+
+        Ok(descriptor)
+    }
+}
+
+type DrawCustom = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    SetCloudBindGroup<2>,
+    DrawMeshInstanced,
+);
+
+struct SetCloudBindGroup<const I: usize>;
+
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetCloudBindGroup<I> {
+    type Param = Option<SRes<CloudBindGroup>>;
+    type ViewQuery = ();
+    type ItemQuery = Read<CloudUniformOffset>;
+
+    #[inline]
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        offset: Option<&'w CloudUniformOffset>,
+        bind_group: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (Some(bind_group), Some(offset)) = (bind_group, offset) else {
+            return RenderCommandResult::Failure;
+        };
+        pass.set_bind_group(I, &bind_group.into_inner().0, &[offset.0]);
+        RenderCommandResult::Success
+    }
+}
+
+struct DrawMeshInstanced;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
+    type Param = (SRes<RenderAssets<GpuMesh>>, SRes<RenderMeshInstances>);
+    type ViewQuery = ();
+    type ItemQuery = Read<InstanceBuffer>;
+
+    #[inline]
+    fn render<'w>(
+        item: &P,
+        _view: (),
+        instance_buffer: Option<&'w InstanceBuffer>,
+        (meshes, render_mesh_instances): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(item.entity())
+        else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(instance_buffer) = instance_buffer else {
+            return RenderCommandResult::Failure;
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..instance_buffer.length as u32);
+            }
+            GpuBufferInfo::NonIndexed => {
+                pass.draw(0..gpu_mesh.vertex_count, 0..instance_buffer.length as u32);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
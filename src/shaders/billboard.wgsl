@@ -0,0 +1,175 @@
+#import bevy_pbr::mesh_functions::{get_model_matrix, mesh_position_local_to_world}
+#import bevy_pbr::mesh_view_bindings::view
+#import bevy_pbr::view_transformations::{direction_view_to_world, position_world_to_clip}
+
+// Brightness of points facing away from the camera.
+const AMBIENT: f32 = 0.3;
+
+struct CropBox {
+    min: vec3<f32>,
+    max: vec3<f32>,
+    enabled: u32,
+};
+
+@group(2) @binding(0) var<uniform> crop_box: CropBox;
+
+struct Dequantize {
+    center: vec3<f32>,
+    half_extents: vec3<f32>,
+    instance_scale: f32,
+};
+
+@group(2) @binding(1) var<uniform> dequantize: Dequantize;
+
+struct SizeAttenuation {
+    min_size: f32,
+    max_size: f32,
+    enabled: u32,
+};
+
+@group(2) @binding(2) var<uniform> size_attenuation: SizeAttenuation;
+
+struct Cloud {
+    alpha: f32,
+};
+
+@group(2) @binding(3) var<uniform> cloud: Cloud;
+
+struct GroundPlane {
+    normal: vec3<f32>,
+    offset: f32,
+    threshold: f32,
+    // 0 to leave the plane's inliers alone, 1 to color them and 2 to hide them.
+    mode: u32,
+    color: vec4<f32>,
+};
+
+@group(2) @binding(4) var<uniform> ground_plane: GroundPlane;
+
+struct Fog {
+    color: vec4<f32>,
+    start: f32,
+    end: f32,
+    enabled: u32,
+};
+
+@group(2) @binding(5) var<uniform> fog: Fog;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+
+    @location(3) i_pos_scale: vec4<f32>,
+    @location(4) i_color: vec4<f32>,
+    @location(5) i_normal: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) world_position: vec3<f32>,
+};
+
+// Instance colors are packed as sRGB bytes, while the shader outputs linear color.
+fn srgb_to_linear(color: vec4<f32>) -> vec4<f32> {
+    let rgb = select(
+        pow((color.rgb + 0.055) / 1.055, vec3<f32>(2.4)),
+        color.rgb / 12.92,
+        color.rgb <= vec3<f32>(0.04045)
+    );
+    return vec4<f32>(rgb, color.a);
+}
+
+// Lambert shading lit from the camera, so the shape of the cloud shows from any viewpoint.
+// Normals face the camera that captured the points rather than the view, so either side is
+// lit. Points without a normal are left unlit.
+fn shade(color: vec4<f32>, normal: vec3<f32>, position: vec3<f32>) -> vec4<f32> {
+    if all(normal == vec3<f32>(0.0)) {
+        return color;
+    }
+    let to_camera = normalize(view.world_position - position);
+    let lambert = abs(dot(normalize(normal), to_camera));
+    return vec4<f32>(color.rgb * mix(AMBIENT, 1.0, lambert), color.a);
+}
+
+// Blends the color toward the fog's with the distance from the view.
+fn apply_fog(color: vec4<f32>, world_position: vec3<f32>) -> vec4<f32> {
+    if fog.enabled == 0u {
+        return color;
+    }
+    let distance = length(view.world_position - world_position);
+    let amount = clamp((distance - fog.start) / (fog.end - fog.start), 0.0, 1.0);
+    return vec4<f32>(mix(color.rgb, fog.color.rgb, amount), color.a);
+}
+
+// Position and scale of the instance. Quantized positions are normalized within the cloud's
+// bounds, and share a single scale.
+fn instance_pos_scale(i_pos_scale: vec4<f32>) -> vec4<f32> {
+#ifdef QUANTIZED
+    return vec4<f32>(
+        dequantize.center + i_pos_scale.xyz * dequantize.half_extents,
+        dequantize.instance_scale
+    );
+#else
+    return i_pos_scale;
+#endif
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    // NOTE: As in instancing.wgsl, passing 0 as the instance_index to get_model_matrix() is a
+    // hack as the instance_index builtin would map to the wrong index in the Mesh array.
+    let pos_scale = instance_pos_scale(vertex.i_pos_scale);
+    let center = mesh_position_local_to_world(
+        get_model_matrix(0u),
+        vec4<f32>(pos_scale.xyz, 1.0)
+    );
+
+    // Expand the quad along the camera's right and up axes so it always faces the view.
+    let right = direction_view_to_world(vec3<f32>(1.0, 0.0, 0.0));
+    let up = direction_view_to_world(vec3<f32>(0.0, 1.0, 0.0));
+    var size = pos_scale.w;
+    if size_attenuation.enabled != 0u {
+        // Size of the billboard on screen, in pixels, found by projecting its height.
+        let bottom = position_world_to_clip(center.xyz);
+        let top = position_world_to_clip(center.xyz + up * size);
+        let pixels = length((top.xy / top.w - bottom.xy / bottom.w) * view.viewport.zw * 0.5);
+        let clamped = clamp(pixels, size_attenuation.min_size, size_attenuation.max_size);
+        size *= clamped / max(pixels, 1e-6);
+    }
+    let offset = (right * vertex.position.x + up * vertex.position.y) * size;
+
+    var out: VertexOutput;
+    out.clip_position = position_world_to_clip(center.xyz + offset);
+    // A zero scale marks points hidden by the transform shader, see `gpu_transform.rs`.
+    let cropped = crop_box.enabled != 0u
+        && any((center.xyz < crop_box.min) | (center.xyz > crop_box.max));
+    // The plane is in the cloud's space, like the instance positions.
+    let on_ground = ground_plane.mode != 0u
+        && abs(dot(ground_plane.normal, pos_scale.xyz) - ground_plane.offset)
+            <= ground_plane.threshold;
+    if cropped || pos_scale.w == 0.0 || (on_ground && ground_plane.mode == 2u) {
+        // Outside the clip volume, so the whole quad is clipped away.
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+    let normal = (get_model_matrix(0u) * vec4<f32>(vertex.i_normal.xyz, 0.0)).xyz;
+    var color = srgb_to_linear(vertex.i_color) * vec4<f32>(1.0, 1.0, 1.0, cloud.alpha);
+    if on_ground && ground_plane.mode == 1u {
+        color = vec4<f32>(ground_plane.color.rgb, color.a);
+    }
+    out.color = shade(color, normal, center.xyz);
+    out.uv = vertex.uv;
+    out.world_position = center.xyz + offset;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Round off the corners of the quad.
+    if length(in.uv - vec2<f32>(0.5)) > 0.5 {
+        discard;
+    }
+    return apply_fog(in.color, in.world_position);
+}
@@ -0,0 +1,50 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+struct EyeDomeLighting {
+    strength: f32,
+    radius: f32,
+};
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+#ifdef MULTISAMPLED
+@group(0) @binding(1) var depth_texture: texture_depth_multisampled_2d;
+#else
+@group(0) @binding(1) var depth_texture: texture_depth_2d;
+#endif
+@group(0) @binding(2) var<uniform> settings: EyeDomeLighting;
+
+const NEIGHBORS: u32 = 8u;
+const PI: f32 = 3.141592653589793;
+// Scales the summed depth differences, which are small, into a visible darkening.
+const RESPONSE_SCALE: f32 = 100.0;
+
+// Bevy's reversed, infinite projection makes the stored depth near / linear depth, so
+// -log2(depth) is the log of the linear depth up to a constant that cancels out in
+// differences.
+fn log_depth(coord: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(depth_texture));
+    let depth = textureLoad(depth_texture, clamp(coord, vec2<i32>(0), size - 1), 0);
+    // The background is cleared to 0, which is treated as very far away.
+    return -log2(max(depth, 1e-30));
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.position.xy);
+    let color = textureLoad(screen_texture, coord, 0);
+    if textureLoad(depth_texture, coord, 0) == 0.0 {
+        return color;
+    }
+
+    // Sum how much closer each neighbor is. Pixels next to a closer surface, i.e. on the far
+    // side of a silhouette, are darkened.
+    let center = log_depth(coord);
+    var response = 0.0;
+    for (var i = 0u; i < NEIGHBORS; i++) {
+        let angle = 2.0 * PI * f32(i) / f32(NEIGHBORS);
+        let offset = vec2<i32>(round(vec2<f32>(cos(angle), sin(angle)) * settings.radius));
+        response += max(0.0, center - log_depth(coord + offset));
+    }
+    let shade = exp(-response / f32(NEIGHBORS) * settings.strength * RESPONSE_SCALE);
+    return vec4<f32>(color.rgb * shade, color.a);
+}
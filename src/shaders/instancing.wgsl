@@ -0,0 +1,152 @@
+#import bevy_pbr::mesh_functions::{get_model_matrix, mesh_position_local_to_world}
+#import bevy_pbr::mesh_view_bindings::view
+#import bevy_pbr::view_transformations::position_world_to_clip
+
+// Brightness of points facing away from the camera.
+const AMBIENT: f32 = 0.3;
+
+struct CropBox {
+    min: vec3<f32>,
+    max: vec3<f32>,
+    enabled: u32,
+};
+
+@group(2) @binding(0) var<uniform> crop_box: CropBox;
+
+struct Dequantize {
+    center: vec3<f32>,
+    half_extents: vec3<f32>,
+    instance_scale: f32,
+};
+
+@group(2) @binding(1) var<uniform> dequantize: Dequantize;
+
+struct Cloud {
+    alpha: f32,
+};
+
+@group(2) @binding(3) var<uniform> cloud: Cloud;
+
+struct GroundPlane {
+    normal: vec3<f32>,
+    offset: f32,
+    threshold: f32,
+    // 0 to leave the plane's inliers alone, 1 to color them and 2 to hide them.
+    mode: u32,
+    color: vec4<f32>,
+};
+
+@group(2) @binding(4) var<uniform> ground_plane: GroundPlane;
+
+struct Fog {
+    color: vec4<f32>,
+    start: f32,
+    end: f32,
+    enabled: u32,
+};
+
+@group(2) @binding(5) var<uniform> fog: Fog;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+#ifndef POINT_LIST
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+#endif
+
+    @location(3) i_pos_scale: vec4<f32>,
+    @location(4) i_color: vec4<f32>,
+    @location(5) i_normal: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) world_position: vec3<f32>,
+};
+
+// Instance colors are packed as sRGB bytes, while the shader outputs linear color.
+fn srgb_to_linear(color: vec4<f32>) -> vec4<f32> {
+    let rgb = select(
+        pow((color.rgb + 0.055) / 1.055, vec3<f32>(2.4)),
+        color.rgb / 12.92,
+        color.rgb <= vec3<f32>(0.04045)
+    );
+    return vec4<f32>(rgb, color.a);
+}
+
+// Lambert shading lit from the camera, so the shape of the cloud shows from any viewpoint.
+// Normals face the camera that captured the points rather than the view, so either side is
+// lit. Points without a normal are left unlit.
+fn shade(color: vec4<f32>, normal: vec3<f32>, position: vec3<f32>) -> vec4<f32> {
+    if all(normal == vec3<f32>(0.0)) {
+        return color;
+    }
+    let to_camera = normalize(view.world_position - position);
+    let lambert = abs(dot(normalize(normal), to_camera));
+    return vec4<f32>(color.rgb * mix(AMBIENT, 1.0, lambert), color.a);
+}
+
+// Blends the color toward the fog's with the distance from the view.
+fn apply_fog(color: vec4<f32>, world_position: vec3<f32>) -> vec4<f32> {
+    if fog.enabled == 0u {
+        return color;
+    }
+    let distance = length(view.world_position - world_position);
+    let amount = clamp((distance - fog.start) / (fog.end - fog.start), 0.0, 1.0);
+    return vec4<f32>(mix(color.rgb, fog.color.rgb, amount), color.a);
+}
+
+// Position and scale of the instance. Quantized positions are normalized within the cloud's
+// bounds, and share a single scale.
+fn instance_pos_scale(i_pos_scale: vec4<f32>) -> vec4<f32> {
+#ifdef QUANTIZED
+    return vec4<f32>(
+        dequantize.center + i_pos_scale.xyz * dequantize.half_extents,
+        dequantize.instance_scale
+    );
+#else
+    return i_pos_scale;
+#endif
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let pos_scale = instance_pos_scale(vertex.i_pos_scale);
+    let position = vertex.position * pos_scale.w + pos_scale.xyz;
+    var out: VertexOutput;
+    // NOTE: Passing 0 as the instance_index to get_model_matrix() is a hack
+    // for this example as the instance_index builtin would map to the wrong
+    // index in the Mesh array. This index could be passed in via another
+    // uniform instead but it's unnecessary for the example.
+    let model = get_model_matrix(0u);
+    let world = mesh_position_local_to_world(model, vec4<f32>(position, 1.0));
+    out.clip_position = position_world_to_clip(world.xyz);
+
+    // Test the point itself rather than the vertex, so cubes are dropped whole.
+    let center = mesh_position_local_to_world(model, vec4<f32>(pos_scale.xyz, 1.0));
+    // A zero scale marks points hidden by the transform shader, see `gpu_transform.rs`.
+    let cropped = crop_box.enabled != 0u
+        && any((center.xyz < crop_box.min) | (center.xyz > crop_box.max));
+    // The plane is in the cloud's space, like the instance positions.
+    let on_ground = ground_plane.mode != 0u
+        && abs(dot(ground_plane.normal, pos_scale.xyz) - ground_plane.offset)
+            <= ground_plane.threshold;
+    if cropped || pos_scale.w == 0.0 || (on_ground && ground_plane.mode == 2u) {
+        // Outside the clip volume, so the whole primitive is clipped away.
+        out.clip_position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+    let normal = (model * vec4<f32>(vertex.i_normal.xyz, 0.0)).xyz;
+    var color = srgb_to_linear(vertex.i_color) * vec4<f32>(1.0, 1.0, 1.0, cloud.alpha);
+    if on_ground && ground_plane.mode == 1u {
+        color = vec4<f32>(ground_plane.color.rgb, color.a);
+    }
+    out.color = shade(color, normal, center.xyz);
+    out.world_position = world.xyz;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return apply_fog(in.color, in.world_position);
+}
@@ -0,0 +1,83 @@
+// Converts the raw points of a frame into `InstanceData`, see `gpu_transform.rs`.
+
+struct Params {
+    transform: mat4x4<f32>,
+    scale: vec3<f32>,
+    instance_scale: f32,
+    depth_range: vec2<f32>,
+    depth_colormap_range: vec2<f32>,
+    height_colormap_range: vec2<f32>,
+    color_mode: u32,
+    colored: u32,
+    offset: u32,
+    count: u32,
+};
+
+const COLOR_MODE_RGB: u32 = 0u;
+const COLOR_MODE_HEIGHT: u32 = 2u;
+
+@group(0) @binding(0) var<uniform> params: Params;
+// Positions in millimeters as float bits, each followed by RGBA bytes when colored. Read as
+// words so the color bits aren't reinterpreted as a float.
+@group(0) @binding(1) var<storage, read> points: array<u32>;
+// `InstanceData`: position, scale, sRGBA bytes and normal, six words each.
+@group(0) @binding(2) var<storage, read_write> instances: array<u32>;
+
+// Google's Turbo colormap, matching `colormap::turbo`.
+fn turbo(t: f32) -> vec3<f32> {
+    let x = clamp(t, 0.0, 1.0);
+    let v4 = vec4<f32>(1.0, x, x * x, x * x * x);
+    let v2 = v4.zw * v4.z;
+    let color = vec3<f32>(
+        dot(v4, vec4<f32>(0.1357214, 4.615393, -42.66032, 132.1311))
+            + dot(v2, vec2<f32>(-152.9424, 59.28638)),
+        dot(v4, vec4<f32>(0.09140261, 2.194188, 4.842967, -14.18503))
+            + dot(v2, vec2<f32>(4.277299, 2.829566)),
+        dot(v4, vec4<f32>(0.1066733, 12.64195, -60.58205, 110.3628))
+            + dot(v2, vec2<f32>(-89.90311, 27.34825)),
+    );
+    return clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn colormap(value: f32, range: vec2<f32>) -> u32 {
+    return pack4x8unorm(vec4<f32>(turbo((value - range.x) / (range.y - range.x)), 1.0));
+}
+
+@compute @workgroup_size(64)
+fn transform(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.count {
+        return;
+    }
+
+    let stride = select(3u, 4u, params.colored != 0u);
+    let first = index * stride;
+    let position = vec3<f32>(
+        bitcast<f32>(points[first]),
+        bitcast<f32>(points[first + 1u]),
+        bitcast<f32>(points[first + 2u]),
+    );
+    let world = (params.transform * vec4<f32>(position * params.scale, 1.0)).xyz;
+
+    var color: u32;
+    if params.color_mode == COLOR_MODE_HEIGHT {
+        color = colormap(world.y, params.height_colormap_range);
+    } else if params.color_mode == COLOR_MODE_RGB && params.colored != 0u {
+        color = points[first + 3u];
+    } else {
+        color = colormap(length(position), params.depth_colormap_range);
+    }
+
+    // A zero scale hides the instance, see the instancing shaders.
+    let in_range = position.z >= params.depth_range.x && position.z <= params.depth_range.y;
+    let scale = select(0.0, params.instance_scale, in_range);
+
+    let out = (params.offset + index) * 6u;
+    instances[out] = bitcast<u32>(world.x);
+    instances[out + 1u] = bitcast<u32>(world.y);
+    instances[out + 2u] = bitcast<u32>(world.z);
+    instances[out + 3u] = bitcast<u32>(scale);
+    instances[out + 4u] = color;
+    // No normal, so the point is drawn unlit.
+    instances[out + 5u] = 0u;
+}
@@ -0,0 +1,175 @@
+//! Frame rate and latency measurements for the point cloud stream.
+
+use crate::frame::PointCloudReceived;
+use crate::orbbec::OrbbecRx;
+use bevy::prelude::*;
+use std::time::{Duration, SystemTime};
+
+/// How often the rates in [`OrbbecStats`] are recomputed, in seconds.
+const WINDOW_SECS: f32 = 1.0;
+
+/// Throughput and latency of the point cloud stream, updated by [`OrbbecStatsPlugin`].
+///
+/// Comparing the rates shows where frames are lost: `capture_fps` above `receive_fps` means
+/// the capture thread is dropping frames the app didn't pick up in time (see
+/// [`OrbbecConfig::drop_policy`]), and `receive_fps` above `display_fps` means several clouds
+/// arrive per app frame and all but the last are never displayed.
+///
+/// The windowed rates jump each time they are recomputed and the point count varies from one
+/// cloud to the next, so smoothed values updated with every cloud are kept alongside. Prefer
+/// those for display or for logic adapting to the stream.
+///
+/// [`OrbbecConfig::drop_policy`]: crate::OrbbecConfig::drop_policy
+#[derive(Resource, Debug, Clone)]
+pub struct OrbbecStats {
+    /// Point clouds generated by the capture thread per second.
+    pub capture_fps: f32,
+    /// Point clouds received by the app per second.
+    pub receive_fps: f32,
+    /// App frames per second in which a new point cloud was displayed.
+    pub display_fps: f32,
+    /// Average number of points in the clouds received during the last window.
+    pub average_point_count: f32,
+    /// Number of points in the most recent point cloud.
+    pub point_count: usize,
+    /// [`Self::point_count`] smoothed with an exponential moving average.
+    pub smoothed_point_count: f32,
+    /// Point clouds received by the app per second, smoothed with an exponential moving
+    /// average as they arrive rather than averaged over a window like [`Self::receive_fps`].
+    pub smoothed_fps: f32,
+    /// App frames per second, whether or not a point cloud arrived, smoothed with an
+    /// exponential moving average every frame.
+    pub app_fps: f32,
+    /// How much of the previous smoothed values is kept when a point cloud arrives, in
+    /// `0.0..1.0`. Higher values are steadier but slower to follow changes. Defaults to `0.9`.
+    pub smoothing: f32,
+    /// Time since the capture thread received the most recent point cloud.
+    pub latest_frame_age: Duration,
+    /// Frames the device dropped since capture started, see [`OrbbecRx::frames_dropped`]. A
+    /// growing count usually means the USB link can't keep up, and a lower resolution or
+    /// frame rate is needed.
+    pub dropped_frames: u64,
+}
+
+impl Default for OrbbecStats {
+    fn default() -> Self {
+        Self {
+            capture_fps: 0.0,
+            receive_fps: 0.0,
+            display_fps: 0.0,
+            average_point_count: 0.0,
+            point_count: 0,
+            smoothed_point_count: 0.0,
+            smoothed_fps: 0.0,
+            app_fps: 0.0,
+            smoothing: 0.9,
+            latest_frame_age: Duration::ZERO,
+            dropped_frames: 0,
+        }
+    }
+}
+
+impl OrbbecStats {
+    /// Blends `value` into the moving average `average` by [`Self::smoothing`], starting from
+    /// `value` itself when there is no average yet.
+    fn smooth(&self, average: f32, value: f32) -> f32 {
+        if average == 0.0 {
+            value
+        } else {
+            average * self.smoothing + value * (1.0 - self.smoothing)
+        }
+    }
+}
+
+/// Keeps [`OrbbecStats`] up to date from the [`PointCloudReceived`] events.
+pub struct OrbbecStatsPlugin;
+
+impl Plugin for OrbbecStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OrbbecStats>()
+            .add_systems(Update, update_stats);
+    }
+}
+
+/// Counts accumulated since the rates were last computed.
+#[derive(Default)]
+struct StatsWindow {
+    elapsed: f32,
+    frames_captured: u64,
+    received: u32,
+    displayed: u32,
+    points: usize,
+    latest: Option<SystemTime>,
+}
+
+fn update_stats(
+    time: Res<Time>,
+    orbbec: Res<OrbbecRx>,
+    mut frames: EventReader<PointCloudReceived>,
+    mut stats: ResMut<OrbbecStats>,
+    mut window: Local<StatsWindow>,
+    mut last_received: Local<Option<Duration>>,
+) {
+    let mut received = 0;
+    for PointCloudReceived(frame) in frames.read() {
+        window.received += 1;
+        window.points += frame.points.len();
+        window.latest = Some(frame.system_time);
+        stats.point_count = frame.points.len();
+        stats.smoothed_point_count =
+            stats.smooth(stats.smoothed_point_count, frame.points.len() as f32);
+        received += 1;
+    }
+    // Only the last cloud of each app frame is displayed.
+    if received > 0 {
+        window.displayed += 1;
+
+        // Clouds arriving in the same app frame are spread over the time since the last ones.
+        let now = time.elapsed();
+        if let Some(interval) = last_received.map(|last| (now - last).as_secs_f32()) {
+            if interval > 0.0 {
+                stats.smoothed_fps = stats.smooth(stats.smoothed_fps, received as f32 / interval);
+            }
+        }
+        *last_received = Some(now);
+    }
+
+    if time.delta_seconds() > 0.0 {
+        stats.app_fps = stats.smooth(stats.app_fps, 1.0 / time.delta_seconds());
+    }
+
+    if let Some(latest) = window.latest {
+        stats.latest_frame_age = SystemTime::now().duration_since(latest).unwrap_or_default();
+    }
+
+    window.elapsed += time.delta_seconds();
+    if window.elapsed < WINDOW_SECS {
+        return;
+    }
+
+    let dropped_frames = orbbec.frames_dropped();
+    if dropped_frames > stats.dropped_frames {
+        warn!(
+            "The device dropped {} frames in the last {:.1}s, try a lower resolution or frame rate",
+            dropped_frames - stats.dropped_frames,
+            window.elapsed
+        );
+    }
+    stats.dropped_frames = dropped_frames;
+
+    let frames_captured = orbbec.frames_captured();
+    stats.capture_fps = (frames_captured - window.frames_captured) as f32 / window.elapsed;
+    stats.receive_fps = window.received as f32 / window.elapsed;
+    stats.display_fps = window.displayed as f32 / window.elapsed;
+    stats.average_point_count = if window.received > 0 {
+        window.points as f32 / window.received as f32
+    } else {
+        0.0
+    };
+
+    *window = StatsWindow {
+        frames_captured,
+        latest: window.latest,
+        ..default()
+    };
+}
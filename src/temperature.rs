@@ -0,0 +1,70 @@
+//! Device temperatures, for watching a long-running capture for overheating.
+
+use crate::orbbec::OrbbecRx;
+use bevy::prelude::*;
+
+/// The latest temperatures read from the camera, in degrees Celsius. Polled every few seconds
+/// by the capture thread, and only inserted once a reading has succeeded, which requires a
+/// device that reports its temperature.
+///
+/// Each channel is `None` when the device doesn't report it.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+pub struct OrbbecTemperature {
+    pub cpu: Option<f32>,
+    pub ir: Option<f32>,
+    pub ir_left: Option<f32>,
+    pub ir_right: Option<f32>,
+    /// The laser projector.
+    pub laser: Option<f32>,
+    pub main_board: Option<f32>,
+    /// The thermoelectric cooler.
+    pub tec: Option<f32>,
+    pub imu: Option<f32>,
+    pub color: Option<f32>,
+    pub chip_top: Option<f32>,
+    pub chip_bottom: Option<f32>,
+}
+
+impl OrbbecTemperature {
+    /// The hottest channel the device reports.
+    pub fn max(&self) -> Option<f32> {
+        [
+            self.cpu,
+            self.ir,
+            self.ir_left,
+            self.ir_right,
+            self.laser,
+            self.main_board,
+            self.tec,
+            self.imu,
+            self.color,
+            self.chip_top,
+            self.chip_bottom,
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(f32::max)
+    }
+}
+
+/// Keeps the [`OrbbecTemperature`] resource up to date with the readings from [`OrbbecRx`].
+pub struct OrbbecTemperaturePlugin;
+
+impl Plugin for OrbbecTemperaturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, update_temperature);
+    }
+}
+
+fn update_temperature(
+    mut commands: Commands,
+    orbbec: Res<OrbbecRx>,
+    temperature: Option<Res<OrbbecTemperature>>,
+) {
+    let Some(reading) = orbbec.temperature() else {
+        return;
+    };
+    if temperature.map_or(true, |temperature| *temperature != reading) {
+        commands.insert_resource(reading);
+    }
+}
@@ -0,0 +1,143 @@
+//! Keeping recent clouds on screen to visualize motion.
+
+use crate::orbbec::RenderStyle;
+use crate::render::{InstanceData, InstanceMaterialData, PointAlpha, TranslucentPoints};
+use crate::PointCloudSource;
+use bevy::{prelude::*, render::view::NoFrustumCulling, utils::HashMap};
+use std::sync::Arc;
+
+/// How many previous clouds [`OrbbecTrailPlugin`] keeps and how they fade.
+#[derive(Resource, Debug, Clone)]
+pub struct PointTrail {
+    /// How many previous clouds to draw behind the live one, `0` disables the trail.
+    pub trail_frames: usize,
+    /// Opacity of each cloud relative to the next newer one, in `0.0..=1.0`. The cloud `n`
+    /// frames old is drawn with an alpha of `fade` to the power of `n`.
+    pub fade: f32,
+    /// Most points kept across the whole trail, including the trail of every cloud with
+    /// [`OrbbecConfig::separate_clouds`](crate::OrbbecConfig::separate_clouds), bounding its
+    /// memory use. The oldest clouds are dropped to stay under it.
+    pub max_points: usize,
+}
+
+impl Default for PointTrail {
+    fn default() -> Self {
+        Self {
+            trail_frames: 8,
+            fade: 0.6,
+            max_points: 2_000_000,
+        }
+    }
+}
+
+/// A previous cloud kept by [`OrbbecTrailPlugin`]. Drawn like the live cloud, as its own
+/// entity with [`InstanceMaterialData`], so exclude it from queries meant for the live cloud.
+/// Has the [`PointCloudSource`] of the cloud it came from, if any.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct TrailFrame {
+    /// How many clouds ago this one was live, starting from `1`.
+    pub age: usize,
+}
+
+/// Draws the last [`PointTrail::trail_frames`] clouds behind each live one, fading with age.
+/// Each cloud keeps sharing its points with the entity it came from, so the trail costs no
+/// copies, only the GPU memory of its instance buffers. Has no effect on clouds converted by
+/// the `gpu_transform` feature.
+pub struct OrbbecTrailPlugin;
+
+impl Plugin for OrbbecTrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PointTrail>()
+            .add_systems(Update, update_trail);
+    }
+}
+
+type LiveCloud<'a> = (
+    Ref<'a, InstanceMaterialData>,
+    &'a Handle<Mesh>,
+    &'a RenderStyle,
+    &'a Transform,
+    Option<&'a PointCloudSource>,
+);
+
+type PreviousCloud<'a> = (
+    Entity,
+    &'a mut TrailFrame,
+    &'a mut PointAlpha,
+    &'a InstanceMaterialData,
+    Option<&'a PointCloudSource>,
+);
+
+fn update_trail(
+    mut commands: Commands,
+    trail: Res<PointTrail>,
+    clouds: Query<LiveCloud, Without<TrailFrame>>,
+    mut frames: Query<PreviousCloud>,
+    mut previous: Local<HashMap<Option<PointCloudSource>, Arc<Vec<InstanceData>>>>,
+) {
+    // Clouds that were cleared don't come back as a trail with the next ones.
+    previous.retain(|source, _| {
+        clouds
+            .iter()
+            .any(|(.., cloud_source)| cloud_source.copied() == *source)
+    });
+
+    // The clouds that were live until now join their trail, which ages by one.
+    let mut received = Vec::new();
+    let mut joining = Vec::new();
+    for (cloud, mesh, style, transform, source) in &clouds {
+        if !cloud.is_changed() {
+            continue;
+        }
+        let source = source.copied();
+        received.push(source);
+        if let Some(points) = previous.insert(source, cloud.0.clone()) {
+            joining.push((points, mesh, style, transform, source));
+        }
+    }
+    if received.is_empty() && !trail.is_changed() {
+        return;
+    }
+
+    let mut frames: Vec<_> = frames.iter_mut().collect();
+    for (_, frame, .., source) in &mut frames {
+        if received.contains(&source.copied()) {
+            frame.age += 1;
+        }
+    }
+    frames.sort_by_key(|(_, frame, ..)| frame.age);
+
+    // Keep frames from newest to oldest until one doesn't fit, then drop the rest.
+    let mut budget = trail.max_points;
+    let mut full = false;
+    for (points, mesh, style, transform, source) in joining {
+        if full || trail.trail_frames == 0 || points.len() > budget {
+            full = true;
+            continue;
+        }
+        budget -= points.len();
+        let mut frame = commands.spawn((
+            mesh.clone(),
+            SpatialBundle::from_transform(*transform),
+            InstanceMaterialData(points),
+            *style,
+            TrailFrame { age: 1 },
+            PointAlpha(trail.fade),
+            TranslucentPoints,
+            // See the note where the live cloud is spawned.
+            NoFrustumCulling,
+        ));
+        if let Some(source) = source {
+            frame.insert(source);
+        }
+    }
+    for (entity, frame, mut alpha, points, _) in frames {
+        if full || frame.age > trail.trail_frames || points.len() > budget {
+            full = true;
+            commands.entity(entity).despawn();
+            continue;
+        }
+        budget -= points.len();
+        alpha.0 = trail.fade.powi(frame.age as i32);
+    }
+}